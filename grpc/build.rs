@@ -1,4 +1,7 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::compile_protos("proto/greetings.proto")?;
+    tonic_build::compile_protos("proto/storage.proto")?;
+    tonic_build::compile_protos("proto/replication.proto")?;
+    tonic_build::compile_protos("proto/raft.proto")?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -1,4 +1,7 @@
 mod greetings_server;
+mod replication_server;
+mod raft_server;
+mod storage_conversion;
 
 fn main() {
     println!("Hello, world!");
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+
+use tokio::runtime::Handle;
+use tonic::{Request, Response, Status};
+use tonic::transport::{Channel, Endpoint};
+
+use language::raft::raft_log::RaftEntry;
+use language::raft::raft_message::{NodeId, RaftMessage};
+use language::raft::raft_server::RaftInput;
+use language::raft::raft_transport::RaftTransport;
+
+use crate::raft_server::mod_raft::raft_client::RaftClient;
+use crate::raft_server::mod_raft::raft_envelope::Message;
+use crate::raft_server::mod_raft::raft_server::Raft;
+//the generated raft messages refer to the shared messages by their package, as super::storage
+use crate::storage_conversion::mod_storage as storage;
+use crate::storage_conversion::{from_proto_command, to_proto_command};
+
+pub mod mod_raft {
+    tonic::include_proto!("raft"); //package name
+}
+
+//hands the messages of other nodes to the inbox of the local raft server
+pub struct RaftPeerService {
+    inbox: Mutex<Sender<RaftInput>>,
+}
+
+impl RaftPeerService {
+    pub fn new(inbox: Sender<RaftInput>) -> RaftPeerService {
+        return RaftPeerService { inbox: Mutex::new(inbox) };
+    }
+}
+
+#[tonic::async_trait]
+impl Raft for RaftPeerService {
+    async fn send(&self, request: Request<mod_raft::RaftEnvelope>) -> Result<Response<mod_raft::Empty>, Status> {
+        let (from, message) = from_proto_envelope(request.into_inner()).map_err(Status::invalid_argument)?;
+        self.inbox
+            .lock()
+            .unwrap()
+            .send(RaftInput::Message { from, message })
+            .map_err(|_| Status::unavailable("raft server is stopped"))?;
+        return Ok(Response::new(mod_raft::Empty {}));
+    }
+}
+
+//the raft runner thread must not wait on the network, so every message is sent from a task of the runtime
+pub struct GrpcRaftTransport {
+    peers: HashMap<NodeId, RaftClient<Channel>>,
+    runtime: Handle,
+}
+
+impl GrpcRaftTransport {
+    pub fn new(peers: HashMap<NodeId, String>) -> Result<GrpcRaftTransport, tonic::transport::Error> {
+        let mut clients = HashMap::with_capacity(peers.len());
+        for (id, address) in peers {
+            let channel = Endpoint::from_shared(address)?.connect_lazy();
+            clients.insert(id, RaftClient::new(channel));
+        }
+        return Ok(GrpcRaftTransport { peers: clients, runtime: Handle::current() });
+    }
+}
+
+impl RaftTransport for GrpcRaftTransport {
+    fn send(&self, from: NodeId, to: NodeId, message: RaftMessage) {
+        if let Some(client) = self.peers.get(&to) {
+            let mut client = client.clone();
+            let envelope = to_proto_envelope(from, message);
+            self.runtime.spawn(async move {
+                let _ = client.send(Request::new(envelope)).await;
+            });
+        }
+    }
+}
+
+fn to_proto_entry(entry: RaftEntry) -> mod_raft::RaftEntry {
+    return mod_raft::RaftEntry {
+        index: entry.index,
+        term: entry.term,
+        command: entry.command.map(to_proto_command),
+    };
+}
+
+fn from_proto_entry(entry: mod_raft::RaftEntry) -> Result<RaftEntry, String> {
+    //the entry a leader appends when it gets elected carries no command
+    let command = entry.command.map(from_proto_command).transpose()?;
+    return Ok(RaftEntry { index: entry.index, term: entry.term, command });
+}
+
+fn to_proto_envelope(from: NodeId, message: RaftMessage) -> mod_raft::RaftEnvelope {
+    let message = match message {
+        RaftMessage::RequestVote { term, last_log_index, last_log_term } => {
+            Message::RequestVote(mod_raft::RequestVote { term, last_log_index, last_log_term })
+        }
+        RaftMessage::RequestVoteResponse { term, vote_granted } => {
+            Message::RequestVoteResponse(mod_raft::RequestVoteResponse { term, vote_granted })
+        }
+        RaftMessage::AppendEntries { term, prev_log_index, prev_log_term, entries, leader_commit } => {
+            Message::AppendEntries(mod_raft::AppendEntries {
+                term,
+                prev_log_index,
+                prev_log_term,
+                entries: entries.into_iter().map(to_proto_entry).collect(),
+                leader_commit,
+            })
+        }
+        RaftMessage::AppendEntriesResponse { term, success, match_index } => {
+            Message::AppendEntriesResponse(mod_raft::AppendEntriesResponse { term, success, match_index })
+        }
+        RaftMessage::InstallSnapshot { term, last_included_index, last_included_term, data } => {
+            Message::InstallSnapshot(mod_raft::InstallSnapshot {
+                term,
+                last_included_index,
+                last_included_term,
+                data: data
+                    .into_iter()
+                    .map(|(key, value, version)| mod_raft::KeyValue { key, deleted: value.is_none(), value: value.unwrap_or_default(), version })
+                    .collect(),
+            })
+        }
+        RaftMessage::InstallSnapshotResponse { term, match_index } => {
+            Message::InstallSnapshotResponse(mod_raft::InstallSnapshotResponse { term, match_index })
+        }
+    };
+    return mod_raft::RaftEnvelope { from, message: Some(message) };
+}
+
+fn from_proto_envelope(envelope: mod_raft::RaftEnvelope) -> Result<(NodeId, RaftMessage), String> {
+    let message = match envelope.message {
+        Some(Message::RequestVote(vote)) => RaftMessage::RequestVote {
+            term: vote.term,
+            last_log_index: vote.last_log_index,
+            last_log_term: vote.last_log_term,
+        },
+        Some(Message::RequestVoteResponse(response)) => RaftMessage::RequestVoteResponse {
+            term: response.term,
+            vote_granted: response.vote_granted,
+        },
+        Some(Message::AppendEntries(append)) => RaftMessage::AppendEntries {
+            term: append.term,
+            prev_log_index: append.prev_log_index,
+            prev_log_term: append.prev_log_term,
+            entries: append.entries.into_iter().map(from_proto_entry).collect::<Result<Vec<RaftEntry>, String>>()?,
+            leader_commit: append.leader_commit,
+        },
+        Some(Message::AppendEntriesResponse(response)) => RaftMessage::AppendEntriesResponse {
+            term: response.term,
+            success: response.success,
+            match_index: response.match_index,
+        },
+        Some(Message::InstallSnapshot(snapshot)) => RaftMessage::InstallSnapshot {
+            term: snapshot.term,
+            last_included_index: snapshot.last_included_index,
+            last_included_term: snapshot.last_included_term,
+            data: snapshot.data
+                .into_iter()
+                .map(|key_value| (key_value.key, (!key_value.deleted).then_some(key_value.value), key_value.version))
+                .collect(),
+        },
+        Some(Message::InstallSnapshotResponse(response)) => RaftMessage::InstallSnapshotResponse {
+            term: response.term,
+            match_index: response.match_index,
+        },
+        None => return Err(format!("message from {} has no content", envelope.from)),
+    };
+    return Ok((envelope.from, message));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::mpsc;
+    use tonic::transport::Server;
+
+    use language::raft::raft_config::RaftConfig;
+    use language::raft::raft_handler::RaftHandler;
+    use language::raft::raft_node::{RaftNode, Role};
+    use language::raft::raft_server::RaftServer;
+    use language::singular_update_queue::command::CommandHandler;
+    use language::singular_update_queue::storage_command::{InMemoryStorageHandler, Status as CommandStatus, Storage, StorageCommand};
+    use language::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+
+    use crate::raft_server::mod_raft::raft_server::RaftServer as RaftGrpcServer;
+
+    use super::*;
+
+    #[test]
+    fn test_convert_messages() {
+        let messages = vec![
+            RaftMessage::RequestVote { term: 2, last_log_index: 3, last_log_term: 1 },
+            RaftMessage::RequestVoteResponse { term: 2, vote_granted: true },
+            RaftMessage::AppendEntries {
+                term: 2,
+                prev_log_index: 1,
+                prev_log_term: 1,
+                entries: vec![
+                    RaftEntry { index: 2, term: 2, command: None },
+                    RaftEntry { index: 3, term: 2, command: Some(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") }) },
+                    RaftEntry { index: 4, term: 2, command: Some(StorageCommand::Delete { key: String::from("key1") }) },
+                    RaftEntry { index: 5, term: 2, command: Some(StorageCommand::Transaction(Transaction::new()
+                        .with_precondition(Precondition::KeyExists { key: String::from("key2") })
+                        .with_precondition(Precondition::ValueEquals { key: String::from("key2"), value: String::from("value2") })
+                        .with_operation(Operation::Delete { key: String::from("key2") })
+                        .with_operation(Operation::Get { key: String::from("key2") }))) },
+                ],
+                leader_commit: 1,
+            },
+            RaftMessage::AppendEntriesResponse { term: 2, success: false, match_index: 1 },
+            RaftMessage::InstallSnapshot {
+                term: 2,
+                last_included_index: 4,
+                last_included_term: 2,
+                data: vec![(String::from("key2"), Some(String::from("value2")), 3), (String::from("key3"), None, 2)],
+            },
+            RaftMessage::InstallSnapshotResponse { term: 2, match_index: 4 },
+        ];
+        for message in messages {
+            assert_eq!(Ok((1, message.clone())), from_proto_envelope(to_proto_envelope(1, message)));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_replicate_through_raft_over_grpc() {
+        let addresses: HashMap<NodeId, String> = (1..=3)
+            .map(|id| (id, format!("[::1]:{}", 50052 + id)))
+            .collect();
+        let mut servers = Vec::new();
+        let mut storages = Vec::new();
+        let mut shutdown_signal_senders = Vec::new();
+        let mut server_handles = Vec::new();
+
+        for id in 1..=3 {
+            let peers = addresses
+                .iter()
+                .filter(|(peer, _)| **peer != id)
+                .map(|(peer, address)| (*peer, format!("http://{}/", address)))
+                .collect();
+            let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+            let node = RaftNode::new(id, vec![1, 2, 3], RaftConfig::default(), Arc::new(InMemoryStorageHandler::new(storage.clone())));
+            let server = RaftServer::start(node, Arc::new(GrpcRaftTransport::new(peers).unwrap()), Duration::from_millis(20));
+
+            let (shutdown_signal_sender, mut shutdown_signal_receiver) = mpsc::channel(1);
+            let shutdown_block = async move {
+                shutdown_signal_receiver.recv().await.map(|_: &str| ());
+                return;
+            };
+            let server_address = addresses[&id].parse().unwrap();
+            let service = RaftPeerService::new(server.inbox());
+            server_handles.push(tokio::spawn(async move {
+                Server::builder()
+                    .add_service(RaftGrpcServer::new(service))
+                    .serve_with_shutdown(server_address, shutdown_block)
+                    .await
+                    .expect("Failed in starting the server");
+            }));
+            servers.push(server);
+            storages.push(storage);
+            shutdown_signal_senders.push(shutdown_signal_sender);
+        }
+
+        let servers = tokio::task::spawn_blocking(move || {
+            let deadline = Instant::now() + Duration::from_secs(20);
+            let leader = loop {
+                assert_eq!(true, Instant::now() < deadline);
+                if let Some(leader) = servers.iter().find(|server| server.status().unwrap().role == Role::Leader) {
+                    break leader;
+                }
+                thread::sleep(Duration::from_millis(10));
+            };
+            let handler = RaftHandler::new(leader.inbox(), Duration::from_secs(5));
+            assert_eq!(CommandStatus::Ok, handler.handle(StorageCommand::Put {
+                key: String::from("key1"),
+                value: String::from("value1"),
+            }));
+            let commit_index = leader.status().unwrap().commit_index;
+            for server in &servers {
+                while server.status().unwrap().last_applied < commit_index {
+                    assert_eq!(true, Instant::now() < deadline);
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+            return servers;
+        }).await.unwrap();
+
+        for storage in &storages {
+            assert_eq!("value1", storage.read().unwrap().get("key1").unwrap());
+        }
+        for server in &servers {
+            server.stop();
+        }
+        for shutdown_signal_sender in shutdown_signal_senders {
+            shutdown_signal_sender.send("shutdown").await.expect("Failed in sending the shutdown signal");
+        }
+        for server_handle in server_handles {
+            server_handle.await.unwrap();
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use tokio::runtime::Handle;
+use tonic::{Request, Response, Status};
+use tonic::transport::Channel;
+
+use language::replication::append_entries::{AppendEntriesRequest, AppendEntriesResponse};
+use language::replication::follower::Follower;
+use language::replication::replication_error::ReplicationError;
+use language::replication::transport::ReplicationTransport;
+use language::singular_update_queue::write_ahead_log::LogEntry;
+
+use crate::replication_server::mod_replication::replication_client::ReplicationClient;
+use crate::replication_server::mod_replication::replication_server::Replication;
+//the generated replication messages refer to the shared messages by their package, as super::storage
+use crate::storage_conversion::mod_storage as storage;
+use crate::storage_conversion::{from_proto_command, to_proto_command};
+
+pub mod mod_replication {
+    tonic::include_proto!("replication"); //package name
+}
+
+//serves append entries requests of a leader on behalf of a follower
+pub struct FollowerReplicationServer {
+    follower: Arc<Follower>,
+}
+
+impl FollowerReplicationServer {
+    pub fn new(follower: Arc<Follower>) -> FollowerReplicationServer {
+        return FollowerReplicationServer { follower };
+    }
+}
+
+#[tonic::async_trait]
+impl Replication for FollowerReplicationServer {
+    async fn append_entries(&self, request: Request<mod_replication::AppendEntriesRequest>) -> Result<Response<mod_replication::AppendEntriesResponse>, Status> {
+        let request = from_proto_request(request.into_inner()).map_err(Status::invalid_argument)?;
+        let response = self.follower.append_entries(request);
+        return Ok(Response::new(mod_replication::AppendEntriesResponse {
+            success: response.success,
+            last_index: response.last_index,
+        }));
+    }
+}
+
+//replicator threads are plain threads, so the transport blocks on the runtime it was connected from
+pub struct GrpcReplicationTransport {
+    client: ReplicationClient<Channel>,
+    runtime: Handle,
+}
+
+impl GrpcReplicationTransport {
+    pub async fn connect(address: String) -> Result<GrpcReplicationTransport, tonic::transport::Error> {
+        let client = ReplicationClient::connect(address).await?;
+        return Ok(GrpcReplicationTransport { client, runtime: Handle::current() });
+    }
+}
+
+impl ReplicationTransport for GrpcReplicationTransport {
+    fn append_entries(&self, request: AppendEntriesRequest) -> Result<AppendEntriesResponse, ReplicationError> {
+        let mut client = self.client.clone();
+        let response = self.runtime
+            .block_on(client.append_entries(Request::new(to_proto_request(request))))
+            .map_err(|status| ReplicationError::Unreachable(status.message().to_string()))?
+            .into_inner();
+        return Ok(AppendEntriesResponse { success: response.success, last_index: response.last_index });
+    }
+}
+
+fn to_proto_request(request: AppendEntriesRequest) -> mod_replication::AppendEntriesRequest {
+    let entries = request.entries
+        .into_iter()
+        .map(|entry| mod_replication::LogEntry { index: entry.index, command: Some(to_proto_command(entry.command)) })
+        .collect();
+    return mod_replication::AppendEntriesRequest {
+        prev_index: request.prev_index,
+        entries,
+        commit_index: request.commit_index,
+    };
+}
+
+fn from_proto_request(request: mod_replication::AppendEntriesRequest) -> Result<AppendEntriesRequest, String> {
+    let mut entries = Vec::with_capacity(request.entries.len());
+    for entry in request.entries {
+        let command = match entry.command {
+            Some(command) => from_proto_command(command)?,
+            None => return Err(format!("log entry {} has no command", entry.index)),
+        };
+        entries.push(LogEntry { index: entry.index, command });
+    }
+    return Ok(AppendEntriesRequest {
+        prev_index: request.prev_index,
+        entries,
+        commit_index: request.commit_index,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+    use std::thread;
+    use std::time::Duration;
+
+    use tokio::sync::mpsc;
+    use tonic::transport::Server;
+
+    use language::replication::leader::ReplicatingHandler;
+    use language::replication::replication_config::ReplicationConfig;
+    use language::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+    use language::singular_update_queue::storage_command::{InMemoryStorageHandler, Status as CommandStatus, Storage, StorageCommand};
+    use language::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+
+    use crate::replication_server::mod_replication::replication_server::ReplicationServer;
+
+    use super::*;
+
+    #[test]
+    fn test_convert_a_request() {
+        let request = AppendEntriesRequest {
+            prev_index: 1,
+            entries: vec![
+                LogEntry { index: 2, command: StorageCommand::Put { key: String::from("key1"), value: String::from("value1") } },
+                LogEntry { index: 3, command: StorageCommand::Delete { key: String::from("key1") } },
+                LogEntry { index: 4, command: StorageCommand::Transaction(Transaction::new()
+                    .with_precondition(Precondition::VersionMatches { key: String::from("key1"), version: 0 })
+                    .with_operation(Operation::Put { key: String::from("key1"), value: String::from("value2") })) },
+            ],
+            commit_index: 1,
+        };
+        assert_eq!(request.clone(), from_proto_request(to_proto_request(request)).unwrap());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_replicate_over_grpc() {
+        let server_address = "[::1]:50052".parse().unwrap();
+        let follower_storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Arc::new(Follower::new(Arc::new(InMemoryStorageHandler::new(follower_storage.clone()))));
+        let (shutdown_signal_sender, mut shutdown_signal_receiver) = mpsc::channel(1);
+
+        let shutdown_block = async move {
+            shutdown_signal_receiver.recv().await.map(|_: &str| ());
+            return;
+        };
+        let server_follower = follower.clone();
+        let server_handle = tokio::spawn(async move {
+            Server::builder()
+                .add_service(ReplicationServer::new(FollowerReplicationServer::new(server_follower)))
+                .serve_with_shutdown(server_address, shutdown_block)
+                .await
+                .expect("Failed in starting the server");
+        });
+
+        thread::sleep(Duration::from_secs(3));
+        let transport = GrpcReplicationTransport::connect(String::from("http://[::1]:50052/")).await.unwrap();
+        let leader_storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let leader = Arc::new(ReplicatingHandler::new(
+            Arc::new(InMemoryStorageHandler::new(leader_storage.clone())),
+            vec![Arc::new(transport) as Arc<dyn ReplicationTransport>],
+            ReplicationConfig::new(2).with_ack_timeout(Duration::from_secs(5)),
+        ));
+        let singular_update_queue = SingularUpdateQueue::init(leader.clone(), Executor::Thread);
+
+        let status = tokio::task::spawn_blocking(move || {
+            return singular_update_queue.execute(StorageCommand::Put {
+                key: String::from("key1"),
+                value: String::from("value1"),
+            }).wait();
+        }).await.unwrap();
+
+        assert_eq!(Ok(CommandStatus::Ok), status);
+        assert_eq!(1, follower.last_index());
+        assert_eq!("value1", leader_storage.read().unwrap().get("key1").unwrap());
+
+        leader.stop();
+        shutdown_signal_sender.send("shutdown").await.expect("Failed in sending the shutdown signal");
+        server_handle.await.unwrap();
+    }
+}
@@ -0,0 +1,101 @@
+use language::singular_update_queue::storage_command::StorageCommand;
+use language::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+
+use crate::storage_conversion::mod_storage::command::Command;
+use crate::storage_conversion::mod_storage::operation::Operation as ProtoOperation;
+use crate::storage_conversion::mod_storage::precondition::Condition;
+
+//the storage messages which the raft and the replication services share
+pub mod mod_storage {
+    tonic::include_proto!("storage"); //package name
+}
+
+pub fn to_proto_command(command: StorageCommand) -> mod_storage::Command {
+    let command = match command {
+        StorageCommand::Put { key, value } => Command::Put(mod_storage::Put { key, value }),
+        StorageCommand::Delete { key } => Command::Delete(mod_storage::Delete { key }),
+        StorageCommand::Get { key } => Command::Get(mod_storage::Get { key }),
+        StorageCommand::Transaction(transaction) => Command::Transaction(to_proto_transaction(transaction)),
+    };
+    return mod_storage::Command { command: Some(command) };
+}
+
+pub fn from_proto_command(command: mod_storage::Command) -> Result<StorageCommand, String> {
+    return match command.command {
+        Some(Command::Put(put)) => Ok(StorageCommand::Put { key: put.key, value: put.value }),
+        Some(Command::Delete(delete)) => Ok(StorageCommand::Delete { key: delete.key }),
+        Some(Command::Get(get)) => Ok(StorageCommand::Get { key: get.key }),
+        Some(Command::Transaction(transaction)) => Ok(StorageCommand::Transaction(from_proto_transaction(transaction)?)),
+        None => Err(String::from("command has no content")),
+    };
+}
+
+fn to_proto_transaction(transaction: Transaction) -> mod_storage::Transaction {
+    let preconditions = transaction.preconditions
+        .into_iter()
+        .map(|precondition| mod_storage::Precondition {
+            condition: Some(match precondition {
+                Precondition::KeyExists { key } => Condition::KeyExists(mod_storage::KeyExists { key }),
+                Precondition::ValueEquals { key, value } => Condition::ValueEquals(mod_storage::ValueEquals { key, value }),
+                Precondition::VersionMatches { key, version } => Condition::VersionMatches(mod_storage::VersionMatches { key, version }),
+            }),
+        })
+        .collect();
+    let operations = transaction.operations
+        .into_iter()
+        .map(|operation| mod_storage::Operation {
+            operation: Some(match operation {
+                Operation::Put { key, value } => ProtoOperation::Put(mod_storage::Put { key, value }),
+                Operation::Delete { key } => ProtoOperation::Delete(mod_storage::Delete { key }),
+                Operation::Get { key } => ProtoOperation::Get(mod_storage::Get { key }),
+            }),
+        })
+        .collect();
+    return mod_storage::Transaction { preconditions, operations };
+}
+
+fn from_proto_transaction(transaction: mod_storage::Transaction) -> Result<Transaction, String> {
+    let mut result = Transaction::new();
+    for precondition in transaction.preconditions {
+        result = result.with_precondition(match precondition.condition {
+            Some(Condition::KeyExists(exists)) => Precondition::KeyExists { key: exists.key },
+            Some(Condition::ValueEquals(equals)) => Precondition::ValueEquals { key: equals.key, value: equals.value },
+            Some(Condition::VersionMatches(matches)) => Precondition::VersionMatches { key: matches.key, version: matches.version },
+            None => return Err(String::from("transaction has a precondition without a condition")),
+        });
+    }
+    for operation in transaction.operations {
+        result = result.with_operation(match operation.operation {
+            Some(ProtoOperation::Put(put)) => Operation::Put { key: put.key, value: put.value },
+            Some(ProtoOperation::Delete(delete)) => Operation::Delete { key: delete.key },
+            Some(ProtoOperation::Get(get)) => Operation::Get { key: get.key },
+            None => return Err(String::from("transaction has an operation without a command")),
+        });
+    }
+    return Ok(result);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_convert_commands() {
+        let commands = vec![
+            StorageCommand::Put { key: String::from("key1"), value: String::from("value1") },
+            StorageCommand::Delete { key: String::from("key1") },
+            StorageCommand::Get { key: String::from("key1") },
+            StorageCommand::Transaction(Transaction::new()
+                .with_precondition(Precondition::KeyExists { key: String::from("key2") })
+                .with_precondition(Precondition::ValueEquals { key: String::from("key2"), value: String::from("value2") })
+                .with_precondition(Precondition::VersionMatches { key: String::from("key2"), version: 3 })
+                .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value3") })
+                .with_operation(Operation::Delete { key: String::from("key2") })
+                .with_operation(Operation::Get { key: String::from("key2") })),
+        ];
+        for command in commands {
+            assert_eq!(Ok(command.clone()), from_proto_command(to_proto_command(command)));
+        }
+        assert_eq!(true, from_proto_command(mod_storage::Command { command: None }).is_err());
+    }
+}
@@ -1,59 +1,73 @@
-use std::collections::hash_map::DefaultHasher;
-use std::collections::HashMap;
-use std::hash::{Hash, Hasher};
-use std::sync::{Arc, RwLock};
+use std::collections::HashSet;
 
 use crate::cache::expiry::{Expiry, ValueRef};
+use crate::cache::memory::MemoryBudget;
+use crate::cache::sharded_storage::{ReshardingError, ShardedLockedStorage, ShardedStorage};
+use crate::cache::sync::Arc;
 
-pub(crate) type ShardedLockedStorage = Arc<Vec<RwLock<HashMap<String, Arc<ValueRef>>>>>;
-
-pub(crate) type BucketIndex = usize;
-
-struct EvictingCache {
+pub(crate) struct EvictingCache {
     storage: ShardedLockedStorage,
-    buckets: usize,
 }
 
 impl EvictingCache {
-    fn new(buckets: usize) -> EvictingCache {
-        let mut elements = Vec::with_capacity(buckets);
-        for _ in 0..buckets {
-            elements.push(RwLock::new(HashMap::new()));
-        }
-        return EvictingCache { storage: Arc::new(elements), buckets };
+    pub(crate) fn new(buckets: usize) -> EvictingCache {
+        return EvictingCache { storage: ShardedStorage::new(buckets).locked() };
+    }
+
+    fn with_memory_budget(buckets: usize, memory_budget: MemoryBudget) -> EvictingCache {
+        let storage = ShardedStorage::new(buckets).with_memory_budget(memory_budget);
+        return EvictingCache { storage: storage.locked() };
     }
 
-    fn put(&mut self, key: String, value: String) {
+    fn put(&self, key: String, value: String) {
         self.put_with_expiry(key, value, Expiry::never());
     }
 
-    fn put_with_expiry(&mut self, key: String, value: String, expiry: Expiry) {
-        let key_index = self.index_of(&key);
-        let mut value_by_key = self.storage[key_index].write().unwrap();
-        value_by_key.insert(key, Arc::new(ValueRef::new(value, expiry)));
+    pub(crate) fn put_with_expiry(&self, key: String, value: String, expiry: Expiry) {
+        self.put_with_expiry_and_tags(key, value, expiry, HashSet::new());
+    }
+
+    fn put_with_tags(&self, key: String, value: String, tags: HashSet<String>) {
+        self.put_with_expiry_and_tags(key, value, Expiry::never(), tags);
+    }
+
+    fn put_with_expiry_and_tags(&self, key: String, value: String, expiry: Expiry, tags: HashSet<String>) {
+        self.storage.read().unwrap().put(key, Arc::new(ValueRef::tagged(value, expiry, tags)));
+        ShardedStorage::advance_resharding(&self.storage);
+    }
+
+    fn invalidate_tag(&self, tag: String) -> usize {
+        return self.storage.read().unwrap().invalidate_tag(&tag);
     }
 
-    fn get(&self, key: String) -> Option<Arc<ValueRef>> {
-        let key_index = self.index_of(&key);
-        let value_by_key = self.storage[key_index].read().unwrap();
+    pub(crate) fn get(&self, key: String) -> Option<Arc<ValueRef>> {
+        let value = self.storage.read().unwrap().get(&key);
 
-        return match value_by_key.get(&key) {
+        return match value {
             None => { None }
             Some(rc_value) => {
                 return match rc_value.has_expired() {
                     true => None,
-                    false => Some(rc_value.clone())
+                    false => Some(rc_value)
                 };
             }
         };
     }
 
-    fn index_of(&self, key: &String) -> BucketIndex {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
+    fn reshard(&self, buckets: usize) -> Result<(), ReshardingError> {
+        return self.storage.write().unwrap().begin_resharding(buckets);
+    }
+
+    fn memory_usage(&self) -> usize {
+        return self.storage.read().unwrap().memory_usage();
+    }
+
+    fn buckets(&self) -> usize {
+        return self.storage.read().unwrap().buckets();
+    }
 
-        let hash = hasher.finish() as usize;
-        return hash % self.buckets;
+    fn storage(&self) -> ShardedLockedStorage {
+        return self.storage.clone();
     }
 }
 
@@ -63,7 +77,7 @@ mod tests {
 
     #[test]
     fn test_get_value_by_an_existing_key() {
-        let mut evicting_cache = EvictingCache::new(64);
+        let evicting_cache = EvictingCache::new(64);
         evicting_cache.put(String::from("disk_type"), String::from("SSD"));
 
         let value = evicting_cache.get(String::from("disk_type"));
@@ -72,7 +86,7 @@ mod tests {
 
     #[test]
     fn test_get_value_by_an_non_existing_key() {
-        let mut evicting_cache = EvictingCache::new(64);
+        let evicting_cache = EvictingCache::new(64);
         evicting_cache.put(String::from("disk_type"), String::from("SSD"));
 
         let value = evicting_cache.get(String::from("non_existing"));
@@ -81,11 +95,200 @@ mod tests {
 
     #[test]
     fn test_get_value_by_an_expired_value_of_key() {
-        let mut evicting_cache = EvictingCache::new(64);
+        let evicting_cache = EvictingCache::new(64);
         evicting_cache.put_with_expiry(String::from("disk_type"), String::from("SSD"), Expiry::immediate());
 
         let value = evicting_cache.get(String::from("disk_type"));
         assert!(value.is_none());
     }
+
+    #[test]
+    fn test_get_value_while_resharding() {
+        let evicting_cache = EvictingCache::new(2);
+        evicting_cache.put(String::from("disk_type"), String::from("SSD"));
+        evicting_cache.reshard(8).unwrap();
+
+        let value = evicting_cache.get(String::from("disk_type"));
+        assert_eq!(&String::from("SSD"), value.unwrap().value());
+    }
+
+    #[test]
+    fn test_puts_complete_resharding() {
+        let evicting_cache = EvictingCache::new(2);
+        evicting_cache.put(String::from("disk_type"), String::from("SSD"));
+        evicting_cache.reshard(8).unwrap();
+
+        evicting_cache.put(String::from("cpu"), String::from("8 cores"));
+        evicting_cache.put(String::from("memory"), String::from("16 GB"));
+
+        assert_eq!(8, evicting_cache.buckets());
+        assert_eq!(false, evicting_cache.storage().read().unwrap().is_resharding());
+        assert_eq!(&String::from("SSD"), evicting_cache.get(String::from("disk_type")).unwrap().value());
+        assert_eq!(&String::from("8 cores"), evicting_cache.get(String::from("cpu")).unwrap().value());
+        assert_eq!(&String::from("16 GB"), evicting_cache.get(String::from("memory")).unwrap().value());
+    }
+
+    #[test]
+    fn test_reshard_while_resharding() {
+        let evicting_cache = EvictingCache::new(2);
+        evicting_cache.reshard(8).unwrap();
+
+        let result = evicting_cache.reshard(4);
+        assert_eq!(Err(ReshardingError::InProgress), result);
+    }
+
+    #[test]
+    fn test_invalidate_tag_removes_all_the_tagged_entries() {
+        let evicting_cache = EvictingCache::new(16);
+        let tags = HashSet::from([String::from("user:1")]);
+        evicting_cache.put_with_tags(String::from("user:1:profile"), String::from("profile"), tags.clone());
+        evicting_cache.put_with_tags(String::from("user:1:orders"), String::from("orders"), tags.clone());
+        evicting_cache.put(String::from("disk_type"), String::from("SSD"));
+
+        let invalidated = evicting_cache.invalidate_tag(String::from("user:1"));
+
+        assert_eq!(2, invalidated);
+        assert!(evicting_cache.get(String::from("user:1:profile")).is_none());
+        assert!(evicting_cache.get(String::from("user:1:orders")).is_none());
+        assert_eq!(&String::from("SSD"), evicting_cache.get(String::from("disk_type")).unwrap().value());
+    }
+
+    #[test]
+    fn test_invalidate_a_non_existing_tag() {
+        let evicting_cache = EvictingCache::new(16);
+        evicting_cache.put(String::from("disk_type"), String::from("SSD"));
+
+        let invalidated = evicting_cache.invalidate_tag(String::from("user:1"));
+
+        assert_eq!(0, invalidated);
+        assert_eq!(&String::from("SSD"), evicting_cache.get(String::from("disk_type")).unwrap().value());
+    }
+
+    #[test]
+    fn test_memory_usage_of_a_cache() {
+        let evicting_cache = EvictingCache::with_memory_budget(4, MemoryBudget::new(1024, 512));
+        let empty_usage = evicting_cache.memory_usage();
+        evicting_cache.put(String::from("disk_type"), String::from("SSD"));
+
+        assert!(evicting_cache.memory_usage() > empty_usage);
+        assert_eq!(false, evicting_cache.storage().read().unwrap().is_under_memory_pressure());
+    }
 }
 
+#[cfg(test)]
+mod property_tests {
+    use std::collections::HashMap;
+
+    use proptest::prelude::*;
+    use tokio::time::{Duration, Instant};
+
+    use super::*;
+
+    #[derive(Debug, Clone)]
+    enum Operation {
+        Put { key: usize, value: u8, expiry_seconds: Option<u64>, tag: Option<usize> },
+        Get { key: usize },
+        InvalidateTag { tag: usize },
+        Reshard { buckets: usize },
+        AdvanceClock { seconds: u64 },
+        Evict,
+    }
+
+    struct ModelEntry {
+        value: String,
+        expires_at: Option<Instant>,
+        tag: Option<String>,
+    }
+
+    impl ModelEntry {
+        fn has_expired(&self) -> bool {
+            return match self.expires_at {
+                None => false,
+                Some(expires_at) => Instant::now() >= expires_at
+            };
+        }
+    }
+
+    fn operation() -> impl Strategy<Value=Operation> {
+        return prop_oneof![
+            4 => (0..8usize, any::<u8>(), proptest::option::of(0..5u64), proptest::option::of(0..3usize))
+                .prop_map(|(key, value, expiry_seconds, tag)| Operation::Put { key, value, expiry_seconds, tag }),
+            3 => (0..8usize).prop_map(|key| Operation::Get { key }),
+            1 => (0..3usize).prop_map(|tag| Operation::InvalidateTag { tag }),
+            1 => (1..16usize).prop_map(|buckets| Operation::Reshard { buckets }),
+            2 => (1..4u64).prop_map(|seconds| Operation::AdvanceClock { seconds }),
+            1 => Just(Operation::Evict),
+        ];
+    }
+
+    async fn apply(evicting_cache: &EvictingCache, model: &mut HashMap<String, ModelEntry>, operation: Operation) -> Result<(), TestCaseError> {
+        match operation {
+            Operation::Put { key, value, expiry_seconds, tag } => {
+                let key = format!("key{}", key);
+                let value = format!("value{}", value);
+                let tag = tag.map(|tag| format!("tag{}", tag));
+                let expiry = match expiry_seconds {
+                    None => Expiry::never(),
+                    Some(seconds) => Expiry::after_seconds(seconds)
+                };
+                let tags = tag.iter().cloned().collect();
+                evicting_cache.put_with_expiry_and_tags(key.clone(), value.clone(), expiry, tags);
+
+                let expires_at = expiry_seconds.map(|seconds| Instant::now() + Duration::from_secs(seconds));
+                model.insert(key, ModelEntry { value, expires_at, tag });
+            }
+            Operation::Get { key } => {
+                let key = format!("key{}", key);
+                let expected = model.get(&key)
+                    .filter(|entry| !entry.has_expired())
+                    .map(|entry| entry.value.clone());
+                let actual = evicting_cache.get(key).map(|value| value.value().clone());
+                prop_assert_eq!(expected, actual);
+            }
+            Operation::InvalidateTag { tag } => {
+                let tag = format!("tag{}", tag);
+                let expected = model.values().filter(|entry| entry.tag.as_ref() == Some(&tag)).count();
+                model.retain(|_, entry| entry.tag.as_ref() != Some(&tag));
+
+                prop_assert_eq!(expected, evicting_cache.invalidate_tag(tag));
+            }
+            Operation::Reshard { buckets } => {
+                let result = evicting_cache.reshard(buckets);
+                prop_assert!(result.is_ok() || result == Err(ReshardingError::InProgress));
+            }
+            Operation::AdvanceClock { seconds } => {
+                tokio::time::advance(Duration::from_secs(seconds)).await;
+            }
+            Operation::Evict => {
+                let storage = evicting_cache.storage();
+                let read_storage = storage.read().unwrap();
+                for bucket in 0..read_storage.buckets_to_sweep() {
+                    read_storage.evict(bucket);
+                }
+                model.retain(|_, entry| !entry.has_expired());
+            }
+        }
+        prop_assert_eq!(model.len(), evicting_cache.storage().read().unwrap().len());
+        return Ok(());
+    }
+
+    proptest! {
+        #[test]
+        fn test_cache_behaves_like_a_hash_map(operations in proptest::collection::vec(operation(), 1..64)) {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .start_paused(true)
+                .build()
+                .unwrap();
+
+            runtime.block_on(async {
+                let evicting_cache = EvictingCache::new(4);
+                let mut model = HashMap::new();
+                for operation in operations {
+                    apply(&evicting_cache, &mut model, operation).await?;
+                }
+                return Ok::<(), TestCaseError>(());
+            })?;
+        }
+    }
+}
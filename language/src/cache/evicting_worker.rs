@@ -1,45 +1,55 @@
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
 use std::thread;
 use std::time::Duration;
 
-use crate::cache::evicting_cache::{BucketIndex, ShardedLockedStorage};
-use crate::cache::expiry::{Expiry, ValueRef};
+use crate::cache::sharded_storage::{BucketIndex, ShardedLockedStorage, ShardedStorage};
 
 struct EvictingWorker {
     storage: ShardedLockedStorage,
     current_bucket: BucketIndex,
-    buckets: usize,
 }
 
 impl EvictingWorker {
     const SLEEP_FOR_SECONDS: Duration = Duration::from_micros(5);
 
-    fn run(buckets: usize, storage: ShardedLockedStorage) {
-        Self::run_from(buckets, 0, storage);
+    fn run(storage: ShardedLockedStorage) {
+        Self::run_from(0, storage);
     }
 
-    fn run_from(buckets: usize, current_bucket: BucketIndex, storage: ShardedLockedStorage) {
-        let mut worker = EvictingWorker { storage, current_bucket, buckets };
+    fn run_from(current_bucket: BucketIndex, storage: ShardedLockedStorage) {
+        let mut worker = EvictingWorker { storage, current_bucket };
         thread::spawn(move || {
             loop {
                 worker.evict();
-                worker.current_bucket = (worker.current_bucket + 1) % worker.buckets;
+                ShardedStorage::advance_resharding(&worker.storage);
+                worker.current_bucket = worker.next_bucket();
                 thread::sleep(EvictingWorker::SLEEP_FOR_SECONDS);
             }
         });
     }
 
     fn evict(&self) {
-        let mut locked_storage = self.storage[self.current_bucket].write().unwrap();
-        locked_storage.retain(|_, value_ref| {
-            !value_ref.has_expired()
-        });
+        let storage = self.storage.read().unwrap();
+        storage.evict(self.current_bucket);
+        if storage.is_under_memory_pressure() {
+            storage.evict_for_capacity(self.current_bucket);
+        }
+    }
+
+    //bucket count changes while resharding, so it is read on every step
+    fn next_bucket(&self) -> BucketIndex {
+        let buckets = self.storage.read().unwrap().buckets_to_sweep();
+        return (self.current_bucket + 1) % buckets;
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+
+    use crate::cache::expiry::{Expiry, ValueRef};
+    use crate::cache::memory::MemoryBudget;
+    use crate::cache::sync::Arc;
+
     use super::*;
 
     #[test]
@@ -54,17 +64,63 @@ mod tests {
                 )
             ],
         );
-        let storage: ShardedLockedStorage = Arc::new(vec![RwLock::new(key_value_pairs)]);
-        EvictingWorker::run(1, storage.clone());
+        let storage: ShardedLockedStorage = ShardedStorage::from(vec![key_value_pairs]).locked();
+        EvictingWorker::run(storage.clone());
 
         thread::sleep(Duration::from_secs(5));
 
-        let read_map = storage[0].read().unwrap();
-        let expired_value = read_map.get("expired");
-        let living_value = read_map.get("living");
+        let read_storage = storage.read().unwrap();
+        let expired_value = read_storage.get(&String::from("expired"));
+        let living_value = read_storage.get(&String::from("living"));
 
-        assert_eq!(1, read_map.len());
+        assert_eq!(1, read_storage.len());
         assert_eq!(true, expired_value.is_none());
         assert_eq!(&String::from("living_value"), living_value.unwrap().value());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_resharding_with_eviction() {
+        let key_value_pairs = HashMap::from(
+            [
+                (String::from("expired"),
+                 Arc::new(ValueRef::new(String::from("expired_value"), Expiry::immediate()))
+                ),
+                (String::from("living"),
+                 Arc::new(ValueRef::new(String::from("living_value"), Expiry::never()))
+                )
+            ],
+        );
+        let storage: ShardedLockedStorage = ShardedStorage::from(vec![key_value_pairs]).locked();
+        storage.write().unwrap().begin_resharding(4).unwrap();
+        EvictingWorker::run(storage.clone());
+
+        thread::sleep(Duration::from_secs(1));
+
+        let read_storage = storage.read().unwrap();
+        let living_value = read_storage.get(&String::from("living"));
+
+        assert_eq!(false, read_storage.is_resharding());
+        assert_eq!(4, read_storage.buckets());
+        assert_eq!(1, read_storage.len());
+        assert_eq!(&String::from("living_value"), living_value.unwrap().value());
+    }
+
+    #[test]
+    fn test_eviction_under_memory_pressure() {
+        let storage: ShardedLockedStorage = ShardedStorage::new(8)
+            .with_memory_budget(MemoryBudget::new(8192, 4096))
+            .locked();
+        for count in 0..200 {
+            let value = Arc::new(ValueRef::new(format!("value{}", count), Expiry::never()));
+            storage.read().unwrap().put(format!("key{}", count), value);
+        }
+        assert!(storage.read().unwrap().memory_usage() > 8192);
+        EvictingWorker::run(storage.clone());
+
+        thread::sleep(Duration::from_secs(1));
+
+        let read_storage = storage.read().unwrap();
+        assert!(read_storage.memory_usage() <= 4096);
+        assert!(read_storage.len() > 0);
+    }
+}
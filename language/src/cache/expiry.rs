@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::thread;
 use std::time::Duration;
 
@@ -6,17 +7,35 @@ use tokio::time::Instant;
 pub struct ValueRef {
     value: String,
     expiry_after: Expiry,
+    tags: HashSet<String>,
+    created_at: Instant,
 }
 
 impl ValueRef {
     pub fn new(value: String, expiry: Expiry) -> ValueRef {
-        return ValueRef { value, expiry_after: expiry };
+        return ValueRef::tagged(value, expiry, HashSet::new());
+    }
+
+    pub fn tagged(value: String, expiry: Expiry, tags: HashSet<String>) -> ValueRef {
+        return ValueRef { value, expiry_after: expiry, tags, created_at: Instant::now() };
     }
 
     pub fn value(&self) -> &String {
         return &self.value;
     }
 
+    pub fn tags(&self) -> &HashSet<String> {
+        return &self.tags;
+    }
+
+    pub fn has_tag(&self, tag: &String) -> bool {
+        return self.tags.contains(tag);
+    }
+
+    pub fn created_at(&self) -> Instant {
+        return self.created_at;
+    }
+
     pub fn has_expired(&self) -> bool {
         return match self.expiry_after.instant {
             None => { false }
@@ -74,4 +93,13 @@ mod tests {
         let has_expired = value_ref.has_expired();
         assert_eq!(true, has_expired);
     }
+
+    #[test]
+    fn test_a_tagged_value() {
+        let tags = HashSet::from([String::from("user:1")]);
+        let value_ref = ValueRef::tagged(String::from("some value"), Expiry::never(), tags);
+
+        assert!(value_ref.has_tag(&String::from("user:1")));
+        assert_eq!(false, value_ref.has_tag(&String::from("user:2")));
+    }
 }
\ No newline at end of file
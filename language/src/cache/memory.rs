@@ -0,0 +1,83 @@
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+
+use crate::cache::expiry::ValueRef;
+use crate::cache::sync::Arc;
+
+//hashbrown keeps a control byte per slot and grows at 7/8th load, a word per entry is a fair approximation
+const ENTRY_OVERHEAD: usize = size_of::<usize>();
+const ARC_COUNTERS: usize = 2 * size_of::<usize>();
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryBudget {
+    high_water_bytes: usize,
+    low_water_bytes: usize,
+}
+
+impl MemoryBudget {
+    pub fn new(high_water_bytes: usize, low_water_bytes: usize) -> MemoryBudget {
+        assert!(low_water_bytes <= high_water_bytes, "low water mark must not exceed the high water mark");
+        return MemoryBudget { high_water_bytes, low_water_bytes };
+    }
+
+    pub fn high_water_bytes(&self) -> usize {
+        return self.high_water_bytes;
+    }
+
+    pub fn low_water_bytes(&self) -> usize {
+        return self.low_water_bytes;
+    }
+}
+
+pub(crate) fn estimate_entry(key: &String, value: &ValueRef) -> usize {
+    let key_size = size_of::<String>() + key.len();
+    let value_size = size_of::<Arc<ValueRef>>() + ARC_COUNTERS + size_of::<ValueRef>() + value.value().capacity();
+    return key_size + value_size + estimate_tags(value.tags()) + ENTRY_OVERHEAD;
+}
+
+pub(crate) fn estimate_shard() -> usize {
+    return size_of::<HashMap<String, Arc<ValueRef>>>();
+}
+
+fn estimate_tags(tags: &HashSet<String>) -> usize {
+    let mut total = 0;
+    for tag in tags {
+        total = total + size_of::<String>() + tag.capacity() + ENTRY_OVERHEAD;
+    }
+    return total;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::expiry::Expiry;
+
+    use super::*;
+
+    #[test]
+    fn test_estimate_grows_with_the_value() {
+        let small = ValueRef::new(String::from("SSD"), Expiry::never());
+        let large = ValueRef::new("SSD".repeat(100), Expiry::never());
+
+        let small_estimate = estimate_entry(&String::from("disk_type"), &small);
+        let large_estimate = estimate_entry(&String::from("disk_type"), &large);
+
+        assert_eq!(297, large_estimate - small_estimate);
+    }
+
+    #[test]
+    fn test_estimate_includes_tags() {
+        let untagged = ValueRef::new(String::from("SSD"), Expiry::never());
+        let tagged = ValueRef::tagged(String::from("SSD"), Expiry::never(), HashSet::from([String::from("disk")]));
+
+        let untagged_estimate = estimate_entry(&String::from("disk_type"), &untagged);
+        let tagged_estimate = estimate_entry(&String::from("disk_type"), &tagged);
+
+        assert!(tagged_estimate > untagged_estimate);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_budget_with_low_water_above_high_water() {
+        MemoryBudget::new(1024, 2048);
+    }
+}
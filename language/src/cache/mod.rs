@@ -1,3 +1,8 @@
 mod evicting_cache;
 mod expiry;
-mod evicting_worker;
\ No newline at end of file
+mod evicting_worker;
+mod sharded_storage;
+mod tag_index;
+mod memory;
+mod sync;
+mod warm_up;
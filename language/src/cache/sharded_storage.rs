@@ -0,0 +1,692 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::cache::expiry::ValueRef;
+use crate::cache::memory::{estimate_entry, estimate_shard, MemoryBudget};
+use crate::cache::sync::{Arc, AtomicBool, AtomicUsize, Ordering, RwLock};
+use crate::cache::tag_index::TagIndex;
+
+pub(crate) type BucketIndex = usize;
+
+pub(crate) type Shard = RwLock<HashMap<String, Arc<ValueRef>>>;
+
+pub(crate) type ShardedLockedStorage = Arc<RwLock<ShardedStorage>>;
+
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum ReshardingError {
+    InvalidBucketCount,
+    InProgress,
+}
+
+pub(crate) struct ShardedStorage {
+    shards: Vec<Shard>,
+    resharding: Option<Resharding>,
+    tag_index: TagIndex,
+    used_bytes: AtomicUsize,
+    memory_budget: Option<MemoryBudget>,
+    under_memory_pressure: AtomicBool,
+}
+
+struct Resharding {
+    shards: Vec<Shard>,
+    next_bucket: AtomicUsize,
+    migrated_buckets: AtomicUsize,
+}
+
+impl ShardedStorage {
+    pub(crate) fn new(buckets: usize) -> ShardedStorage {
+        return Self::with_shards(Self::empty_shards(buckets));
+    }
+
+    pub(crate) fn from(maps: Vec<HashMap<String, Arc<ValueRef>>>) -> ShardedStorage {
+        let storage = Self::with_shards(Vec::with_capacity(maps.len()));
+        for (key, value) in maps.iter().flatten() {
+            storage.track_insert(key, value);
+        }
+        let shards = maps.into_iter().map(RwLock::new).collect();
+        return ShardedStorage { shards, ..storage };
+    }
+
+    pub(crate) fn with_memory_budget(self, memory_budget: MemoryBudget) -> ShardedStorage {
+        return ShardedStorage { memory_budget: Some(memory_budget), ..self };
+    }
+
+    pub(crate) fn locked(self) -> ShardedLockedStorage {
+        return Arc::new(RwLock::new(self));
+    }
+
+    pub(crate) fn buckets(&self) -> usize {
+        return match &self.resharding {
+            None => self.shards.len(),
+            Some(resharding) => resharding.shards.len()
+        };
+    }
+
+    pub(crate) fn buckets_to_sweep(&self) -> usize {
+        return match &self.resharding {
+            None => self.shards.len(),
+            Some(resharding) => self.shards.len().max(resharding.shards.len())
+        };
+    }
+
+    pub(crate) fn is_resharding(&self) -> bool {
+        return self.resharding.is_some();
+    }
+
+    pub(crate) fn put(&self, key: String, value: Arc<ValueRef>) {
+        let key_index = index_of(&key, self.shards.len());
+        let mut value_by_key = self.shards[key_index].write().unwrap();
+        self.track_insert(&key, &value);
+        match &self.resharding {
+            None => {
+                let existing = value_by_key.insert(key.clone(), value.clone());
+                self.track_replace(&key, existing, &value);
+            }
+            Some(resharding) => {
+                let stale = value_by_key.remove(&key);
+                self.track_replace(&key, stale, &value);
+                let target_index = index_of(&key, resharding.shards.len());
+                let existing = resharding.shards[target_index].write().unwrap().insert(key.clone(), value.clone());
+                self.track_replace(&key, existing, &value);
+            }
+        }
+    }
+
+    pub(crate) fn invalidate_tag(&self, tag: &String) -> usize {
+        let mut invalidated = 0;
+        let mut keys: Vec<String> = self.tag_index.remove(tag).into_iter().collect();
+        keys.sort();
+        for key in keys {
+            let key_index = index_of(&key, self.shards.len());
+            let mut value_by_key = self.shards[key_index].write().unwrap();
+            if let Some(value) = Self::remove_tagged(&mut value_by_key, &key, tag) {
+                self.track_remove(&key, &value);
+                invalidated = invalidated + 1;
+            }
+            if let Some(resharding) = &self.resharding {
+                let target_index = index_of(&key, resharding.shards.len());
+                let mut target_value_by_key = resharding.shards[target_index].write().unwrap();
+                if let Some(value) = Self::remove_tagged(&mut target_value_by_key, &key, tag) {
+                    self.track_remove(&key, &value);
+                    invalidated = invalidated + 1;
+                }
+            }
+        }
+        return invalidated;
+    }
+
+    pub(crate) fn keys_tagged(&self, tag: &String) -> HashSet<String> {
+        return self.tag_index.keys_of(tag);
+    }
+
+    pub(crate) fn get(&self, key: &String) -> Option<Arc<ValueRef>> {
+        let key_index = index_of(key, self.shards.len());
+        let value_by_key = self.shards[key_index].read().unwrap();
+        if let Some(resharding) = &self.resharding {
+            let target_index = index_of(key, resharding.shards.len());
+            if let Some(value) = resharding.shards[target_index].read().unwrap().get(key) {
+                return Some(value.clone());
+            }
+        }
+        return value_by_key.get(key).cloned();
+    }
+
+    pub(crate) fn evict(&self, bucket: BucketIndex) {
+        if let Some(shard) = self.shards.get(bucket) {
+            self.evict_expired(shard);
+        }
+        if let Some(resharding) = &self.resharding {
+            if let Some(shard) = resharding.shards.get(bucket) {
+                self.evict_expired(shard);
+            }
+        }
+    }
+
+    pub(crate) fn memory_usage(&self) -> usize {
+        let mut shards = self.shards.len();
+        if let Some(resharding) = &self.resharding {
+            shards = shards + resharding.shards.len();
+        }
+        return self.used_bytes.load(Ordering::SeqCst) + shards * estimate_shard();
+    }
+
+    //aggressive eviction starts above the high water mark and continues until the usage drops below the low water mark
+    pub(crate) fn is_under_memory_pressure(&self) -> bool {
+        let memory_budget = match &self.memory_budget {
+            None => return false,
+            Some(memory_budget) => memory_budget
+        };
+        let usage = self.memory_usage();
+        if usage > memory_budget.high_water_bytes() {
+            self.under_memory_pressure.store(true, Ordering::SeqCst);
+        } else if usage <= memory_budget.low_water_bytes() {
+            self.under_memory_pressure.store(false, Ordering::SeqCst);
+        }
+        return self.under_memory_pressure.load(Ordering::SeqCst);
+    }
+
+    //frees this bucket's share of the bytes above the low water mark, oldest entries first
+    pub(crate) fn evict_for_capacity(&self, bucket: BucketIndex) {
+        let low_water_bytes = match &self.memory_budget {
+            None => return,
+            Some(memory_budget) => memory_budget.low_water_bytes()
+        };
+        let usage = self.memory_usage();
+        if usage <= low_water_bytes {
+            return;
+        }
+        let mut bytes_to_free = (usage - low_water_bytes) / self.buckets_to_sweep() + 1;
+        if let Some(shard) = self.shards.get(bucket) {
+            bytes_to_free = self.evict_oldest(shard, bytes_to_free);
+        }
+        if let Some(resharding) = &self.resharding {
+            if let Some(shard) = resharding.shards.get(bucket) {
+                self.evict_oldest(shard, bytes_to_free);
+            }
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        let mut total = 0;
+        for shard in &self.shards {
+            total = total + shard.read().unwrap().len();
+        }
+        if let Some(resharding) = &self.resharding {
+            for shard in &resharding.shards {
+                total = total + shard.read().unwrap().len();
+            }
+        }
+        return total;
+    }
+
+    pub(crate) fn begin_resharding(&mut self, buckets: usize) -> Result<(), ReshardingError> {
+        if buckets == 0 {
+            return Err(ReshardingError::InvalidBucketCount);
+        }
+        if self.resharding.is_some() {
+            return Err(ReshardingError::InProgress);
+        }
+        self.resharding = Some(Resharding {
+            shards: Self::empty_shards(buckets),
+            next_bucket: AtomicUsize::new(0),
+            migrated_buckets: AtomicUsize::new(0),
+        });
+        return Ok(());
+    }
+
+    //migrates at most one bucket, returns true once every bucket of the old layout has been moved
+    pub(crate) fn migrate_next(&self) -> bool {
+        let resharding = match &self.resharding {
+            None => return false,
+            Some(resharding) => resharding
+        };
+        let bucket = resharding.next_bucket.fetch_add(1, Ordering::SeqCst);
+        if bucket < self.shards.len() {
+            let mut value_by_key = self.shards[bucket].write().unwrap();
+            for (key, value) in value_by_key.drain() {
+                let target_index = index_of(&key, resharding.shards.len());
+                let mut target_value_by_key = resharding.shards[target_index].write().unwrap();
+                match target_value_by_key.get(&key) {
+                    None => {
+                        target_value_by_key.insert(key, value);
+                    }
+                    Some(newer) => {
+                        let newer = newer.clone();
+                        self.track_replace(&key, Some(value), &newer);
+                    }
+                }
+            }
+            resharding.migrated_buckets.fetch_add(1, Ordering::SeqCst);
+        }
+        return resharding.migrated_buckets.load(Ordering::SeqCst) == self.shards.len();
+    }
+
+    pub(crate) fn complete_resharding(&mut self) -> bool {
+        let completed = match &self.resharding {
+            None => false,
+            Some(resharding) => resharding.migrated_buckets.load(Ordering::SeqCst) == self.shards.len()
+        };
+        if completed {
+            self.shards = self.resharding.take().unwrap().shards;
+        }
+        return completed;
+    }
+
+    pub(crate) fn advance_resharding(storage: &ShardedLockedStorage) {
+        let migrated_all = storage.read().unwrap().migrate_next();
+        if migrated_all {
+            storage.write().unwrap().complete_resharding();
+        }
+    }
+
+    fn evict_expired(&self, shard: &Shard) {
+        let mut value_by_key = shard.write().unwrap();
+        value_by_key.retain(|key, value_ref| {
+            let expired = value_ref.has_expired();
+            if expired {
+                self.track_remove(key, value_ref);
+            }
+            !expired
+        });
+    }
+
+    fn evict_oldest(&self, shard: &Shard, bytes_to_free: usize) -> usize {
+        let mut value_by_key = shard.write().unwrap();
+        let mut keys_by_age: Vec<(String, bool, tokio::time::Instant)> = value_by_key
+            .iter()
+            .map(|(key, value)| (key.clone(), !value.has_expired(), value.created_at()))
+            .collect();
+        keys_by_age.sort_by_key(|(_, living, created_at)| (*living, *created_at));
+
+        let mut remaining = bytes_to_free;
+        for (key, _, _) in keys_by_age {
+            if remaining == 0 {
+                break;
+            }
+            if let Some(value) = value_by_key.remove(&key) {
+                let freed = estimate_entry(&key, &value);
+                self.track_remove(&key, &value);
+                remaining = remaining.saturating_sub(freed);
+            }
+        }
+        return remaining;
+    }
+
+    fn track_insert(&self, key: &String, value: &Arc<ValueRef>) {
+        self.tag_index.index(key, value.tags());
+        self.used_bytes.fetch_add(estimate_entry(key, value), Ordering::SeqCst);
+    }
+
+    fn track_replace(&self, key: &String, replaced: Option<Arc<ValueRef>>, by: &Arc<ValueRef>) {
+        if let Some(replaced) = replaced {
+            let dropped_tags = replaced.tags().difference(by.tags()).cloned().collect();
+            self.tag_index.unindex(key, &dropped_tags);
+            self.used_bytes.fetch_sub(estimate_entry(key, &replaced), Ordering::SeqCst);
+        }
+    }
+
+    fn track_remove(&self, key: &String, value: &Arc<ValueRef>) {
+        self.tag_index.unindex(key, value.tags());
+        self.used_bytes.fetch_sub(estimate_entry(key, value), Ordering::SeqCst);
+    }
+
+    fn with_shards(shards: Vec<Shard>) -> ShardedStorage {
+        return ShardedStorage {
+            shards,
+            resharding: None,
+            tag_index: TagIndex::new(),
+            used_bytes: AtomicUsize::new(0),
+            memory_budget: None,
+            under_memory_pressure: AtomicBool::new(false),
+        };
+    }
+
+    fn remove_tagged(value_by_key: &mut HashMap<String, Arc<ValueRef>>, key: &String, tag: &String) -> Option<Arc<ValueRef>> {
+        return match value_by_key.get(key) {
+            Some(value) if value.has_tag(tag) => value_by_key.remove(key),
+            _ => None
+        };
+    }
+
+    fn empty_shards(buckets: usize) -> Vec<Shard> {
+        let mut shards = Vec::with_capacity(buckets);
+        for _ in 0..buckets {
+            shards.push(RwLock::new(HashMap::new()));
+        }
+        return shards;
+    }
+}
+
+pub(crate) fn index_of(key: &String, buckets: usize) -> BucketIndex {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+
+    let hash = hasher.finish() as usize;
+    return hash % buckets;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::expiry::Expiry;
+
+    use super::*;
+
+    fn value(value: &str) -> Arc<ValueRef> {
+        return Arc::new(ValueRef::new(String::from(value), Expiry::never()));
+    }
+
+    fn tagged_value(value: &str, tags: &[&str]) -> Arc<ValueRef> {
+        let tags = tags.iter().map(|tag| String::from(*tag)).collect();
+        return Arc::new(ValueRef::tagged(String::from(value), Expiry::never(), tags));
+    }
+
+    #[test]
+    fn test_begin_resharding_with_zero_buckets() {
+        let mut storage = ShardedStorage::new(4);
+        let result = storage.begin_resharding(0);
+
+        assert_eq!(Err(ReshardingError::InvalidBucketCount), result);
+    }
+
+    #[test]
+    fn test_begin_resharding_while_resharding() {
+        let mut storage = ShardedStorage::new(4);
+        storage.begin_resharding(8).unwrap();
+
+        let result = storage.begin_resharding(16);
+        assert_eq!(Err(ReshardingError::InProgress), result);
+    }
+
+    #[test]
+    fn test_get_while_resharding_from_the_old_layout() {
+        let mut storage = ShardedStorage::new(4);
+        storage.put(String::from("disk_type"), value("SSD"));
+        storage.begin_resharding(8).unwrap();
+
+        let value = storage.get(&String::from("disk_type"));
+        assert_eq!(&String::from("SSD"), value.unwrap().value());
+    }
+
+    #[test]
+    fn test_put_while_resharding_overrides_the_old_layout() {
+        let mut storage = ShardedStorage::new(4);
+        storage.put(String::from("disk_type"), value("HDD"));
+        storage.begin_resharding(8).unwrap();
+        storage.put(String::from("disk_type"), value("SSD"));
+
+        while !storage.migrate_next() {}
+        storage.complete_resharding();
+
+        let value = storage.get(&String::from("disk_type"));
+        assert_eq!(&String::from("SSD"), value.unwrap().value());
+        assert_eq!(1, storage.len());
+    }
+
+    #[test]
+    fn test_grow_the_number_of_buckets() {
+        let mut storage = ShardedStorage::new(2);
+        for count in 0..100 {
+            storage.put(format!("key{}", count), value(&format!("value{}", count)));
+        }
+        storage.begin_resharding(16).unwrap();
+        while !storage.migrate_next() {}
+
+        assert_eq!(true, storage.complete_resharding());
+        assert_eq!(false, storage.is_resharding());
+        assert_eq!(16, storage.buckets());
+        assert_eq!(100, storage.len());
+        for count in 0..100 {
+            let value = storage.get(&format!("key{}", count));
+            assert_eq!(&format!("value{}", count), value.unwrap().value());
+        }
+    }
+
+    #[test]
+    fn test_shrink_the_number_of_buckets() {
+        let mut storage = ShardedStorage::new(16);
+        for count in 0..100 {
+            storage.put(format!("key{}", count), value(&format!("value{}", count)));
+        }
+        storage.begin_resharding(3).unwrap();
+        while !storage.migrate_next() {}
+        storage.complete_resharding();
+
+        assert_eq!(3, storage.buckets());
+        assert_eq!(100, storage.len());
+        for count in 0..100 {
+            let value = storage.get(&format!("key{}", count));
+            assert_eq!(&format!("value{}", count), value.unwrap().value());
+        }
+    }
+
+    #[test]
+    fn test_complete_resharding_before_migrating_all_the_buckets() {
+        let mut storage = ShardedStorage::new(4);
+        storage.begin_resharding(8).unwrap();
+        storage.migrate_next();
+
+        assert_eq!(false, storage.complete_resharding());
+        assert_eq!(true, storage.is_resharding());
+    }
+
+    #[test]
+    fn test_advance_resharding_incrementally() {
+        let storage = ShardedStorage::new(2).locked();
+        storage.read().unwrap().put(String::from("disk_type"), value("SSD"));
+        storage.write().unwrap().begin_resharding(4).unwrap();
+
+        ShardedStorage::advance_resharding(&storage);
+        assert_eq!(true, storage.read().unwrap().is_resharding());
+
+        ShardedStorage::advance_resharding(&storage);
+        assert_eq!(false, storage.read().unwrap().is_resharding());
+        assert_eq!(4, storage.read().unwrap().buckets());
+    }
+
+    #[test]
+    fn test_invalidate_a_tag_across_shards() {
+        let storage = ShardedStorage::new(8);
+        for count in 0..20 {
+            storage.put(format!("user:1:view{}", count), tagged_value("derived", &["user:1"]));
+        }
+        storage.put(String::from("user:2:profile"), tagged_value("profile", &["user:2"]));
+
+        let invalidated = storage.invalidate_tag(&String::from("user:1"));
+
+        assert_eq!(20, invalidated);
+        assert_eq!(1, storage.len());
+        assert!(storage.keys_tagged(&String::from("user:1")).is_empty());
+        assert_eq!(&String::from("profile"), storage.get(&String::from("user:2:profile")).unwrap().value());
+    }
+
+    #[test]
+    fn test_invalidate_a_tag_while_resharding() {
+        let mut storage = ShardedStorage::new(2);
+        storage.put(String::from("user:1:profile"), tagged_value("profile", &["user:1"]));
+        storage.begin_resharding(4).unwrap();
+        storage.put(String::from("user:1:orders"), tagged_value("orders", &["user:1"]));
+
+        let invalidated = storage.invalidate_tag(&String::from("user:1"));
+
+        assert_eq!(2, invalidated);
+        assert_eq!(0, storage.len());
+    }
+
+    #[test]
+    fn test_put_without_a_tag_removes_the_key_from_the_tag() {
+        let storage = ShardedStorage::new(4);
+        storage.put(String::from("user:1:profile"), tagged_value("profile", &["user:1"]));
+        storage.put(String::from("user:1:profile"), value("profile"));
+
+        let invalidated = storage.invalidate_tag(&String::from("user:1"));
+
+        assert_eq!(0, invalidated);
+        assert_eq!(1, storage.len());
+    }
+
+    #[test]
+    fn test_evict_removes_expired_keys_from_the_tag_index() {
+        let tags = HashSet::from([String::from("user:1")]);
+        let storage = ShardedStorage::new(1);
+        storage.put(String::from("user:1:profile"), Arc::new(ValueRef::tagged(String::from("profile"), Expiry::immediate(), tags)));
+
+        storage.evict(0);
+
+        assert_eq!(0, storage.len());
+        assert!(storage.keys_tagged(&String::from("user:1")).is_empty());
+    }
+
+    #[test]
+    fn test_memory_usage_grows_with_puts_and_shrinks_with_invalidation() {
+        let storage = ShardedStorage::new(4);
+        let empty_usage = storage.memory_usage();
+
+        storage.put(String::from("user:1:profile"), tagged_value("profile", &["user:1"]));
+        let usage = storage.memory_usage();
+        assert!(usage > empty_usage);
+
+        storage.put(String::from("user:1:profile"), tagged_value("profile", &["user:1"]));
+        assert_eq!(usage, storage.memory_usage());
+
+        storage.invalidate_tag(&String::from("user:1"));
+        assert_eq!(empty_usage, storage.memory_usage());
+    }
+
+    #[test]
+    fn test_memory_usage_is_unchanged_by_resharding() {
+        let mut storage = ShardedStorage::new(2);
+        for count in 0..50 {
+            storage.put(format!("key{}", count), value(&format!("value{}", count)));
+        }
+        let usage = storage.memory_usage();
+        storage.begin_resharding(2).unwrap();
+        while !storage.migrate_next() {}
+        storage.complete_resharding();
+
+        assert_eq!(usage, storage.memory_usage());
+    }
+
+    #[test]
+    fn test_no_memory_pressure_without_a_budget() {
+        let storage = ShardedStorage::new(2);
+        storage.put(String::from("disk_type"), value("SSD"));
+
+        assert_eq!(false, storage.is_under_memory_pressure());
+    }
+
+    #[test]
+    fn test_evict_for_capacity_until_below_the_low_water_mark() {
+        let storage = ShardedStorage::new(4).with_memory_budget(MemoryBudget::new(4096, 2048));
+        for count in 0..100 {
+            storage.put(format!("key{}", count), value(&format!("value{}", count)));
+        }
+        assert_eq!(true, storage.is_under_memory_pressure());
+
+        for _ in 0..10 {
+            for bucket in 0..storage.buckets_to_sweep() {
+                storage.evict_for_capacity(bucket);
+            }
+        }
+
+        assert!(storage.memory_usage() <= 2048);
+        assert_eq!(false, storage.is_under_memory_pressure());
+        assert!(storage.len() > 0);
+    }
+
+    #[test]
+    fn test_evict_for_capacity_prefers_expired_entries() {
+        let storage = ShardedStorage::new(1).with_memory_budget(MemoryBudget::new(0, 0));
+        storage.put(String::from("living"), value("living_value"));
+        storage.put(String::from("expired"), Arc::new(ValueRef::new(String::from("expired_value"), Expiry::immediate())));
+        let bytes_to_free = storage.memory_usage() - estimate_entry(&String::from("living"), &value("living_value")) - estimate_shard();
+
+        storage.evict_oldest(&storage.shards[0], bytes_to_free);
+
+        assert_eq!(1, storage.len());
+        assert!(storage.get(&String::from("living")).is_some());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use loom::thread;
+
+    use crate::cache::expiry::Expiry;
+
+    use super::*;
+
+    fn value(value: &str) -> Arc<ValueRef> {
+        return Arc::new(ValueRef::new(String::from(value), Expiry::never()));
+    }
+
+    #[test]
+    fn test_get_while_migrating() {
+        loom::model(|| {
+            let storage = ShardedStorage::new(2).locked();
+            storage.read().unwrap().put(String::from("disk_type"), value("SSD"));
+            storage.write().unwrap().begin_resharding(1).unwrap();
+
+            let migrating_storage = storage.clone();
+            let migration = thread::spawn(move || {
+                ShardedStorage::advance_resharding(&migrating_storage);
+                ShardedStorage::advance_resharding(&migrating_storage);
+            });
+
+            let value = storage.read().unwrap().get(&String::from("disk_type"));
+            assert_eq!(&String::from("SSD"), value.unwrap().value());
+
+            migration.join().unwrap();
+            assert_eq!(false, storage.read().unwrap().is_resharding());
+        });
+    }
+
+    #[test]
+    fn test_put_while_migrating() {
+        loom::model(|| {
+            let storage = ShardedStorage::new(2).locked();
+            storage.read().unwrap().put(String::from("disk_type"), value("HDD"));
+            storage.write().unwrap().begin_resharding(1).unwrap();
+
+            let migrating_storage = storage.clone();
+            let migration = thread::spawn(move || {
+                ShardedStorage::advance_resharding(&migrating_storage);
+                ShardedStorage::advance_resharding(&migrating_storage);
+            });
+
+            storage.read().unwrap().put(String::from("disk_type"), value("SSD"));
+            migration.join().unwrap();
+
+            let read_storage = storage.read().unwrap();
+            assert_eq!(&String::from("SSD"), read_storage.get(&String::from("disk_type")).unwrap().value());
+            assert_eq!(1, read_storage.len());
+        });
+    }
+
+    #[test]
+    fn test_put_while_evicting() {
+        loom::model(|| {
+            let storage = ShardedStorage::new(1).locked();
+            let expired = Arc::new(ValueRef::new(String::from("HDD"), Expiry::immediate()));
+            storage.read().unwrap().put(String::from("disk_type"), expired);
+
+            let evicting_storage = storage.clone();
+            let eviction = thread::spawn(move || {
+                evicting_storage.read().unwrap().evict(0);
+            });
+
+            let living = value("SSD");
+            storage.read().unwrap().put(String::from("disk_type"), living.clone());
+            eviction.join().unwrap();
+
+            let read_storage = storage.read().unwrap();
+            assert_eq!(&String::from("SSD"), read_storage.get(&String::from("disk_type")).unwrap().value());
+            assert_eq!(estimate_entry(&String::from("disk_type"), &living) + estimate_shard(), read_storage.memory_usage());
+        });
+    }
+
+    #[test]
+    fn test_put_while_invalidating_a_tag() {
+        loom::model(|| {
+            let tags: HashSet<String> = HashSet::from([String::from("user:1")]);
+            let storage = ShardedStorage::new(2).locked();
+            let profile = Arc::new(ValueRef::tagged(String::from("profile"), Expiry::never(), tags.clone()));
+            storage.read().unwrap().put(String::from("user:1:profile"), profile);
+
+            let invalidating_storage = storage.clone();
+            let invalidation = thread::spawn(move || {
+                invalidating_storage.read().unwrap().invalidate_tag(&String::from("user:1"));
+            });
+
+            let orders = Arc::new(ValueRef::tagged(String::from("orders"), Expiry::never(), tags));
+            storage.read().unwrap().put(String::from("user:1:orders"), orders);
+            invalidation.join().unwrap();
+
+            let read_storage = storage.read().unwrap();
+            let tagged_keys = read_storage.keys_tagged(&String::from("user:1"));
+            assert!(read_storage.get(&String::from("user:1:profile")).is_none());
+            assert_eq!(read_storage.get(&String::from("user:1:orders")).is_some(), tagged_keys.contains("user:1:orders"));
+            assert_eq!(read_storage.len(), tagged_keys.len());
+        });
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(loom)]
+pub(crate) use loom::sync::{Arc, RwLock};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[cfg(not(loom))]
+pub(crate) use std::sync::{Arc, RwLock};
+#[cfg(not(loom))]
+pub(crate) use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
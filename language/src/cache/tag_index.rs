@@ -0,0 +1,89 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::cache::sync::RwLock;
+
+pub(crate) struct TagIndex {
+    keys_by_tag: RwLock<HashMap<String, HashSet<String>>>,
+}
+
+impl TagIndex {
+    pub(crate) fn new() -> TagIndex {
+        return TagIndex { keys_by_tag: RwLock::new(HashMap::new()) };
+    }
+
+    pub(crate) fn index(&self, key: &String, tags: &HashSet<String>) {
+        if tags.is_empty() {
+            return;
+        }
+        let mut keys_by_tag = self.keys_by_tag.write().unwrap();
+        for tag in tags {
+            keys_by_tag.entry(tag.clone()).or_default().insert(key.clone());
+        }
+    }
+
+    pub(crate) fn unindex(&self, key: &String, tags: &HashSet<String>) {
+        if tags.is_empty() {
+            return;
+        }
+        let mut keys_by_tag = self.keys_by_tag.write().unwrap();
+        for tag in tags {
+            if let Some(keys) = keys_by_tag.get_mut(tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    keys_by_tag.remove(tag);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn remove(&self, tag: &String) -> HashSet<String> {
+        return self.keys_by_tag.write().unwrap().remove(tag).unwrap_or_default();
+    }
+
+    pub(crate) fn keys_of(&self, tag: &String) -> HashSet<String> {
+        return self.keys_by_tag.read().unwrap().get(tag).cloned().unwrap_or_default();
+    }
+
+    pub(crate) fn tag_count(&self) -> usize {
+        return self.keys_by_tag.read().unwrap().len();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(tags: &[&str]) -> HashSet<String> {
+        return tags.iter().map(|tag| String::from(*tag)).collect();
+    }
+
+    #[test]
+    fn test_keys_of_an_indexed_tag() {
+        let tag_index = TagIndex::new();
+        tag_index.index(&String::from("user:1:profile"), &tags(&["user:1"]));
+        tag_index.index(&String::from("user:1:orders"), &tags(&["user:1", "orders"]));
+
+        let keys = tag_index.keys_of(&String::from("user:1"));
+        assert_eq!(tags(&["user:1:profile", "user:1:orders"]), keys);
+    }
+
+    #[test]
+    fn test_unindex_drops_an_empty_tag() {
+        let tag_index = TagIndex::new();
+        tag_index.index(&String::from("user:1:profile"), &tags(&["user:1"]));
+        tag_index.unindex(&String::from("user:1:profile"), &tags(&["user:1"]));
+
+        assert!(tag_index.keys_of(&String::from("user:1")).is_empty());
+        assert_eq!(0, tag_index.tag_count());
+    }
+
+    #[test]
+    fn test_remove_a_tag() {
+        let tag_index = TagIndex::new();
+        tag_index.index(&String::from("user:1:profile"), &tags(&["user:1"]));
+
+        let keys = tag_index.remove(&String::from("user:1"));
+        assert_eq!(tags(&["user:1:profile"]), keys);
+        assert!(tag_index.keys_of(&String::from("user:1")).is_empty());
+    }
+}
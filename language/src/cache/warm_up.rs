@@ -0,0 +1,275 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::cache::evicting_cache::EvictingCache;
+use crate::cache::expiry::Expiry;
+
+pub(crate) type Loader = Arc<dyn Fn(&String) -> Option<String> + Send + Sync>;
+
+pub(crate) type ExpiryFor = Arc<dyn Fn(&String) -> Expiry + Send + Sync>;
+
+pub(crate) type ProgressListener = Arc<dyn Fn(WarmUpProgress) + Send + Sync>;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct WarmUpProgress {
+    pub(crate) processed: usize,
+    pub(crate) loaded: usize,
+    pub(crate) missed: usize,
+}
+
+pub(crate) struct WarmUp {
+    loader: Loader,
+    parallelism: usize,
+    expiry_for: ExpiryFor,
+    progress_listener: Option<ProgressListener>,
+    report_every: usize,
+}
+
+struct Counters {
+    processed: AtomicUsize,
+    loaded: AtomicUsize,
+    missed: AtomicUsize,
+    //the periodic report with the highest count, the final report is skipped when it already counted every key
+    last_reported: Mutex<Option<WarmUpProgress>>,
+}
+
+impl WarmUp {
+    const DEFAULT_PARALLELISM: usize = 4;
+    const DEFAULT_REPORT_EVERY: usize = 100;
+
+    pub(crate) fn new(loader: Loader) -> WarmUp {
+        return WarmUp {
+            loader,
+            parallelism: Self::DEFAULT_PARALLELISM,
+            expiry_for: Arc::new(|_| Expiry::never()),
+            progress_listener: None,
+            report_every: Self::DEFAULT_REPORT_EVERY,
+        };
+    }
+
+    pub(crate) fn with_parallelism(self, parallelism: usize) -> WarmUp {
+        return WarmUp { parallelism: parallelism.max(1), ..self };
+    }
+
+    pub(crate) fn with_expiry(self, expiry_for: ExpiryFor) -> WarmUp {
+        return WarmUp { expiry_for, ..self };
+    }
+
+    pub(crate) fn on_progress(self, report_every: usize, progress_listener: ProgressListener) -> WarmUp {
+        return WarmUp { progress_listener: Some(progress_listener), report_every: report_every.max(1), ..self };
+    }
+
+    pub(crate) fn warm_from_file(&self, cache: &EvictingCache, path: &Path) -> io::Result<WarmUpProgress> {
+        let file = File::open(path)?;
+        return self.warm_from_reader(cache, BufReader::new(file));
+    }
+
+    pub(crate) fn warm_from_reader<R: BufRead + Send>(&self, cache: &EvictingCache, reader: R) -> io::Result<WarmUpProgress> {
+        let keys = reader
+            .lines()
+            .map(|line| line.map(|key| String::from(key.trim())))
+            .filter(|line| !matches!(line, Ok(key) if key.is_empty()));
+        return self.warm(cache, keys);
+    }
+
+    pub(crate) fn warm_from_keys<I: Iterator<Item=String> + Send>(&self, cache: &EvictingCache, keys: I) -> WarmUpProgress {
+        return self.warm(cache, keys.map(Ok)).unwrap();
+    }
+
+    //workers pull keys one at a time from the shared source, so at most `parallelism` loads run concurrently
+    fn warm<I: Iterator<Item=io::Result<String>> + Send>(&self, cache: &EvictingCache, keys: I) -> io::Result<WarmUpProgress> {
+        let keys = Mutex::new(keys);
+        let failure: Mutex<Option<io::Error>> = Mutex::new(None);
+        let counters = Counters {
+            processed: AtomicUsize::new(0),
+            loaded: AtomicUsize::new(0),
+            missed: AtomicUsize::new(0),
+            last_reported: Mutex::new(None),
+        };
+
+        thread::scope(|scope| {
+            for _ in 0..self.parallelism {
+                scope.spawn(|| {
+                    loop {
+                        let next = keys.lock().unwrap().next();
+                        let key = match next {
+                            None => break,
+                            Some(Err(err)) => {
+                                failure.lock().unwrap().get_or_insert(err);
+                                break;
+                            }
+                            Some(Ok(key)) => key
+                        };
+                        if failure.lock().unwrap().is_some() {
+                            break;
+                        }
+                        self.load(cache, key, &counters);
+                    }
+                });
+            }
+        });
+
+        if let Some(err) = failure.into_inner().unwrap() {
+            return Err(err);
+        }
+        let progress = counters.progress();
+        if let Some(progress_listener) = &self.progress_listener {
+            let mut last_reported = counters.last_reported.lock().unwrap();
+            if last_reported.is_none_or(|last_reported| last_reported.processed != progress.processed) {
+                progress_listener(progress);
+                *last_reported = Some(progress);
+            }
+        }
+        return Ok(progress);
+    }
+
+    fn load(&self, cache: &EvictingCache, key: String, counters: &Counters) {
+        match (self.loader)(&key) {
+            None => {
+                counters.missed.fetch_add(1, Ordering::SeqCst);
+            }
+            Some(value) => {
+                let expiry = (self.expiry_for)(&key);
+                cache.put_with_expiry(key, value, expiry);
+                counters.loaded.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        let processed = counters.processed.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(progress_listener) = &self.progress_listener {
+            if processed.is_multiple_of(self.report_every) {
+                //the processed count of this load, so two loads never report the same count even when they finish together
+                let mut last_reported = counters.last_reported.lock().unwrap();
+                let progress = WarmUpProgress { processed, ..counters.progress() };
+                progress_listener(progress);
+                if last_reported.is_none_or(|last_reported| last_reported.processed < processed) {
+                    *last_reported = Some(progress);
+                }
+            }
+        }
+    }
+}
+
+impl Counters {
+    fn progress(&self) -> WarmUpProgress {
+        return WarmUpProgress {
+            processed: self.processed.load(Ordering::SeqCst),
+            loaded: self.loaded.load(Ordering::SeqCst),
+            missed: self.missed.load(Ordering::SeqCst),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn loader() -> Loader {
+        return Arc::new(|key: &String| {
+            let id: u32 = key.trim_start_matches("key").parse().unwrap();
+            return if id.is_multiple_of(2) { Some(format!("value{}", id)) } else { None };
+        });
+    }
+
+    #[test]
+    fn test_warm_up_from_keys() {
+        let cache = EvictingCache::new(16);
+        let progress = WarmUp::new(loader()).warm_from_keys(&cache, (0..10).map(|id| format!("key{}", id)));
+
+        assert_eq!(WarmUpProgress { processed: 10, loaded: 5, missed: 5 }, progress);
+        assert_eq!(&String::from("value4"), cache.get(String::from("key4")).unwrap().value());
+        assert!(cache.get(String::from("key5")).is_none());
+    }
+
+    #[test]
+    fn test_warm_up_from_a_file() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("keys.txt");
+        fs::write(&path, "key0\nkey1\n\n  key2  \n").unwrap();
+
+        let cache = EvictingCache::new(16);
+        let progress = WarmUp::new(loader()).warm_from_file(&cache, &path);
+
+        assert_eq!(WarmUpProgress { processed: 3, loaded: 2, missed: 1 }, progress.unwrap());
+        assert_eq!(&String::from("value2"), cache.get(String::from("key2")).unwrap().value());
+    }
+
+    #[test]
+    fn test_warm_up_from_a_missing_file() {
+        let cache = EvictingCache::new(16);
+        let progress = WarmUp::new(loader()).warm_from_file(&cache, Path::new("/non/existing/keys.txt"));
+
+        assert!(progress.is_err());
+    }
+
+    #[test]
+    fn test_warm_up_with_an_expiry_per_entry() {
+        let cache = EvictingCache::new(16);
+        let expiry_for: ExpiryFor = Arc::new(|key: &String| {
+            return if key == "key0" { Expiry::immediate() } else { Expiry::never() };
+        });
+        WarmUp::new(loader()).with_expiry(expiry_for).warm_from_keys(&cache, (0..4).map(|id| format!("key{}", id)));
+
+        assert!(cache.get(String::from("key0")).is_none());
+        assert_eq!(&String::from("value2"), cache.get(String::from("key2")).unwrap().value());
+    }
+
+    #[test]
+    fn test_warm_up_with_bounded_parallelism() {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let (running_clone, max_running_clone) = (running.clone(), max_running.clone());
+        let loader: Loader = Arc::new(move |key: &String| {
+            let now_running = running_clone.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running_clone.fetch_max(now_running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(5));
+            running_clone.fetch_sub(1, Ordering::SeqCst);
+            return Some(key.clone());
+        });
+
+        let cache = EvictingCache::new(16);
+        let progress = WarmUp::new(loader).with_parallelism(3).warm_from_keys(&cache, (0..30).map(|id| format!("key{}", id)));
+
+        assert_eq!(30, progress.loaded);
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn test_warm_up_reports_progress() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports_clone = reports.clone();
+        let listener: ProgressListener = Arc::new(move |progress| reports_clone.lock().unwrap().push(progress.processed));
+
+        let cache = EvictingCache::new(16);
+        WarmUp::new(loader())
+            .with_parallelism(1)
+            .on_progress(4, listener)
+            .warm_from_keys(&cache, (0..10).map(|id| format!("key{}", id)));
+
+        assert_eq!(vec![4, 8, 10], *reports.lock().unwrap());
+    }
+
+    #[test]
+    fn test_warm_up_does_not_repeat_the_last_report() {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports_clone = reports.clone();
+        let listener: ProgressListener = Arc::new(move |progress| reports_clone.lock().unwrap().push(progress.processed));
+
+        let cache = EvictingCache::new(16);
+        WarmUp::new(loader())
+            .with_parallelism(1)
+            .on_progress(4, listener)
+            .warm_from_keys(&cache, (0..8).map(|id| format!("key{}", id)));
+
+        assert_eq!(vec![4, 8], *reports.lock().unwrap());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod singular_update_queue;
+pub mod replication;
+pub mod raft;
+pub mod simulation;
+pub mod lsm;
@@ -0,0 +1,109 @@
+use crate::singular_update_queue::write_ahead_log::{read_u32, read_u64};
+
+const FNV_OFFSET: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+
+//answers whether a key may be in a table, a false answer saves reading a block, a true answer can be wrong at the configured rate
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    pub fn new(expected_keys: usize, false_positive_rate: f64) -> BloomFilter {
+        assert!(false_positive_rate > 0.0 && false_positive_rate < 1.0, "the false positive rate must be between 0 and 1");
+        let expected_keys = expected_keys.max(1) as f64;
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-expected_keys * false_positive_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let hashes = ((bits as f64 / expected_keys) * ln2).round().clamp(1.0, 30.0) as u32;
+        return BloomFilter { bits: vec![0; bits.div_ceil(64)], hashes };
+    }
+
+    pub fn insert(&mut self, key: &str) {
+        for bit in self.bit_positions(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn might_contain(&self, key: &str) -> bool {
+        return self.bit_positions(key).all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0);
+    }
+
+    //layout: hash count | word count | words
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(8 + self.bits.len() * 8);
+        bytes.extend_from_slice(&self.hashes.to_le_bytes());
+        bytes.extend_from_slice(&(self.bits.len() as u32).to_le_bytes());
+        for word in &self.bits {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        return bytes;
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<BloomFilter> {
+        if bytes.len() < 8 {
+            return None;
+        }
+        let hashes = read_u32(bytes);
+        let words = read_u32(&bytes[4..]) as usize;
+        if hashes == 0 || words == 0 || bytes.len() != 8 + words * 8 {
+            return None;
+        }
+        let bits = (0..words).map(|word| read_u64(&bytes[8 + word * 8..])).collect();
+        return Some(BloomFilter { bits, hashes });
+    }
+
+    //double hashing, the second hash is forced odd so that the probes do not repeat
+    fn bit_positions(&self, key: &str) -> impl Iterator<Item = usize> {
+        let first = fnv1a(key.as_bytes(), FNV_OFFSET);
+        let second = fnv1a(key.as_bytes(), first) | 1;
+        let total = (self.bits.len() * 64) as u64;
+        return (0..self.hashes as u64).map(move |probe| (first.wrapping_add(probe.wrapping_mul(second)) % total) as usize);
+    }
+}
+
+//a hash which stays the same across rust releases, the filters are persisted in the tables
+fn fnv1a(bytes: &[u8], seed: u64) -> u64 {
+    let mut hash = seed;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    return hash;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_never_miss_an_inserted_key() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for key in 0..1000 {
+            filter.insert(&format!("key{}", key));
+        }
+        assert_eq!(true, (0..1000).all(|key| filter.might_contain(&format!("key{}", key))));
+    }
+
+    #[test]
+    fn test_keep_false_positives_near_the_rate() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for key in 0..1000 {
+            filter.insert(&format!("key{}", key));
+        }
+        let false_positives = (0..10000).filter(|key| filter.might_contain(&format!("absent{}", key))).count();
+        assert_eq!(true, false_positives < 300);
+    }
+
+    #[test]
+    fn test_decode_an_encoded_filter() {
+        let mut filter = BloomFilter::new(10, 0.01);
+        filter.insert("key1");
+
+        let decoded = BloomFilter::decode(&filter.encode()).unwrap();
+        assert_eq!(filter, decoded);
+        assert_eq!(true, decoded.might_contain("key1"));
+        assert_eq!(None, BloomFilter::decode(&[1, 2, 3]));
+    }
+}
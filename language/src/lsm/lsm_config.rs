@@ -0,0 +1,128 @@
+use crate::singular_update_queue::write_ahead_log::FsyncPolicy;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LsmConfig {
+    memtable_size: usize,
+    block_size: usize,
+    false_positive_rate: f64,
+    level0_tables: usize,
+    level1_size: u64,
+    level_size_multiplier: u64,
+    table_size: u64,
+    fsync_policy: FsyncPolicy,
+}
+
+impl Default for LsmConfig {
+    fn default() -> Self {
+        return LsmConfig {
+            memtable_size: 4 * 1024 * 1024,
+            block_size: 4096,
+            false_positive_rate: 0.01,
+            level0_tables: 4,
+            level1_size: 10 * 1024 * 1024,
+            level_size_multiplier: 10,
+            table_size: 2 * 1024 * 1024,
+            fsync_policy: FsyncPolicy::Always,
+        };
+    }
+}
+
+impl LsmConfig {
+    //bytes of keys and values the memtable buffers before it is flushed to a level 0 table
+    pub fn with_memtable_size(mut self, memtable_size: usize) -> LsmConfig {
+        assert!(memtable_size > 0, "memtable size must be greater than zero");
+        self.memtable_size = memtable_size;
+        return self;
+    }
+
+    pub fn with_block_size(mut self, block_size: usize) -> LsmConfig {
+        assert!(block_size > 0, "block size must be greater than zero");
+        self.block_size = block_size;
+        return self;
+    }
+
+    pub fn with_false_positive_rate(mut self, false_positive_rate: f64) -> LsmConfig {
+        assert!(false_positive_rate > 0.0 && false_positive_rate < 1.0, "false positive rate must be between 0 and 1");
+        self.false_positive_rate = false_positive_rate;
+        return self;
+    }
+
+    //number of level 0 tables, which may overlap each other, that triggers their compaction into level 1
+    pub fn with_level0_tables(mut self, level0_tables: usize) -> LsmConfig {
+        assert!(level0_tables > 0, "level 0 tables must be greater than zero");
+        self.level0_tables = level0_tables;
+        return self;
+    }
+
+    //level 1 holds up to level1_size bytes and every deeper level multiplier times the one above it
+    pub fn with_level_sizes(mut self, level1_size: u64, level_size_multiplier: u64) -> LsmConfig {
+        assert!(level1_size > 0, "level 1 size must be greater than zero");
+        assert!(level_size_multiplier > 1, "level size multiplier must be greater than one");
+        self.level1_size = level1_size;
+        self.level_size_multiplier = level_size_multiplier;
+        return self;
+    }
+
+    //compaction splits its output into tables of about this many bytes
+    pub fn with_table_size(mut self, table_size: u64) -> LsmConfig {
+        assert!(table_size > 0, "table size must be greater than zero");
+        self.table_size = table_size;
+        return self;
+    }
+
+    //applies to the log which makes the memtable durable, tables are always synced when written
+    pub fn with_fsync_policy(mut self, fsync_policy: FsyncPolicy) -> LsmConfig {
+        self.fsync_policy = fsync_policy;
+        return self;
+    }
+
+    pub fn memtable_size(&self) -> usize {
+        return self.memtable_size;
+    }
+
+    pub fn block_size(&self) -> usize {
+        return self.block_size;
+    }
+
+    pub fn false_positive_rate(&self) -> f64 {
+        return self.false_positive_rate;
+    }
+
+    pub fn level0_tables(&self) -> usize {
+        return self.level0_tables;
+    }
+
+    //the most bytes a level, 1 or deeper, may hold before one of its tables is compacted into the next level
+    pub fn max_level_size(&self, level: usize) -> u64 {
+        assert!(level > 0, "level 0 is bounded by its number of tables");
+        return self.level1_size.saturating_mul(self.level_size_multiplier.saturating_pow(level as u32 - 1));
+    }
+
+    pub fn table_size(&self) -> u64 {
+        return self.table_size;
+    }
+
+    pub fn fsync_policy(&self) -> FsyncPolicy {
+        return self.fsync_policy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grow_levels_by_the_multiplier() {
+        let config = LsmConfig::default().with_level_sizes(100, 10);
+
+        assert_eq!(100, config.max_level_size(1));
+        assert_eq!(1000, config.max_level_size(2));
+        assert_eq!(10000, config.max_level_size(3));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reject_a_multiplier_of_one() {
+        LsmConfig::default().with_level_sizes(100, 1);
+    }
+}
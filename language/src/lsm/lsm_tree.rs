@@ -0,0 +1,600 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::lsm::lsm_config::LsmConfig;
+use crate::lsm::memtable::Memtable;
+use crate::lsm::merging_iterator::{MergingIterator, TableSource};
+use crate::lsm::sstable::{SsTable, TableEntry, TABLE_EXTENSION};
+use crate::singular_update_queue::key_value_store::{KeyValueStore, Mutation};
+use crate::singular_update_queue::log_structured_store::{corrupted_record, decode_record, encode_record};
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::write_ahead_log::FsyncPolicy;
+
+const MEMTABLE_LOG: &str = "memtable.log";
+const MANIFEST: &str = "MANIFEST";
+const TEMPORARY_MANIFEST: &str = "MANIFEST.tmp";
+
+//a log-structured merge tree: writes go to a logged memtable which is flushed to level 0 tables once full,
+//level 0 tables may overlap and are searched newest first, every deeper level is a sorted run of disjoint tables,
+//the manifest lists the live tables per level and is replaced atomically, so a table is live only once the manifest names it
+pub struct LsmTree {
+    directory: PathBuf,
+    config: LsmConfig,
+    state: RwLock<TreeState>,
+    next_table_id: AtomicU64,
+    //one compaction at a time, it holds the compaction pointer of every level: the last key of the table it compacted last
+    compaction: Mutex<Vec<String>>,
+    //the failure of the last flush or compaction a write triggered, the next write retries it
+    maintenance_error: Mutex<Option<QueueError>>,
+}
+
+struct TreeState {
+    memtable: Memtable,
+    log: File,
+    log_size: u64,
+    unsynced: usize,
+    //level 0 newest first, the deeper levels ordered by their first key
+    levels: Vec<Vec<Arc<SsTable>>>,
+}
+
+//the tables one compaction merges, picked under the read lock and merged without holding the lock
+struct Compaction {
+    level: usize,
+    //level 0 newest first
+    inputs: Vec<Arc<SsTable>>,
+    overlapping: Vec<Arc<SsTable>>,
+    //no level below the output holds tables
+    bottom: bool,
+}
+
+impl LsmTree {
+    //loads the tables named by the manifest, removes the ones a crash left behind and replays the memtable log
+    pub fn open(directory: &Path, config: LsmConfig) -> io::Result<LsmTree> {
+        fs::create_dir_all(directory)?;
+        let mut levels: Vec<Vec<Arc<SsTable>>> = Vec::new();
+        let mut next_table_id = 1;
+        for (level, id) in read_manifest(directory)? {
+            if levels.len() <= level {
+                levels.resize_with(level + 1, Vec::new);
+            }
+            levels[level].push(Arc::new(SsTable::open(directory, id)?));
+            next_table_id = next_table_id.max(id + 1);
+        }
+        let live: HashSet<PathBuf> = levels.iter().flatten().map(|table| table.path().to_path_buf()).collect();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            let extension = path.extension().and_then(|extension| extension.to_str());
+            if (extension == Some(TABLE_EXTENSION) && !live.contains(&path)) || extension == Some("tmp") {
+                fs::remove_file(path)?;
+            }
+        }
+
+        let path = directory.join(MEMTABLE_LOG);
+        let bytes = if path.exists() { fs::read(&path)? } else { Vec::new() };
+        let mut memtable = Memtable::new();
+        let mut offset = 0;
+        while let Some(record) = decode_record(&bytes[offset as usize..]).map_err(|error| corrupted_record(&path, offset, error))? {
+            for (mutation, _) in record.mutations {
+                memtable.apply(mutation);
+            }
+            offset += record.length;
+        }
+        let log = OpenOptions::new().append(true).create(true).open(&path)?;
+        //a torn tail is a record which was being written when the process crashed
+        if offset < bytes.len() as u64 {
+            log.set_len(offset)?;
+        }
+
+        return Ok(LsmTree {
+            directory: directory.to_path_buf(),
+            config,
+            state: RwLock::new(TreeState { memtable, log, log_size: offset, unsynced: 0, levels }),
+            next_table_id: AtomicU64::new(next_table_id),
+            compaction: Mutex::new(Vec::new()),
+            maintenance_error: Mutex::new(None),
+        });
+    }
+
+    //number of tables in every level, level 0 first
+    pub fn tables_per_level(&self) -> Result<Vec<usize>, QueueError> {
+        let state = self.state.read().map_err(|_| QueueError::Poisoned)?;
+        return Ok(state.levels.iter().map(|level| level.len()).collect());
+    }
+
+    //a write succeeds once it is durable in the memtable log, the flush or compaction it triggers reports its failure here
+    pub fn maintenance_error(&self) -> Option<QueueError> {
+        return self.maintenance_error.lock().unwrap_or_else(PoisonError::into_inner).clone();
+    }
+
+    //writes the memtable to a level 0 table even if it is not full yet
+    pub fn flush(&self) -> Result<(), QueueError> {
+        let mut state = self.state.write().map_err(|_| QueueError::Poisoned)?;
+        self.flush_memtable(&mut state).map_err(failed)?;
+        drop(state);
+        return self.compact();
+    }
+
+    fn flush_memtable(&self, state: &mut TreeState) -> io::Result<()> {
+        if state.memtable.is_empty() {
+            return Ok(());
+        }
+        let entries: Vec<TableEntry> = state.memtable.entries().iter().map(|(key, value)| (key.clone(), value.clone())).collect();
+        let table = self.write_table(&entries)?;
+        if state.levels.is_empty() {
+            state.levels.push(Vec::new());
+        }
+        state.levels[0].insert(0, Arc::new(table));
+        write_manifest(&self.directory, &state.levels)?;
+
+        //a crash before the truncation replays entries which the new table also holds, applying them twice is harmless
+        state.log.set_len(0)?;
+        state.log_size = 0;
+        state.unsynced = 0;
+        state.memtable = Memtable::new();
+        return Ok(());
+    }
+
+    //compacts level after level until level 0 is under its table count and every deeper level under its size,
+    //the tables are merged without holding the tree lock, so reads and writes go on while the new tables are written
+    fn compact(&self) -> Result<(), QueueError> {
+        let mut pointers = self.compaction.lock().map_err(|_| QueueError::Poisoned)?;
+        loop {
+            let compaction = {
+                let state = self.state.read().map_err(|_| QueueError::Poisoned)?;
+                match self.next_compaction(&state, &mut pointers) {
+                    Some(compaction) => compaction,
+                    None => return Ok(()),
+                }
+            };
+            let outputs = self.merge(&compaction).map_err(failed)?;
+
+            let mut state = self.state.write().map_err(|_| QueueError::Poisoned)?;
+            install(&self.directory, &mut state, &compaction, outputs).map_err(failed)?;
+            drop(state);
+            //no reader can reach the replaced tables once the new levels are installed
+            for table in compaction.inputs.iter().chain(compaction.overlapping.iter()) {
+                fs::remove_file(table.path()).map_err(failed)?;
+            }
+        }
+    }
+
+    //all of level 0, or the table of a deeper level after its compaction pointer, with the tables it overlaps in the next level,
+    //the pointer wraps around at the end of the level so that every key range of the level is compacted in turn
+    fn next_compaction(&self, state: &TreeState, pointers: &mut Vec<String>) -> Option<Compaction> {
+        let level = if state.levels.first().is_some_and(|tables| tables.len() >= self.config.level0_tables()) {
+            0
+        } else {
+            (1..state.levels.len()).find(|level| level_size(&state.levels[*level]) > self.config.max_level_size(*level))?
+        };
+        let inputs: Vec<Arc<SsTable>> = if level == 0 {
+            state.levels[0].clone()
+        } else {
+            if pointers.len() <= level {
+                pointers.resize(level + 1, String::new());
+            }
+            let tables = &state.levels[level];
+            let position = tables.iter().position(|table| table.first_key() > pointers[level].as_str()).unwrap_or(0);
+            pointers[level] = tables[position].last_key().to_string();
+            vec![tables[position].clone()]
+        };
+        let start = inputs.iter().map(|table| table.first_key()).min().unwrap();
+        let end = inputs.iter().map(|table| table.last_key()).max().unwrap();
+        let overlapping: Vec<Arc<SsTable>> = state.levels.get(level + 1).map_or(Vec::new(), |tables| {
+            tables.iter().filter(|table| table.overlaps(start, end)).cloned().collect()
+        });
+        let bottom = state.levels.iter().skip(level + 2).all(|tables| tables.is_empty());
+        return Some(Compaction { level, inputs, overlapping, bottom });
+    }
+
+    //streams the tables through a merge, holding a block per table, and cuts a new table every time the output reaches the table size,
+    //tombstones are dropped at the bottom since nothing older than the output can hold their key
+    fn merge(&self, compaction: &Compaction) -> io::Result<Vec<Arc<SsTable>>> {
+        let mut sources: Vec<TableSource> =
+            compaction.inputs.iter().map(|table| Box::new(table.iter()) as TableSource).collect();
+        //the overlapping tables are disjoint and sorted, together they are one source older than every input
+        sources.push(Box::new(compaction.overlapping.iter().flat_map(|table| table.iter())));
+
+        let mut outputs = Vec::new();
+        let mut entries: Vec<TableEntry> = Vec::new();
+        let mut size = 0;
+        for entry in MergingIterator::new(sources) {
+            let (key, value) = entry?;
+            if compaction.bottom && value.is_none() {
+                continue;
+            }
+            size += key.len() + value.as_ref().map_or(0, |value| value.len());
+            entries.push((key, value));
+            if size as u64 >= self.config.table_size() {
+                outputs.push(Arc::new(self.write_table(&entries)?));
+                entries.clear();
+                size = 0;
+            }
+        }
+        if !entries.is_empty() {
+            outputs.push(Arc::new(self.write_table(&entries)?));
+        }
+        return Ok(outputs);
+    }
+
+    fn write_table(&self, entries: &[TableEntry]) -> io::Result<SsTable> {
+        let id = self.next_table_id.fetch_add(1, Ordering::SeqCst);
+        return SsTable::write(&self.directory, id, entries, self.config.block_size(), self.config.false_positive_rate());
+    }
+}
+
+impl KeyValueStore for LsmTree {
+    //the memtable first, then level 0 newest first, then the one table of every deeper level whose range holds the key
+    fn get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        let state = self.state.read().map_err(|_| QueueError::Poisoned)?;
+        if let Some(value) = state.memtable.get(key) {
+            return Ok(value);
+        }
+        for (level, tables) in state.levels.iter().enumerate() {
+            let candidates: Vec<&Arc<SsTable>> = if level == 0 {
+                tables.iter().collect()
+            } else {
+                let position = tables.partition_point(|table| table.last_key() < key);
+                tables.get(position).filter(|table| table.first_key() <= key).into_iter().collect()
+            };
+            for table in candidates {
+                if let Some(value) = table.get(key).map_err(failed)? {
+                    return Ok(value);
+                }
+            }
+        }
+        return Ok(None);
+    }
+
+    //the whole mutation list is one record of the memtable log, the memtable is flushed once it passes its size
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<(), QueueError> {
+        let mut state = self.state.write().map_err(|_| QueueError::Poisoned)?;
+        let (record, _) = encode_record(&mutations);
+        if let Err(error) = state.log.write_all(&record) {
+            let size = state.log_size;
+            let _ = state.log.set_len(size);
+            return Err(failed(error));
+        }
+        state.log_size += record.len() as u64;
+        state.unsynced += 1;
+        let should_sync = match self.config.fsync_policy() {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EveryN(count) => state.unsynced >= count,
+            FsyncPolicy::Never => false,
+        };
+        if should_sync {
+            //the caller is told the write failed, so a later open must not replay it
+            if let Err(error) = state.log.sync_data() {
+                let size = state.log_size - record.len() as u64;
+                let _ = state.log.set_len(size);
+                state.log_size = size;
+                return Err(failed(error));
+            }
+            state.unsynced = 0;
+        }
+
+        for mutation in mutations {
+            state.memtable.apply(mutation);
+        }
+        //a full memtable stays in place when its flush fails, so the next write flushes it again
+        let should_flush = state.memtable.size() >= self.config.memtable_size();
+        let flushed = if should_flush { self.flush_memtable(&mut state).map_err(failed) } else { Ok(()) };
+        drop(state);
+        let mut maintenance_error = self.maintenance_error.lock().unwrap_or_else(PoisonError::into_inner);
+        if should_flush || maintenance_error.is_some() {
+            *maintenance_error = flushed.and_then(|_| self.compact()).err();
+        }
+        return Ok(());
+    }
+
+    //merges the memtable and every table overlapping the range, newer sources override older ones and tombstones hide keys
+    fn scan(&self, start: &str, end: Option<&str>) -> Result<Vec<(String, String)>, QueueError> {
+        let state = self.state.read().map_err(|_| QueueError::Poisoned)?;
+        if end.is_some_and(|end| end <= start) {
+            return Ok(Vec::new());
+        }
+        let mut merged: BTreeMap<String, Option<String>> = BTreeMap::new();
+        let deeper = state.levels.iter().skip(1).rev().flatten();
+        let level0 = state.levels.iter().take(1).flatten().rev();
+        for table in deeper.chain(level0) {
+            if table.last_key() < start || end.is_some_and(|end| table.first_key() >= end) {
+                continue;
+            }
+            merged.extend(table.range(start, end).map_err(failed)?);
+        }
+        merged.extend(state.memtable.range(start, end));
+        return Ok(merged.into_iter().filter_map(|(key, value)| value.map(|value| (key, value))).collect());
+    }
+}
+
+//replaces the merged tables with the outputs, flushes may have added level 0 tables meanwhile and those stay
+fn install(directory: &Path, state: &mut TreeState, compaction: &Compaction, outputs: Vec<Arc<SsTable>>) -> io::Result<()> {
+    let level = compaction.level;
+    if state.levels.len() <= level + 1 {
+        state.levels.push(Vec::new());
+    }
+    let replaced: HashSet<u64> = compaction.inputs.iter().chain(compaction.overlapping.iter()).map(|table| table.id()).collect();
+    state.levels[level].retain(|table| !replaced.contains(&table.id()));
+    state.levels[level + 1].retain(|table| !replaced.contains(&table.id()));
+    state.levels[level + 1].extend(outputs);
+    state.levels[level + 1].sort_by(|first, second| first.first_key().cmp(second.first_key()));
+    return write_manifest(directory, &state.levels);
+}
+
+fn level_size(tables: &[Arc<SsTable>]) -> u64 {
+    return tables.iter().map(|table| table.size()).sum();
+}
+
+//one "level id" line per live table, in the order of the level
+fn write_manifest(directory: &Path, levels: &[Vec<Arc<SsTable>>]) -> io::Result<()> {
+    let mut manifest = String::new();
+    for (level, tables) in levels.iter().enumerate() {
+        for table in tables {
+            manifest.push_str(&format!("{} {}\n", level, table.id()));
+        }
+    }
+    let temporary = directory.join(TEMPORARY_MANIFEST);
+    let mut file = File::create(&temporary)?;
+    file.write_all(manifest.as_bytes())?;
+    file.sync_all()?;
+    return fs::rename(temporary, directory.join(MANIFEST));
+}
+
+fn read_manifest(directory: &Path) -> io::Result<Vec<(usize, u64)>> {
+    let path = directory.join(MANIFEST);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let mut tables = Vec::new();
+    for line in fs::read_to_string(&path)?.lines() {
+        let parsed = line.split_once(' ').and_then(|(level, id)| Some((level.parse().ok()?, id.parse().ok()?)));
+        match parsed {
+            Some(table) => tables.push(table),
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("corrupted manifest line {}", line))),
+        }
+    }
+    return Ok(tables);
+}
+
+fn failed(error: io::Error) -> QueueError {
+    return QueueError::HandlerFailed(format!("lsm tree failed: {}", error));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tempfile::TempDir;
+
+    use crate::simulation::seeded_random::SeededRandom;
+    use crate::singular_update_queue::key_value_store::KeyValueStoreHandler;
+    use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+    use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+
+    use super::*;
+
+    fn config() -> LsmConfig {
+        return LsmConfig::default()
+            .with_memtable_size(256)
+            .with_block_size(64)
+            .with_level0_tables(3)
+            .with_level_sizes(2048, 4)
+            .with_table_size(512)
+            .with_fsync_policy(FsyncPolicy::Never);
+    }
+
+    fn key(index: u64) -> String {
+        return format!("key{:05}", index);
+    }
+
+    #[test]
+    fn test_read_through_the_memtable_and_the_tables() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        for index in 0..200 {
+            tree.put(key(index), format!("value{}", index)).unwrap();
+        }
+
+        assert_eq!(true, tree.tables_per_level().unwrap().iter().sum::<usize>() > 0);
+        assert_eq!(Ok(Some(String::from("value0"))), tree.get(&key(0)));
+        assert_eq!(Ok(Some(String::from("value199"))), tree.get(&key(199)));
+        assert_eq!(Ok(None), tree.get(&key(200)));
+    }
+
+    #[test]
+    fn test_shadow_older_values_with_newer_writes_and_tombstones() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        tree.put(key(1), String::from("old")).unwrap();
+        tree.put(key(2), String::from("old")).unwrap();
+        tree.flush().unwrap();
+
+        tree.put(key(1), String::from("new")).unwrap();
+        tree.delete(&key(2)).unwrap();
+        assert_eq!(Ok(Some(String::from("new"))), tree.get(&key(1)));
+        assert_eq!(Ok(None), tree.get(&key(2)));
+
+        tree.flush().unwrap();
+        assert_eq!(Ok(Some(String::from("new"))), tree.get(&key(1)));
+        assert_eq!(Ok(None), tree.get(&key(2)));
+    }
+
+    #[test]
+    fn test_scan_merges_every_source() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        for index in 0..100 {
+            tree.put(key(index), String::from("first")).unwrap();
+        }
+        for index in (0..100).step_by(2) {
+            tree.put(key(index), String::from("second")).unwrap();
+        }
+        for index in (0..100).step_by(5) {
+            tree.delete(&key(index)).unwrap();
+        }
+
+        let scanned = tree.scan(&key(10), Some(&key(20))).unwrap();
+        let expected: Vec<(String, String)> = (11..20)
+            .filter(|index| index % 5 != 0)
+            .map(|index| (key(index), String::from(if index % 2 == 0 { "second" } else { "first" })))
+            .collect();
+        assert_eq!(expected, scanned);
+        assert_eq!(80, tree.scan("", None).unwrap().len());
+    }
+
+    #[test]
+    fn test_compact_into_deeper_levels() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        for index in 0..2000 {
+            tree.put(key(index % 700), format!("value{}", index)).unwrap();
+        }
+
+        let tables = tree.tables_per_level().unwrap();
+        assert_eq!(true, tables[0] < 3);
+        assert_eq!(true, tables.len() > 2);
+        assert_eq!(Ok(Some(String::from("value1999"))), tree.get(&key(1999 % 700)));
+        assert_eq!(Ok(Some(String::from("value1400"))), tree.get(&key(0)));
+        assert_eq!(700, tree.scan("", None).unwrap().len());
+
+        let live: usize = tables.iter().sum();
+        let files = fs::read_dir(directory.path())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path().extension().and_then(|extension| extension.to_str()) == Some(TABLE_EXTENSION))
+            .count();
+        assert_eq!(live, files);
+    }
+
+    #[test]
+    fn test_keep_a_durable_write_when_its_flush_fails() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        //the first table can not be renamed into place while a directory holds its name
+        fs::create_dir(directory.path().join("00000000000000000001.sst")).unwrap();
+        let mut written = 0;
+        while tree.maintenance_error().is_none() {
+            assert_eq!(Ok(()), tree.put(key(written), format!("value{}", written)));
+            written += 1;
+        }
+        assert_eq!(vec![0; 0], tree.tables_per_level().unwrap());
+        assert_eq!(Ok(Some(format!("value{}", written - 1))), tree.get(&key(written - 1)));
+
+        //the retry writes a table under the next id
+        tree.put(key(written), format!("value{}", written)).unwrap();
+        assert_eq!(None, tree.maintenance_error());
+        assert_eq!(vec![1], tree.tables_per_level().unwrap());
+        assert_eq!(written as usize + 1, tree.scan("", None).unwrap().len());
+    }
+
+    #[test]
+    fn test_move_the_compaction_pointer_through_a_level() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        for index in 0..1000 {
+            tree.put(key(index), format!("value{}", index)).unwrap();
+        }
+        drop(tree);
+
+        //every deeper level is over its size, so each compaction takes the next table of level 1
+        let tree = LsmTree::open(directory.path(), config().with_level0_tables(100).with_level_sizes(1, 2)).unwrap();
+        let state = tree.state.read().unwrap();
+        let tables = state.levels[1].len();
+        assert_eq!(true, tables > 1);
+        let mut pointers = Vec::new();
+        let picked: Vec<u64> = (0..tables + 1).map(|_| tree.next_compaction(&state, &mut pointers).unwrap().inputs[0].id()).collect();
+        let level1: Vec<u64> = state.levels[1].iter().map(|table| table.id()).collect();
+        assert_eq!(level1, picked[..tables]);
+        assert_eq!(level1[0], picked[tables]);
+    }
+
+    #[test]
+    fn test_recover_the_memtable_and_the_tables_on_open() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        for index in 0..300 {
+            tree.put(key(index), format!("value{}", index)).unwrap();
+        }
+        tree.delete(&key(7)).unwrap();
+        let tables = tree.tables_per_level().unwrap();
+        drop(tree);
+
+        //a table written by a compaction which crashed before the manifest named it
+        SsTable::write(directory.path(), 999, &[(key(1), Some(String::from("orphan")))], 64, 0.01).unwrap();
+
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        assert_eq!(tables, tree.tables_per_level().unwrap());
+        assert_eq!(Ok(Some(String::from("value299"))), tree.get(&key(299)));
+        assert_eq!(Ok(Some(String::from("value1"))), tree.get(&key(1)));
+        assert_eq!(Ok(None), tree.get(&key(7)));
+        assert_eq!(false, directory.path().join("00000000000000000999.sst").exists());
+    }
+
+    #[test]
+    fn test_tell_a_torn_memtable_log_from_a_corrupted_one() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        tree.put(key(1), String::from("value1")).unwrap();
+        tree.put(key(2), String::from("value2")).unwrap();
+        drop(tree);
+        let path = directory.path().join(MEMTABLE_LOG);
+        let bytes = fs::read(&path).unwrap();
+
+        fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        assert_eq!(Ok(Some(String::from("value1"))), tree.get(&key(1)));
+        assert_eq!(Ok(None), tree.get(&key(2)));
+        drop(tree);
+
+        let mut corrupted = bytes.clone();
+        corrupted[10] ^= 0xFF;
+        fs::write(&path, corrupted).unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, LsmTree::open(directory.path(), config()).err().unwrap().kind());
+    }
+
+    #[test]
+    fn test_match_a_map_under_random_writes() {
+        let directory = TempDir::new().unwrap();
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        let mut model: BTreeMap<String, String> = BTreeMap::new();
+        let mut random = SeededRandom::new(11);
+        for index in 0..3000 {
+            let key = key(random.between(0, 400));
+            if random.chance(0.3) {
+                tree.delete(&key).unwrap();
+                model.remove(&key);
+            } else {
+                tree.put(key.clone(), format!("value{}", index)).unwrap();
+                model.insert(key, format!("value{}", index));
+            }
+        }
+
+        let expected: Vec<(String, String)> = model.into_iter().collect();
+        assert_eq!(expected, tree.scan("", None).unwrap());
+        drop(tree);
+        let tree = LsmTree::open(directory.path(), config()).unwrap();
+        assert_eq!(expected, tree.scan("", None).unwrap());
+    }
+
+    #[test]
+    fn test_drive_the_tree_through_the_queue() {
+        let directory = TempDir::new().unwrap();
+        let tree = Arc::new(LsmTree::open(directory.path(), config()).unwrap());
+        let handler = Arc::new(KeyValueStoreHandler::new(tree.clone()).unwrap());
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+        let mut expected = HashMap::new();
+        for index in 0..100 {
+            let command = StorageCommand::Put { key: key(index % 30), value: format!("value{}", index) };
+            assert_eq!(Ok(Status::Ok), singular_update_queue.execute(command).wait());
+            expected.insert(key(index % 30), format!("value{}", index));
+        }
+
+        for (key, value) in expected {
+            let status = singular_update_queue.execute(StorageCommand::Get { key }).wait();
+            assert_eq!(Ok(Status::Value(Some(value))), status);
+        }
+    }
+}
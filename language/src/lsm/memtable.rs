@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+use std::ops::Bound;
+
+use crate::singular_update_queue::key_value_store::Mutation;
+
+//the sorted in-memory buffer of the latest writes, a delete is kept as a tombstone (None) so that it shadows older tables
+#[derive(Debug, Default)]
+pub struct Memtable {
+    entries: BTreeMap<String, Option<String>>,
+    size: usize,
+}
+
+impl Memtable {
+    pub fn new() -> Memtable {
+        return Memtable::default();
+    }
+
+    pub fn apply(&mut self, mutation: Mutation) {
+        let (key, value) = match mutation {
+            Mutation::Put { key, value } => (key, Some(value)),
+            Mutation::Delete { key } => (key, None),
+        };
+        let key_length = key.len();
+        let value_length = value.as_ref().map_or(0, |value| value.len());
+        match self.entries.insert(key, value) {
+            //the key is already accounted for, only the value changes
+            Some(replaced) => self.size = self.size - replaced.map_or(0, |replaced| replaced.len()) + value_length,
+            None => self.size += key_length + value_length,
+        }
+    }
+
+    //None when the memtable knows nothing of the key, Some(None) when it holds a tombstone
+    pub fn get(&self, key: &str) -> Option<Option<String>> {
+        return self.entries.get(key).cloned();
+    }
+
+    pub fn range(&self, start: &str, end: Option<&str>) -> Vec<(String, Option<String>)> {
+        if end.is_some_and(|end| end <= start) {
+            return Vec::new();
+        }
+        let end = end.map_or(Bound::Unbounded, Bound::Excluded);
+        return self.entries
+            .range::<str, _>((Bound::Included(start), end))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, Option<String>> {
+        return &self.entries;
+    }
+
+    //the bytes of the keys and values it holds, the tree flushes the memtable once this passes its budget
+    pub fn size(&self) -> usize {
+        return self.size;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.entries.is_empty();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn put(key: &str, value: &str) -> Mutation {
+        return Mutation::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    #[test]
+    fn test_keep_a_tombstone_for_a_delete() {
+        let mut memtable = Memtable::new();
+        memtable.apply(put("key1", "value1"));
+        memtable.apply(Mutation::Delete { key: String::from("key1") });
+
+        assert_eq!(Some(None), memtable.get("key1"));
+        assert_eq!(None, memtable.get("key2"));
+    }
+
+    #[test]
+    fn test_track_the_size_of_keys_and_values() {
+        let mut memtable = Memtable::new();
+        memtable.apply(put("key1", "value1"));
+        memtable.apply(put("key2", "value2"));
+        memtable.apply(put("key1", "v"));
+        memtable.apply(Mutation::Delete { key: String::from("key2") });
+
+        assert_eq!(4 + 1 + 4, memtable.size());
+    }
+
+    #[test]
+    fn test_range_over_sorted_keys() {
+        let mut memtable = Memtable::new();
+        for key in ["key3", "key1", "key2"] {
+            memtable.apply(put(key, "value"));
+        }
+
+        let keys: Vec<String> = memtable.range("key2", None).into_iter().map(|(key, _)| key).collect();
+        assert_eq!(vec![String::from("key2"), String::from("key3")], keys);
+        assert_eq!(true, memtable.range("key3", Some("key2")).is_empty());
+    }
+}
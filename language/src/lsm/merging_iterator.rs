@@ -0,0 +1,82 @@
+use std::io;
+use std::iter::Peekable;
+
+use crate::lsm::sstable::TableEntry;
+
+pub type TableSource<'a> = Box<dyn Iterator<Item = io::Result<TableEntry>> + 'a>;
+
+//merges sources which are each sorted by key into one sorted stream, holding only the head entry of every source,
+//the sources are ordered newest first so when several hold a key the first of them wins and the others skip it
+pub struct MergingIterator<'a> {
+    sources: Vec<Peekable<TableSource<'a>>>,
+}
+
+impl<'a> MergingIterator<'a> {
+    pub fn new(sources: Vec<TableSource<'a>>) -> MergingIterator<'a> {
+        return MergingIterator { sources: sources.into_iter().map(|source| source.peekable()).collect() };
+    }
+}
+
+impl Iterator for MergingIterator<'_> {
+    type Item = io::Result<TableEntry>;
+
+    fn next(&mut self) -> Option<io::Result<TableEntry>> {
+        let mut smallest: Option<(usize, &str)> = None;
+        for (index, source) in self.sources.iter_mut().enumerate() {
+            match source.peek() {
+                Some(Ok((key, _))) if smallest.is_none_or(|(_, smallest)| key.as_str() < smallest) => smallest = Some((index, key)),
+                Some(Err(_)) => return source.next(),
+                _ => {}
+            }
+        }
+        let key = smallest?.1.to_string();
+        let winner = smallest?.0;
+
+        let mut merged = None;
+        for (index, source) in self.sources.iter_mut().enumerate() {
+            if source.peek().is_some_and(|head| head.as_ref().is_ok_and(|(head, _)| *head == key)) {
+                let entry = source.next();
+                if index == winner {
+                    merged = entry;
+                }
+            }
+        }
+        return merged;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(entries: &[(&str, Option<&str>)]) -> TableSource<'static> {
+        let entries: Vec<io::Result<TableEntry>> = entries
+            .iter()
+            .map(|(key, value)| Ok((key.to_string(), value.map(|value| value.to_string()))))
+            .collect();
+        return Box::new(entries.into_iter());
+    }
+
+    #[test]
+    fn test_merge_sorted_sources_with_the_newest_winning() {
+        let newest = source(&[("key2", None), ("key4", Some("new"))]);
+        let oldest = source(&[("key1", Some("old")), ("key2", Some("old")), ("key4", Some("old")), ("key5", Some("old"))]);
+
+        let merged: Vec<TableEntry> = MergingIterator::new(vec![newest, oldest]).collect::<io::Result<_>>().unwrap();
+        let expected = vec![
+            (String::from("key1"), Some(String::from("old"))),
+            (String::from("key2"), None),
+            (String::from("key4"), Some(String::from("new"))),
+            (String::from("key5"), Some(String::from("old"))),
+        ];
+        assert_eq!(expected, merged);
+    }
+
+    #[test]
+    fn test_propagate_an_error_of_a_source() {
+        let failing: TableSource<'static> = Box::new(vec![Err(io::Error::other("unreadable block"))].into_iter());
+        let merged: io::Result<Vec<TableEntry>> = MergingIterator::new(vec![source(&[("key1", None)]), failing]).collect();
+
+        assert_eq!(true, merged.is_err());
+    }
+}
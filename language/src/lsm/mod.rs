@@ -0,0 +1,6 @@
+pub mod bloom_filter;
+pub mod memtable;
+pub mod sstable;
+pub mod merging_iterator;
+pub mod lsm_config;
+pub mod lsm_tree;
@@ -0,0 +1,374 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::lsm::bloom_filter::BloomFilter;
+use crate::singular_update_queue::write_ahead_log::{decode_string, decode_u32, encode_string, read_u32, read_u64};
+
+pub const TABLE_EXTENSION: &str = "sst";
+const TEMPORARY_EXTENSION: &str = "tmp";
+const FOOTER_SIZE: usize = 32;
+const MAGIC: u32 = 0x5353_5442;
+const VALUE_TAG: u8 = 1;
+const TOMBSTONE_TAG: u8 = 2;
+
+//an entry of a table, a None value is a tombstone which shadows the key in older tables
+pub type TableEntry = (String, Option<String>);
+
+//where a data block sits in the file, the last key of every block is kept in memory to find the one block a key can be in
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct BlockHandle {
+    last_key: String,
+    offset: u64,
+    length: u32,
+    crc: u32,
+}
+
+//an immutable sorted string table, only the block index and the bloom filter are held in memory
+pub struct SsTable {
+    id: u64,
+    path: PathBuf,
+    size: u64,
+    first_key: String,
+    blocks: Vec<BlockHandle>,
+    bloom_filter: BloomFilter,
+    file: Mutex<File>,
+}
+
+//the entries of a table in key order, tombstones included
+pub struct TableIterator<'a> {
+    table: &'a SsTable,
+    block: usize,
+    entries: std::vec::IntoIter<TableEntry>,
+}
+
+//file layout: data blocks | index | bloom filter | footer,
+//a block holds (tag, key, value)* in key order, the index holds the first key and a handle per block,
+//the footer holds the offset and length of the index and the filter, a crc over both and a magic number
+impl SsTable {
+    //writes to a temporary file and renames it, so a crash never leaves a partial table behind
+    pub fn write(directory: &Path, id: u64, entries: &[TableEntry], block_size: usize, false_positive_rate: f64) -> io::Result<SsTable> {
+        assert!(!entries.is_empty(), "a table needs at least one entry");
+        let mut bloom_filter = BloomFilter::new(entries.len(), false_positive_rate);
+        let mut blocks = Vec::new();
+        let mut data = Vec::new();
+        let mut block = Vec::new();
+        for (position, (key, value)) in entries.iter().enumerate() {
+            bloom_filter.insert(key);
+            match value {
+                Some(value) => {
+                    block.push(VALUE_TAG);
+                    encode_string(&mut block, key);
+                    encode_string(&mut block, value);
+                }
+                None => {
+                    block.push(TOMBSTONE_TAG);
+                    encode_string(&mut block, key);
+                }
+            }
+            if block.len() >= block_size || position == entries.len() - 1 {
+                blocks.push(BlockHandle {
+                    last_key: key.clone(),
+                    offset: data.len() as u64,
+                    length: block.len() as u32,
+                    crc: crc32fast::hash(&block),
+                });
+                data.append(&mut block);
+            }
+        }
+
+        let first_key = entries[0].0.clone();
+        let index = encode_index(&first_key, &blocks);
+        let filter = bloom_filter.encode();
+        let mut footer = Vec::with_capacity(FOOTER_SIZE);
+        footer.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        footer.extend_from_slice(&(index.len() as u32).to_le_bytes());
+        footer.extend_from_slice(&((data.len() + index.len()) as u64).to_le_bytes());
+        footer.extend_from_slice(&(filter.len() as u32).to_le_bytes());
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&index);
+        hasher.update(&filter);
+        footer.extend_from_slice(&hasher.finalize().to_le_bytes());
+        footer.extend_from_slice(&MAGIC.to_le_bytes());
+
+        let path = table_path(directory, id);
+        let temporary = directory.join(format!("{:020}.{}", id, TEMPORARY_EXTENSION));
+        let mut file = File::create(&temporary)?;
+        file.write_all(&data)?;
+        file.write_all(&index)?;
+        file.write_all(&filter)?;
+        file.write_all(&footer)?;
+        file.sync_all()?;
+        fs::rename(&temporary, &path)?;
+
+        let size = (data.len() + index.len() + filter.len() + footer.len()) as u64;
+        return Ok(SsTable { id, path: path.clone(), size, first_key, blocks, bloom_filter, file: Mutex::new(File::open(&path)?) });
+    }
+
+    pub fn open(directory: &Path, id: u64) -> io::Result<SsTable> {
+        let path = table_path(directory, id);
+        let mut file = File::open(&path)?;
+        let size = file.metadata()?.len();
+        if size < FOOTER_SIZE as u64 {
+            return Err(corrupted(&path));
+        }
+        let mut footer = [0; FOOTER_SIZE];
+        file.seek(SeekFrom::Start(size - FOOTER_SIZE as u64))?;
+        file.read_exact(&mut footer)?;
+        if read_u32(&footer[28..]) != MAGIC {
+            return Err(corrupted(&path));
+        }
+        let index_offset = read_u64(&footer);
+        let index_length = read_u32(&footer[8..]) as usize;
+        let filter_offset = read_u64(&footer[12..]);
+        let filter_length = read_u32(&footer[20..]) as usize;
+        if index_offset + index_length as u64 != filter_offset || filter_offset + (filter_length + FOOTER_SIZE) as u64 != size {
+            return Err(corrupted(&path));
+        }
+
+        let mut metadata = vec![0; index_length + filter_length];
+        file.seek(SeekFrom::Start(index_offset))?;
+        file.read_exact(&mut metadata)?;
+        if crc32fast::hash(&metadata) != read_u32(&footer[24..]) {
+            return Err(corrupted(&path));
+        }
+        let (first_key, blocks) = decode_index(&metadata[..index_length]).ok_or_else(|| corrupted(&path))?;
+        let bloom_filter = BloomFilter::decode(&metadata[index_length..]).ok_or_else(|| corrupted(&path))?;
+        return Ok(SsTable { id, path, size, first_key, blocks, bloom_filter, file: Mutex::new(file) });
+    }
+
+    pub fn id(&self) -> u64 {
+        return self.id;
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+
+    pub fn size(&self) -> u64 {
+        return self.size;
+    }
+
+    pub fn first_key(&self) -> &str {
+        return &self.first_key;
+    }
+
+    pub fn last_key(&self) -> &str {
+        return &self.blocks[self.blocks.len() - 1].last_key;
+    }
+
+    //whether any key of the table falls in [start, end]
+    pub fn overlaps(&self, start: &str, end: &str) -> bool {
+        return self.first_key() <= end && self.last_key() >= start;
+    }
+
+    //None when the table knows nothing of the key, Some(None) when it holds a tombstone
+    pub fn get(&self, key: &str) -> io::Result<Option<Option<String>>> {
+        if key < self.first_key() || key > self.last_key() || !self.bloom_filter.might_contain(key) {
+            return Ok(None);
+        }
+        let block = self.blocks.partition_point(|block| block.last_key.as_str() < key);
+        let entries = self.read_block(block)?;
+        return Ok(entries.into_iter().find(|(entry_key, _)| entry_key == key).map(|(_, value)| value));
+    }
+
+    //the entries with start <= key < end in key order, tombstones included, no end reads to the last key
+    pub fn range(&self, start: &str, end: Option<&str>) -> io::Result<Vec<TableEntry>> {
+        let mut entries = Vec::new();
+        for block in self.blocks.partition_point(|block| block.last_key.as_str() < start)..self.blocks.len() {
+            for (key, value) in self.read_block(block)? {
+                if end.is_some_and(|end| key.as_str() >= end) {
+                    return Ok(entries);
+                }
+                if key.as_str() >= start {
+                    entries.push((key, value));
+                }
+            }
+        }
+        return Ok(entries);
+    }
+
+    pub fn entries(&self) -> io::Result<Vec<TableEntry>> {
+        return self.range("", None);
+    }
+
+    //reads one block at a time, so a compaction holds a block per table instead of whole tables
+    pub fn iter(&self) -> TableIterator<'_> {
+        return TableIterator { table: self, block: 0, entries: Vec::new().into_iter() };
+    }
+
+    fn read_block(&self, block: usize) -> io::Result<Vec<TableEntry>> {
+        let handle = &self.blocks[block];
+        let mut bytes = vec![0; handle.length as usize];
+        {
+            let mut file = self.file.lock().map_err(|_| io::Error::other("table file lock is poisoned"))?;
+            file.seek(SeekFrom::Start(handle.offset))?;
+            file.read_exact(&mut bytes)?;
+        }
+        if crc32fast::hash(&bytes) != handle.crc {
+            return Err(corrupted(&self.path));
+        }
+        return decode_block(&bytes).ok_or_else(|| corrupted(&self.path));
+    }
+}
+
+impl Iterator for TableIterator<'_> {
+    type Item = io::Result<TableEntry>;
+
+    fn next(&mut self) -> Option<io::Result<TableEntry>> {
+        loop {
+            if let Some(entry) = self.entries.next() {
+                return Some(Ok(entry));
+            }
+            if self.block == self.table.blocks.len() {
+                return None;
+            }
+            //a block which can not be read ends the iteration after its error
+            let entries = self.table.read_block(self.block);
+            self.block += 1;
+            match entries {
+                Ok(entries) => self.entries = entries.into_iter(),
+                Err(error) => {
+                    self.block = self.table.blocks.len();
+                    return Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+pub fn table_path(directory: &Path, id: u64) -> PathBuf {
+    return directory.join(format!("{:020}.{}", id, TABLE_EXTENSION));
+}
+
+fn encode_index(first_key: &str, blocks: &[BlockHandle]) -> Vec<u8> {
+    let mut index = Vec::new();
+    encode_string(&mut index, first_key);
+    index.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+    for block in blocks {
+        encode_string(&mut index, &block.last_key);
+        index.extend_from_slice(&block.offset.to_le_bytes());
+        index.extend_from_slice(&block.length.to_le_bytes());
+        index.extend_from_slice(&block.crc.to_le_bytes());
+    }
+    return index;
+}
+
+fn decode_index(bytes: &[u8]) -> Option<(String, Vec<BlockHandle>)> {
+    let (first_key, rest) = decode_string(bytes)?;
+    let (count, mut rest) = decode_u32(rest)?;
+    let mut blocks = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (last_key, remaining) = decode_string(rest)?;
+        if remaining.len() < 16 {
+            return None;
+        }
+        blocks.push(BlockHandle {
+            last_key,
+            offset: read_u64(remaining),
+            length: read_u32(&remaining[8..]),
+            crc: read_u32(&remaining[12..]),
+        });
+        rest = &remaining[16..];
+    }
+    if blocks.is_empty() {
+        return None;
+    }
+    return Some((first_key, blocks));
+}
+
+fn decode_block(mut bytes: &[u8]) -> Option<Vec<TableEntry>> {
+    let mut entries = Vec::new();
+    while let Some((tag, rest)) = bytes.split_first() {
+        let (key, rest) = decode_string(rest)?;
+        match *tag {
+            VALUE_TAG => {
+                let (value, rest) = decode_string(rest)?;
+                entries.push((key, Some(value)));
+                bytes = rest;
+            }
+            TOMBSTONE_TAG => {
+                entries.push((key, None));
+                bytes = rest;
+            }
+            _ => return None,
+        }
+    }
+    return Some(entries);
+}
+
+fn corrupted(table: &Path) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, format!("corrupted table {}", table.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::OpenOptions;
+    use std::os::unix::fs::FileExt;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn entries(count: usize) -> Vec<TableEntry> {
+        return (0..count)
+            .map(|key| {
+                let value = if key % 10 == 9 { None } else { Some(format!("value{}", key)) };
+                (format!("key{:04}", key), value)
+            })
+            .collect();
+    }
+
+    #[test]
+    fn test_get_keys_across_blocks() {
+        let directory = TempDir::new().unwrap();
+        let table = SsTable::write(directory.path(), 1, &entries(500), 256, 0.01).unwrap();
+
+        assert_eq!(true, table.blocks.len() > 1);
+        assert_eq!(Some(Some(String::from("value0"))), table.get("key0000").unwrap());
+        assert_eq!(Some(Some(String::from("value321"))), table.get("key0321").unwrap());
+        assert_eq!(Some(None), table.get("key0019").unwrap());
+        assert_eq!(None, table.get("key0321a").unwrap());
+        assert_eq!(None, table.get("key9999").unwrap());
+    }
+
+    #[test]
+    fn test_read_a_range_with_tombstones() {
+        let directory = TempDir::new().unwrap();
+        let table = SsTable::write(directory.path(), 1, &entries(500), 256, 0.01).unwrap();
+
+        let range = table.range("key0095", Some("key0101")).unwrap();
+        let keys: Vec<&str> = range.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(vec!["key0095", "key0096", "key0097", "key0098", "key0099", "key0100"], keys);
+        assert_eq!(None, range[4].1);
+        assert_eq!(500, table.entries().unwrap().len());
+        assert_eq!(table.entries().unwrap(), table.iter().collect::<io::Result<Vec<TableEntry>>>().unwrap());
+    }
+
+    #[test]
+    fn test_reopen_a_table() {
+        let directory = TempDir::new().unwrap();
+        let written = SsTable::write(directory.path(), 7, &entries(100), 128, 0.01).unwrap();
+        let table = SsTable::open(directory.path(), 7).unwrap();
+
+        assert_eq!(written.blocks, table.blocks);
+        assert_eq!(written.size(), table.size());
+        assert_eq!("key0000", table.first_key());
+        assert_eq!("key0099", table.last_key());
+        assert_eq!(Some(Some(String::from("value42"))), table.get("key0042").unwrap());
+        assert_eq!(true, table.overlaps("key0050", "key0200"));
+        assert_eq!(false, table.overlaps("key0100", "key0200"));
+    }
+
+    #[test]
+    fn test_detect_a_corrupted_block() {
+        let directory = TempDir::new().unwrap();
+        SsTable::write(directory.path(), 1, &entries(100), 128, 0.01).unwrap();
+        let file = OpenOptions::new().write(true).open(table_path(directory.path(), 1)).unwrap();
+        file.write_at(b"x", 10).unwrap();
+
+        let table = SsTable::open(directory.path(), 1).unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, table.get("key0000").unwrap_err().kind());
+    }
+}
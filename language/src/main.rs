@@ -4,7 +4,6 @@ mod basic_error_handling;
 mod closure;
 mod linked_list;
 mod concurrency;
-mod singular_update_queue;
 mod cache;
 
 fn main() {
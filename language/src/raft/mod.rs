@@ -0,0 +1,9 @@
+pub mod raft_message;
+pub mod raft_log;
+pub mod raft_config;
+pub mod state_machine;
+pub mod raft_node;
+pub mod raft_transport;
+pub mod raft_server;
+pub mod raft_handler;
+pub mod raft_cluster;
\ No newline at end of file
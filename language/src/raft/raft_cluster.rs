@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::raft::raft_config::RaftConfig;
+use crate::raft::raft_handler::RaftHandler;
+use crate::raft::raft_message::NodeId;
+use crate::raft::raft_node::{RaftNode, RaftStatus, Role};
+use crate::raft::raft_server::RaftServer;
+use crate::raft::raft_transport::{InMemoryNetwork, RaftTransport};
+use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Storage};
+
+//raft nodes wired together with an in-memory network inside one process, node ids start at one
+pub struct RaftCluster {
+    network: Arc<InMemoryNetwork>,
+    servers: Vec<RaftServer>,
+    storages: Vec<Storage>,
+}
+
+impl RaftCluster {
+    pub fn start(nodes: usize, config: RaftConfig, tick_interval: Duration) -> RaftCluster {
+        let network = Arc::new(InMemoryNetwork::new());
+        let ids: Vec<NodeId> = (1..=nodes as NodeId).collect();
+        let mut servers = Vec::with_capacity(nodes);
+        let mut storages = Vec::with_capacity(nodes);
+        for id in &ids {
+            let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+            let state_machine = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+            let node = RaftNode::new(*id, ids.clone(), config, state_machine);
+            let server = RaftServer::start(node, network.clone() as Arc<dyn RaftTransport>, tick_interval);
+            network.register(*id, server.inbox());
+            servers.push(server);
+            storages.push(storage);
+        }
+        return RaftCluster { network, servers, storages };
+    }
+
+    pub fn status(&self, id: NodeId) -> RaftStatus {
+        return self.server(id).status().unwrap();
+    }
+
+    pub fn storage(&self, id: NodeId) -> &Storage {
+        return &self.storages[(id - 1) as usize];
+    }
+
+    pub fn handler(&self, id: NodeId, timeout: Duration) -> RaftHandler {
+        return RaftHandler::new(self.server(id).inbox(), timeout);
+    }
+
+    pub fn isolate(&self, id: NodeId) {
+        self.network.isolate(id);
+    }
+
+    pub fn heal(&self, id: NodeId) {
+        self.network.heal(id);
+    }
+
+    //the leader of the highest term among the given nodes, a deposed leader may still believe it leads an older term
+    pub fn await_leader(&self, among: &[NodeId], timeout: Duration) -> Option<NodeId> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            let leader = among
+                .iter()
+                .map(|id| self.status(*id))
+                .filter(|status| status.role == Role::Leader)
+                .max_by_key(|status| status.term);
+            if let Some(leader) = leader {
+                return Some(leader.id);
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        return None;
+    }
+
+    pub fn await_applied(&self, id: NodeId, index: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.status(id).last_applied < index {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        return true;
+    }
+
+    pub fn ids(&self) -> Vec<NodeId> {
+        return self.servers.iter().map(|server| server.id()).collect();
+    }
+
+    fn server(&self, id: NodeId) -> &RaftServer {
+        return &self.servers[(id - 1) as usize];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::singular_update_queue::command::CommandHandler;
+    use crate::singular_update_queue::queue_error::QueueError;
+    use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+    use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+
+    use super::*;
+
+    const TICK: Duration = Duration::from_millis(5);
+    const WAIT: Duration = Duration::from_secs(10);
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    #[test]
+    fn test_elect_a_single_leader() {
+        let cluster = RaftCluster::start(3, RaftConfig::default(), TICK);
+        let leader = cluster.await_leader(&cluster.ids(), WAIT).unwrap();
+        let term = cluster.status(leader).term;
+
+        for id in cluster.ids() {
+            let status = cluster.status(id);
+            if status.term == term && id != leader {
+                assert_eq!(Role::Follower, status.role);
+            }
+        }
+    }
+
+    #[test]
+    fn test_replicate_commands_of_a_singular_update_queue() {
+        let cluster = RaftCluster::start(3, RaftConfig::default(), TICK);
+        let leader = cluster.await_leader(&cluster.ids(), WAIT).unwrap();
+        let queue = SingularUpdateQueue::init(Arc::new(cluster.handler(leader, WAIT)), Executor::Thread);
+
+        assert_eq!(Ok(Status::Ok), queue.execute(put("key1", "value1")).wait());
+        assert_eq!(Ok(Status::Ok), queue.execute(StorageCommand::Delete { key: String::from("key1") }).wait());
+        assert_eq!(Ok(Status::Ok), queue.execute(put("key2", "value2")).wait());
+        assert_eq!(Ok(Status::Value(Some(String::from("value2")))), queue.execute(StorageCommand::Get { key: String::from("key2") }).wait());
+
+        let commit_index = cluster.status(leader).commit_index;
+        for id in cluster.ids() {
+            assert_eq!(true, cluster.await_applied(id, commit_index, WAIT));
+            let storage = cluster.storage(id).read().unwrap();
+            assert_eq!(None, storage.get("key1"));
+            assert_eq!("value2", storage.get("key2").unwrap());
+        }
+    }
+
+    #[test]
+    fn test_reject_a_command_on_a_follower() {
+        let cluster = RaftCluster::start(3, RaftConfig::default(), TICK);
+        let leader = cluster.await_leader(&cluster.ids(), WAIT).unwrap();
+        let follower = cluster.ids().into_iter().find(|id| *id != leader).unwrap();
+
+        let status = cluster.handler(follower, WAIT).handle(put("key1", "value1"));
+        assert_eq!(Status::Failed(QueueError::NotLeader), status);
+    }
+
+    #[test]
+    fn test_elect_a_new_leader_after_a_partition() {
+        let cluster = RaftCluster::start(3, RaftConfig::default(), TICK);
+        let old_leader = cluster.await_leader(&cluster.ids(), WAIT).unwrap();
+        let rest: Vec<NodeId> = cluster.ids().into_iter().filter(|id| *id != old_leader).collect();
+
+        cluster.isolate(old_leader);
+        let new_leader = cluster.await_leader(&rest, WAIT).unwrap();
+        assert_eq!(true, cluster.status(new_leader).term > cluster.status(old_leader).term);
+
+        let queue = SingularUpdateQueue::init(Arc::new(cluster.handler(new_leader, WAIT)), Executor::Thread);
+        assert_eq!(Ok(Status::Ok), queue.execute(put("key1", "value1")).wait());
+
+        cluster.heal(old_leader);
+        let commit_index = cluster.status(new_leader).commit_index;
+        assert_eq!(true, cluster.await_applied(old_leader, commit_index, WAIT));
+        assert_eq!(Role::Follower, cluster.status(old_leader).role);
+        assert_eq!("value1", cluster.storage(old_leader).read().unwrap().get("key1").unwrap());
+    }
+
+    #[test]
+    fn test_install_a_snapshot_on_a_lagging_follower() {
+        let cluster = RaftCluster::start(3, RaftConfig::default().with_snapshot_threshold(8), TICK);
+        let leader = cluster.await_leader(&cluster.ids(), WAIT).unwrap();
+        let lagging = cluster.ids().into_iter().find(|id| *id != leader).unwrap();
+        let queue = SingularUpdateQueue::init(Arc::new(cluster.handler(leader, WAIT)), Executor::Thread);
+
+        cluster.isolate(lagging);
+        for index in 0..20 {
+            assert_eq!(Ok(Status::Ok), queue.execute(put(&format!("key{}", index), "value")).wait());
+        }
+        assert_eq!(true, cluster.status(leader).snapshot_index > cluster.status(lagging).last_index);
+
+        cluster.heal(lagging);
+        let commit_index = cluster.status(leader).commit_index;
+        assert_eq!(true, cluster.await_applied(lagging, commit_index, WAIT));
+        assert_eq!(true, cluster.status(lagging).snapshot_index > 0);
+        assert_eq!(20, cluster.storage(lagging).read().unwrap().len());
+    }
+}
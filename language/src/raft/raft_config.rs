@@ -0,0 +1,80 @@
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RaftConfig {
+    min_election_ticks: u64,
+    max_election_ticks: u64,
+    heartbeat_ticks: u64,
+    snapshot_threshold: u64,
+    max_entries_per_message: usize,
+    seed: u64,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        return RaftConfig {
+            min_election_ticks: 10,
+            max_election_ticks: 20,
+            heartbeat_ticks: 3,
+            snapshot_threshold: 1024,
+            max_entries_per_message: 64,
+            seed: 42,
+        };
+    }
+}
+
+impl RaftConfig {
+    //every node picks its election timeout between the bounds, a heartbeat has to be well below the lower bound
+    pub fn with_election_ticks(mut self, min_election_ticks: u64, max_election_ticks: u64) -> RaftConfig {
+        assert!(min_election_ticks > 0 && min_election_ticks <= max_election_ticks, "invalid election ticks");
+        self.min_election_ticks = min_election_ticks;
+        self.max_election_ticks = max_election_ticks;
+        return self;
+    }
+
+    pub fn with_heartbeat_ticks(mut self, heartbeat_ticks: u64) -> RaftConfig {
+        assert!(heartbeat_ticks > 0, "heartbeat ticks must be greater than zero");
+        self.heartbeat_ticks = heartbeat_ticks;
+        return self;
+    }
+
+    //number of applied entries after which the state machine is snapshotted and the log compacted
+    pub fn with_snapshot_threshold(mut self, snapshot_threshold: u64) -> RaftConfig {
+        assert!(snapshot_threshold > 0, "snapshot threshold must be greater than zero");
+        self.snapshot_threshold = snapshot_threshold;
+        return self;
+    }
+
+    pub fn with_max_entries_per_message(mut self, max_entries_per_message: usize) -> RaftConfig {
+        assert!(max_entries_per_message > 0, "max entries per message must be greater than zero");
+        self.max_entries_per_message = max_entries_per_message;
+        return self;
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> RaftConfig {
+        self.seed = seed;
+        return self;
+    }
+
+    pub fn min_election_ticks(&self) -> u64 {
+        return self.min_election_ticks;
+    }
+
+    pub fn max_election_ticks(&self) -> u64 {
+        return self.max_election_ticks;
+    }
+
+    pub fn heartbeat_ticks(&self) -> u64 {
+        return self.heartbeat_ticks;
+    }
+
+    pub fn snapshot_threshold(&self) -> u64 {
+        return self.snapshot_threshold;
+    }
+
+    pub fn max_entries_per_message(&self) -> usize {
+        return self.max_entries_per_message;
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+}
@@ -0,0 +1,57 @@
+use std::sync::mpsc::{self, Sender};
+use std::time::{Duration, Instant};
+
+use crate::raft::raft_server::RaftInput;
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+
+//lets a singular update queue drive commands through consensus, a command completes once it is committed and applied,
+//a get is appended to the log as well which makes the read linearizable even across a change of leader
+pub struct RaftHandler {
+    inbox: Sender<RaftInput>,
+    timeout: Duration,
+}
+
+impl RaftHandler {
+    pub fn new(inbox: Sender<RaftInput>, timeout: Duration) -> RaftHandler {
+        return RaftHandler { inbox, timeout };
+    }
+}
+
+impl CommandHandler<StorageCommand, Status> for RaftHandler {
+    fn handle(&self, command: StorageCommand) -> Status {
+        let (respond_back, receiver) = mpsc::channel();
+        if self.inbox.send(RaftInput::Propose { command, respond_back }).is_err() {
+            return Status::Failed(QueueError::Closed);
+        }
+        return receiver.recv_timeout(self.timeout).unwrap_or(Status::Failed(QueueError::Timeout));
+    }
+
+    //proposals are sent together so they share append entries messages and commit together
+    fn handle_batch(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+        let deadline = Instant::now() + self.timeout;
+        let receivers: Vec<_> = commands
+            .into_iter()
+            .map(|command| {
+                let (respond_back, receiver) = mpsc::channel();
+                let sent = self.inbox.send(RaftInput::Propose { command, respond_back }).is_ok();
+                return (sent, receiver);
+            })
+            .collect();
+        return receivers
+            .into_iter()
+            .map(|(sent, receiver)| {
+                if !sent {
+                    return Status::Failed(QueueError::Closed);
+                }
+                let timeout = deadline.saturating_duration_since(Instant::now());
+                return receiver.recv_timeout(timeout).unwrap_or(Status::Failed(QueueError::Timeout));
+            })
+            .collect();
+    }
+
+    fn command_type(&self, command: &StorageCommand) -> &'static str {
+        return command.command_type();
+    }
+}
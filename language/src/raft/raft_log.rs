@@ -0,0 +1,183 @@
+use crate::singular_update_queue::storage_command::StorageCommand;
+
+//a leader appends an entry without a command when it gets elected, committing it commits the entries of earlier terms
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct RaftEntry {
+    pub index: u64,
+    pub term: u64,
+    pub command: Option<StorageCommand>,
+}
+
+//entries up to snapshot_index are compacted into the state machine snapshot
+#[derive(Debug, Default)]
+pub struct RaftLog {
+    snapshot_index: u64,
+    snapshot_term: u64,
+    entries: Vec<RaftEntry>,
+}
+
+impl RaftLog {
+    pub fn new() -> RaftLog {
+        return RaftLog::default();
+    }
+
+    pub fn last_index(&self) -> u64 {
+        return self.snapshot_index + self.entries.len() as u64;
+    }
+
+    pub fn last_term(&self) -> u64 {
+        return self.entries.last().map(|entry| entry.term).unwrap_or(self.snapshot_term);
+    }
+
+    pub fn snapshot_index(&self) -> u64 {
+        return self.snapshot_index;
+    }
+
+    pub fn snapshot_term(&self) -> u64 {
+        return self.snapshot_term;
+    }
+
+    pub fn term_at(&self, index: u64) -> Option<u64> {
+        if index == self.snapshot_index {
+            return Some(self.snapshot_term);
+        }
+        return self.entry(index).map(|entry| entry.term);
+    }
+
+    pub fn entry(&self, index: u64) -> Option<&RaftEntry> {
+        if index <= self.snapshot_index {
+            return None;
+        }
+        return self.entries.get((index - self.snapshot_index - 1) as usize);
+    }
+
+    pub fn entries_from(&self, index: u64, max_entries: usize) -> Vec<RaftEntry> {
+        if index <= self.snapshot_index {
+            return Vec::new();
+        }
+        let from = ((index - self.snapshot_index - 1) as usize).min(self.entries.len());
+        let until = self.entries.len().min(from + max_entries);
+        return self.entries[from..until].to_vec();
+    }
+
+    pub fn append(&mut self, term: u64, command: Option<StorageCommand>) -> u64 {
+        let index = self.last_index() + 1;
+        self.entries.push(RaftEntry { index, term, command });
+        return index;
+    }
+
+    //entries from a leader replace conflicting entries, an entry which is already present is kept
+    pub fn merge(&mut self, entry: RaftEntry) {
+        if entry.index <= self.snapshot_index {
+            return;
+        }
+        match self.term_at(entry.index) {
+            Some(term) if term == entry.term => {}
+            Some(_) => {
+                self.entries.truncate((entry.index - self.snapshot_index - 1) as usize);
+                self.entries.push(entry);
+            }
+            None => {
+                if entry.index == self.last_index() + 1 {
+                    self.entries.push(entry);
+                }
+            }
+        }
+    }
+
+    pub fn compact(&mut self, index: u64) {
+        if index <= self.snapshot_index || index > self.last_index() {
+            return;
+        }
+        let term = self.term_at(index).unwrap();
+        self.entries.drain(..(index - self.snapshot_index) as usize);
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+    }
+
+    //an installed snapshot keeps the entries after it only when the log agrees with it at the snapshot index
+    pub fn restore(&mut self, snapshot_index: u64, snapshot_term: u64) {
+        if self.term_at(snapshot_index) == Some(snapshot_term) {
+            self.compact(snapshot_index);
+            return;
+        }
+        self.entries.clear();
+        self.snapshot_index = snapshot_index;
+        self.snapshot_term = snapshot_term;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(index: u64, term: u64) -> RaftEntry {
+        return RaftEntry { index, term, command: None };
+    }
+
+    #[test]
+    fn test_append_entries() {
+        let mut log = RaftLog::new();
+        assert_eq!(1, log.append(1, None));
+        assert_eq!(2, log.append(2, None));
+
+        assert_eq!(2, log.last_index());
+        assert_eq!(2, log.last_term());
+        assert_eq!(Some(1), log.term_at(1));
+        assert_eq!(Some(0), log.term_at(0));
+        assert_eq!(None, log.term_at(3));
+    }
+
+    #[test]
+    fn test_merge_replaces_a_conflicting_suffix() {
+        let mut log = RaftLog::new();
+        log.append(1, None);
+        log.append(1, None);
+        log.append(1, None);
+
+        log.merge(entry(2, 2));
+
+        assert_eq!(2, log.last_index());
+        assert_eq!(Some(2), log.term_at(2));
+    }
+
+    #[test]
+    fn test_merge_keeps_a_matching_entry() {
+        let mut log = RaftLog::new();
+        log.append(1, None);
+        log.append(1, None);
+
+        log.merge(entry(1, 1));
+
+        assert_eq!(2, log.last_index());
+    }
+
+    #[test]
+    fn test_compact_the_log() {
+        let mut log = RaftLog::new();
+        for term in [1, 1, 2, 2] {
+            log.append(term, None);
+        }
+
+        log.compact(3);
+
+        assert_eq!(3, log.snapshot_index());
+        assert_eq!(2, log.snapshot_term());
+        assert_eq!(4, log.last_index());
+        assert_eq!(None, log.entry(3));
+        assert_eq!(Some(2), log.term_at(3));
+        assert_eq!(vec![entry(4, 2)], log.entries_from(4, 10));
+    }
+
+    #[test]
+    fn test_restore_a_snapshot_beyond_the_log() {
+        let mut log = RaftLog::new();
+        log.append(1, None);
+
+        log.restore(10, 3);
+
+        assert_eq!(10, log.last_index());
+        assert_eq!(3, log.last_term());
+        assert_eq!(Vec::<RaftEntry>::new(), log.entries_from(11, 10));
+    }
+}
@@ -0,0 +1,53 @@
+use crate::raft::raft_log::RaftEntry;
+use crate::raft::state_machine::Snapshot;
+
+pub type NodeId = u64;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RaftMessage {
+    RequestVote {
+        term: u64,
+        last_log_index: u64,
+        last_log_term: u64,
+    },
+    RequestVoteResponse {
+        term: u64,
+        vote_granted: bool,
+    },
+    AppendEntries {
+        term: u64,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<RaftEntry>,
+        leader_commit: u64,
+    },
+    //match_index is the last index known to match the leader, on a rejection it hints where the leader should resend from
+    AppendEntriesResponse {
+        term: u64,
+        success: bool,
+        match_index: u64,
+    },
+    InstallSnapshot {
+        term: u64,
+        last_included_index: u64,
+        last_included_term: u64,
+        data: Snapshot,
+    },
+    InstallSnapshotResponse {
+        term: u64,
+        match_index: u64,
+    },
+}
+
+impl RaftMessage {
+    pub fn term(&self) -> u64 {
+        return match self {
+            RaftMessage::RequestVote { term, .. } => *term,
+            RaftMessage::RequestVoteResponse { term, .. } => *term,
+            RaftMessage::AppendEntries { term, .. } => *term,
+            RaftMessage::AppendEntriesResponse { term, .. } => *term,
+            RaftMessage::InstallSnapshot { term, .. } => *term,
+            RaftMessage::InstallSnapshotResponse { term, .. } => *term,
+        };
+    }
+}
@@ -0,0 +1,670 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::raft::raft_config::RaftConfig;
+use crate::raft::raft_log::RaftLog;
+use crate::raft::raft_message::{NodeId, RaftMessage};
+use crate::raft::state_machine::{Snapshot, StateMachine};
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Role {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Applied {
+    pub index: u64,
+    pub term: u64,
+    pub status: Status,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RaftStatus {
+    pub id: NodeId,
+    pub role: Role,
+    pub term: u64,
+    pub leader: Option<NodeId>,
+    pub last_index: u64,
+    pub commit_index: u64,
+    pub last_applied: u64,
+    pub snapshot_index: u64,
+}
+
+//what raft keeps on stable storage, a restarted node keeps exactly this
+struct PersistentState {
+    term: u64,
+    voted_for: Option<NodeId>,
+    log: RaftLog,
+    snapshot: Snapshot,
+}
+
+//a deterministic raft node, time only moves with tick and messages only move through step and take_messages,
+//which keeps the node free of threads and clocks and lets tests drive it message by message
+pub struct RaftNode {
+    id: NodeId,
+    peers: Vec<NodeId>,
+    config: RaftConfig,
+    state_machine: Arc<dyn StateMachine>,
+    role: Role,
+    term: u64,
+    voted_for: Option<NodeId>,
+    leader: Option<NodeId>,
+    log: RaftLog,
+    snapshot: Snapshot,
+    commit_index: u64,
+    last_applied: u64,
+    votes: HashSet<NodeId>,
+    next_index: HashMap<NodeId, u64>,
+    match_index: HashMap<NodeId, u64>,
+    election_elapsed: u64,
+    election_timeout: u64,
+    heartbeat_elapsed: u64,
+    random: u64,
+    outbox: Vec<(NodeId, RaftMessage)>,
+    applied: Vec<Applied>,
+}
+
+impl RaftNode {
+    pub fn new(id: NodeId, peers: Vec<NodeId>, config: RaftConfig, state_machine: Arc<dyn StateMachine>) -> RaftNode {
+        let mut node = RaftNode {
+            id,
+            peers: peers.into_iter().filter(|peer| *peer != id).collect(),
+            config,
+            state_machine,
+            role: Role::Follower,
+            term: 0,
+            voted_for: None,
+            leader: None,
+            log: RaftLog::new(),
+            snapshot: Vec::new(),
+            commit_index: 0,
+            last_applied: 0,
+            votes: HashSet::new(),
+            next_index: HashMap::new(),
+            match_index: HashMap::new(),
+            election_elapsed: 0,
+            election_timeout: 0,
+            heartbeat_elapsed: 0,
+            random: (config.seed() ^ id.wrapping_mul(0x9E37_79B9_7F4A_7C15)) | 1,
+            outbox: Vec::new(),
+            applied: Vec::new(),
+        };
+        node.reset_election_timeout();
+        return node;
+    }
+
+    pub fn id(&self) -> NodeId {
+        return self.id;
+    }
+
+    pub fn status(&self) -> RaftStatus {
+        return RaftStatus {
+            id: self.id,
+            role: self.role,
+            term: self.term,
+            leader: self.leader,
+            last_index: self.log.last_index(),
+            commit_index: self.commit_index,
+            last_applied: self.last_applied,
+            snapshot_index: self.log.snapshot_index(),
+        };
+    }
+
+    pub fn tick(&mut self) {
+        if self.role == Role::Leader {
+            self.heartbeat_elapsed += 1;
+            if self.heartbeat_elapsed >= self.config.heartbeat_ticks() {
+                self.heartbeat_elapsed = 0;
+                self.broadcast_append_entries();
+            }
+            return;
+        }
+        self.election_elapsed += 1;
+        if self.election_elapsed >= self.election_timeout {
+            self.start_election();
+        }
+    }
+
+    pub fn propose(&mut self, command: StorageCommand) -> Result<(u64, u64), QueueError> {
+        if self.role != Role::Leader {
+            return Err(QueueError::NotLeader);
+        }
+        let index = self.log.append(self.term, Some(command));
+        self.broadcast_append_entries();
+        self.advance_commit_index();
+        return Ok((index, self.term));
+    }
+
+    pub fn step(&mut self, from: NodeId, message: RaftMessage) {
+        //the sender is whatever the transport reports, a node outside the cluster must not count towards a majority
+        if !self.peers.contains(&from) {
+            return;
+        }
+        if message.term() > self.term {
+            let leader = match message {
+                RaftMessage::AppendEntries { .. } | RaftMessage::InstallSnapshot { .. } => Some(from),
+                _ => None,
+            };
+            self.become_follower(message.term(), leader);
+        }
+
+        match message {
+            RaftMessage::RequestVote { term, last_log_index, last_log_term } => {
+                self.handle_request_vote(from, term, last_log_index, last_log_term)
+            }
+            RaftMessage::RequestVoteResponse { term, vote_granted } => {
+                self.handle_request_vote_response(from, term, vote_granted)
+            }
+            RaftMessage::AppendEntries { term, prev_log_index, prev_log_term, entries, leader_commit } => {
+                self.handle_append_entries(from, term, prev_log_index, prev_log_term, entries, leader_commit)
+            }
+            RaftMessage::AppendEntriesResponse { term, success, match_index } => {
+                self.handle_append_entries_response(from, term, success, match_index)
+            }
+            RaftMessage::InstallSnapshot { term, last_included_index, last_included_term, data } => {
+                self.handle_install_snapshot(from, term, last_included_index, last_included_term, data)
+            }
+            RaftMessage::InstallSnapshotResponse { term, match_index } => {
+                self.handle_install_snapshot_response(from, term, match_index)
+            }
+        }
+    }
+
+    pub fn take_messages(&mut self) -> Vec<(NodeId, RaftMessage)> {
+        return std::mem::take(&mut self.outbox);
+    }
+
+    pub fn take_applied(&mut self) -> Vec<Applied> {
+        return std::mem::take(&mut self.applied);
+    }
+
+    //models a crash, only the persistent state survives and everything else starts over as in a new node, the state machine
+    //is rebuilt from the snapshot and the committed entries are applied again once a leader reports the commit index,
+    //the persistent state lives in memory here, a node which survives a real crash has to write it before it answers a message
+    pub fn restart(&mut self) {
+        let persistent = PersistentState {
+            term: self.term,
+            voted_for: self.voted_for,
+            log: std::mem::take(&mut self.log),
+            snapshot: std::mem::take(&mut self.snapshot),
+        };
+        let mut node = RaftNode::new(self.id, self.peers.clone(), self.config, self.state_machine.clone());
+        node.term = persistent.term;
+        node.voted_for = persistent.voted_for;
+        node.commit_index = persistent.log.snapshot_index();
+        node.last_applied = persistent.log.snapshot_index();
+        node.log = persistent.log;
+        node.state_machine.restore(persistent.snapshot.clone());
+        node.snapshot = persistent.snapshot;
+        *self = node;
+    }
+
+    fn start_election(&mut self) {
+        self.role = Role::Candidate;
+        self.term += 1;
+        self.voted_for = Some(self.id);
+        self.leader = None;
+        self.votes = HashSet::from([self.id]);
+        self.reset_election_timeout();
+
+        if self.votes.len() >= self.majority() {
+            self.become_leader();
+            return;
+        }
+        for peer in self.peers.clone() {
+            self.send(peer, RaftMessage::RequestVote {
+                term: self.term,
+                last_log_index: self.log.last_index(),
+                last_log_term: self.log.last_term(),
+            });
+        }
+    }
+
+    fn become_follower(&mut self, term: u64, leader: Option<NodeId>) {
+        if term > self.term {
+            self.term = term;
+            self.voted_for = None;
+        }
+        self.role = Role::Follower;
+        self.leader = leader;
+        self.votes.clear();
+        self.reset_election_timeout();
+    }
+
+    fn become_leader(&mut self) {
+        self.role = Role::Leader;
+        self.leader = Some(self.id);
+        self.heartbeat_elapsed = 0;
+        let next_index = self.log.last_index() + 1;
+        for peer in &self.peers {
+            self.next_index.insert(*peer, next_index);
+            self.match_index.insert(*peer, 0);
+        }
+        self.log.append(self.term, None);
+        self.broadcast_append_entries();
+        self.advance_commit_index();
+    }
+
+    fn handle_request_vote(&mut self, from: NodeId, term: u64, last_log_index: u64, last_log_term: u64) {
+        let log_is_up_to_date = (last_log_term, last_log_index) >= (self.log.last_term(), self.log.last_index());
+        let can_vote = self.voted_for.is_none() || self.voted_for == Some(from);
+        let vote_granted = term == self.term && can_vote && log_is_up_to_date;
+        if vote_granted {
+            self.voted_for = Some(from);
+            self.election_elapsed = 0;
+        }
+        self.send(from, RaftMessage::RequestVoteResponse { term: self.term, vote_granted });
+    }
+
+    fn handle_request_vote_response(&mut self, from: NodeId, term: u64, vote_granted: bool) {
+        if self.role != Role::Candidate || term != self.term || !vote_granted {
+            return;
+        }
+        self.votes.insert(from);
+        if self.votes.len() >= self.majority() {
+            self.become_leader();
+        }
+    }
+
+    fn handle_append_entries(
+        &mut self,
+        from: NodeId,
+        term: u64,
+        prev_log_index: u64,
+        prev_log_term: u64,
+        entries: Vec<crate::raft::raft_log::RaftEntry>,
+        leader_commit: u64,
+    ) {
+        if term < self.term {
+            self.send(from, RaftMessage::AppendEntriesResponse { term: self.term, success: false, match_index: 0 });
+            return;
+        }
+        self.become_follower(term, Some(from));
+
+        if prev_log_index > self.log.last_index() {
+            let match_index = self.log.last_index();
+            self.send(from, RaftMessage::AppendEntriesResponse { term: self.term, success: false, match_index });
+            return;
+        }
+        //entries at or below the snapshot are committed, so they agree with the leader by definition
+        if prev_log_index >= self.log.snapshot_index() && self.log.term_at(prev_log_index) != Some(prev_log_term) {
+            let match_index = prev_log_index.saturating_sub(1).max(self.commit_index);
+            self.send(from, RaftMessage::AppendEntriesResponse { term: self.term, success: false, match_index });
+            return;
+        }
+
+        let match_index = prev_log_index + entries.len() as u64;
+        for entry in entries {
+            self.log.merge(entry);
+        }
+        //a stale request may verify less of the log than an earlier one did, the commit index never moves back
+        let commit_index = self.commit_index.max(leader_commit.min(match_index));
+        if commit_index > self.commit_index {
+            self.commit_index = commit_index;
+            self.apply_committed();
+        }
+        self.send(from, RaftMessage::AppendEntriesResponse { term: self.term, success: true, match_index });
+    }
+
+    fn handle_append_entries_response(&mut self, from: NodeId, term: u64, success: bool, match_index: u64) {
+        if self.role != Role::Leader || term != self.term {
+            return;
+        }
+        if success {
+            let known = self.match_index.get(&from).copied().unwrap_or(0);
+            self.match_index.insert(from, known.max(match_index));
+            self.next_index.insert(from, known.max(match_index) + 1);
+            self.advance_commit_index();
+            if self.next_index[&from] <= self.log.last_index() {
+                self.send_append_entries(from);
+            }
+            return;
+        }
+        let next_index = self.next_index.get(&from).copied().unwrap_or(1);
+        self.next_index.insert(from, (match_index + 1).min(next_index.saturating_sub(1)).max(1));
+        self.send_append_entries(from);
+    }
+
+    fn handle_install_snapshot(&mut self, from: NodeId, term: u64, last_included_index: u64, last_included_term: u64, data: Snapshot) {
+        if term < self.term {
+            self.send(from, RaftMessage::InstallSnapshotResponse { term: self.term, match_index: 0 });
+            return;
+        }
+        self.become_follower(term, Some(from));
+
+        if last_included_index > self.commit_index {
+            self.state_machine.restore(data.clone());
+            self.snapshot = data;
+            self.log.restore(last_included_index, last_included_term);
+            self.commit_index = last_included_index;
+            self.last_applied = last_included_index;
+        }
+        self.send(from, RaftMessage::InstallSnapshotResponse { term: self.term, match_index: last_included_index });
+    }
+
+    fn handle_install_snapshot_response(&mut self, from: NodeId, term: u64, match_index: u64) {
+        if self.role != Role::Leader || term != self.term {
+            return;
+        }
+        let known = self.match_index.get(&from).copied().unwrap_or(0);
+        self.match_index.insert(from, known.max(match_index));
+        self.next_index.insert(from, known.max(match_index) + 1);
+        self.advance_commit_index();
+        if self.next_index[&from] <= self.log.last_index() {
+            self.send_append_entries(from);
+        }
+    }
+
+    fn broadcast_append_entries(&mut self) {
+        for peer in self.peers.clone() {
+            self.send_append_entries(peer);
+        }
+    }
+
+    //a follower which needs compacted entries receives the snapshot instead
+    fn send_append_entries(&mut self, peer: NodeId) {
+        let next_index = self.next_index.get(&peer).copied().unwrap_or(self.log.last_index() + 1);
+        if next_index <= self.log.snapshot_index() {
+            self.send(peer, RaftMessage::InstallSnapshot {
+                term: self.term,
+                last_included_index: self.log.snapshot_index(),
+                last_included_term: self.log.snapshot_term(),
+                data: self.snapshot.clone(),
+            });
+            return;
+        }
+        let prev_log_index = next_index - 1;
+        self.send(peer, RaftMessage::AppendEntries {
+            term: self.term,
+            prev_log_index,
+            prev_log_term: self.log.term_at(prev_log_index).unwrap_or(0),
+            entries: self.log.entries_from(next_index, self.config.max_entries_per_message()),
+            leader_commit: self.commit_index,
+        });
+    }
+
+    //only entries of the current term are committed by counting replicas, earlier ones are committed along with them
+    fn advance_commit_index(&mut self) {
+        let mut index = self.log.last_index();
+        while index > self.commit_index {
+            if self.log.term_at(index) == Some(self.term) {
+                let replicas = 1 + self.match_index.values().filter(|match_index| **match_index >= index).count();
+                if replicas >= self.majority() {
+                    self.commit_index = index;
+                    self.apply_committed();
+                    return;
+                }
+            }
+            index -= 1;
+        }
+    }
+
+    fn apply_committed(&mut self) {
+        while self.last_applied < self.commit_index {
+            let index = self.last_applied + 1;
+            let entry = self.log.entry(index).cloned().unwrap();
+            let status = match entry.command {
+                Some(command) => self.state_machine.handle(command),
+                None => Status::Ok,
+            };
+            self.last_applied = index;
+            self.applied.push(Applied { index, term: entry.term, status });
+        }
+        if self.last_applied - self.log.snapshot_index() >= self.config.snapshot_threshold() {
+            self.snapshot = self.state_machine.snapshot();
+            self.log.compact(self.last_applied);
+        }
+    }
+
+    fn send(&mut self, to: NodeId, message: RaftMessage) {
+        self.outbox.push((to, message));
+    }
+
+    fn majority(&self) -> usize {
+        let nodes = self.peers.len() + 1;
+        return nodes / 2 + 1;
+    }
+
+    fn reset_election_timeout(&mut self) {
+        self.election_elapsed = 0;
+        let spread = self.config.max_election_ticks() - self.config.min_election_ticks() + 1;
+        self.election_timeout = self.config.min_election_ticks() + self.next_random() % spread;
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 7;
+        self.random ^= self.random << 17;
+        return self.random;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+
+    use crate::raft::raft_log::RaftEntry;
+    use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Storage};
+
+    use super::*;
+
+    fn node(id: NodeId, peers: Vec<NodeId>, config: RaftConfig) -> (RaftNode, Storage) {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let state_machine = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        return (RaftNode::new(id, peers, config, state_machine), storage);
+    }
+
+    fn put(key: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from("value") };
+    }
+
+    //delivers every pending message until the nodes go quiet, dropping messages to and from the isolated nodes
+    fn deliver(nodes: &mut [RaftNode], isolated: &[NodeId]) {
+        loop {
+            let mut messages = Vec::new();
+            for node in nodes.iter_mut() {
+                let from = node.id();
+                messages.extend(node.take_messages().into_iter().map(|(to, message)| (from, to, message)));
+            }
+            if messages.is_empty() {
+                return;
+            }
+            for (from, to, message) in messages {
+                if isolated.contains(&from) || isolated.contains(&to) {
+                    continue;
+                }
+                nodes.iter_mut().find(|node| node.id() == to).unwrap().step(from, message);
+            }
+        }
+    }
+
+    fn elect(nodes: &mut [RaftNode], candidate: usize) {
+        while nodes[candidate].status().role != Role::Candidate {
+            nodes[candidate].tick();
+        }
+        deliver(nodes, &[]);
+    }
+
+    #[test]
+    fn test_single_node_elects_itself() {
+        let (mut node, storage) = node(1, vec![1], RaftConfig::default());
+        for _ in 0..RaftConfig::default().max_election_ticks() {
+            node.tick();
+        }
+
+        assert_eq!(Role::Leader, node.status().role);
+        assert_eq!(Ok((2, 1)), node.propose(put("key1")));
+        assert_eq!(2, node.status().commit_index);
+        assert_eq!("value", storage.read().unwrap().get("key1").unwrap());
+    }
+
+    #[test]
+    fn test_elect_a_leader_and_replicate() {
+        let peers = vec![1, 2, 3];
+        let (node_one, _) = node(1, peers.clone(), RaftConfig::default());
+        let (node_two, storage_two) = node(2, peers.clone(), RaftConfig::default());
+        let (node_three, storage_three) = node(3, peers, RaftConfig::default());
+        let mut nodes = vec![node_one, node_two, node_three];
+
+        elect(&mut nodes, 0);
+        assert_eq!(Role::Leader, nodes[0].status().role);
+        assert_eq!(Some(1), nodes[1].status().leader);
+
+        assert_eq!(Ok((2, 1)), nodes[0].propose(put("key1")));
+        deliver(&mut nodes, &[]);
+        nodes[0].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+
+        assert_eq!(2, nodes[0].status().commit_index);
+        assert_eq!(2, nodes[1].status().last_applied);
+        assert_eq!(true, storage_two.read().unwrap().contains_key("key1"));
+        assert_eq!(true, storage_three.read().unwrap().contains_key("key1"));
+    }
+
+    #[test]
+    fn test_follower_rejects_a_proposal() {
+        let (mut node, _) = node(1, vec![1, 2, 3], RaftConfig::default());
+        assert_eq!(Err(QueueError::NotLeader), node.propose(put("key1")));
+    }
+
+    #[test]
+    fn test_a_stale_leader_steps_down() {
+        let peers = vec![1, 2, 3];
+        let mut nodes: Vec<RaftNode> = peers.iter().map(|id| node(*id, peers.clone(), RaftConfig::default()).0).collect();
+        elect(&mut nodes, 0);
+
+        while nodes[1].status().role != Role::Candidate {
+            nodes[1].tick();
+        }
+        deliver(&mut nodes, &[1]);
+        assert_eq!(Role::Leader, nodes[1].status().role);
+        assert_eq!(2, nodes[1].status().term);
+
+        nodes[1].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+        assert_eq!(Role::Follower, nodes[0].status().role);
+        assert_eq!(Some(2), nodes[0].status().leader);
+    }
+
+    #[test]
+    fn test_uncommitted_entries_of_a_deposed_leader_are_replaced() {
+        let peers = vec![1, 2, 3];
+        let mut nodes: Vec<RaftNode> = peers.iter().map(|id| node(*id, peers.clone(), RaftConfig::default()).0).collect();
+        elect(&mut nodes, 0);
+
+        nodes[0].propose(put("lost")).unwrap();
+        nodes[0].take_messages();
+
+        while nodes[1].status().role != Role::Candidate {
+            nodes[1].tick();
+        }
+        deliver(&mut nodes, &[1]);
+        nodes[1].propose(put("kept")).unwrap();
+        deliver(&mut nodes, &[]);
+        nodes[1].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+
+        let applied: Vec<Option<StorageCommand>> = (1..=nodes[0].status().last_applied)
+            .map(|index| nodes[0].log.entry(index).and_then(|entry| entry.command.clone()))
+            .collect();
+        assert_eq!(false, applied.contains(&Some(put("lost"))));
+        assert_eq!(true, applied.contains(&Some(put("kept"))));
+    }
+
+    #[test]
+    fn test_a_reordered_append_does_not_move_the_commit_index_back() {
+        let (mut follower, _) = node(2, vec![1, 2], RaftConfig::default());
+        let entries: Vec<RaftEntry> = (1..=3).map(|index| RaftEntry { index, term: 1, command: Some(put(&format!("key{}", index))) }).collect();
+
+        follower.step(1, RaftMessage::AppendEntries { term: 1, prev_log_index: 0, prev_log_term: 0, entries: entries.clone(), leader_commit: 2 });
+        assert_eq!(2, follower.status().commit_index);
+
+        follower.step(1, RaftMessage::AppendEntries { term: 1, prev_log_index: 0, prev_log_term: 0, entries: entries[..1].to_vec(), leader_commit: 3 });
+        assert_eq!(2, follower.status().commit_index);
+        assert_eq!(2, follower.status().last_applied);
+    }
+
+    #[test]
+    fn test_ignore_messages_from_outside_the_cluster() {
+        let peers = vec![1, 2, 3];
+        let mut nodes: Vec<RaftNode> = peers.iter().map(|id| node(*id, peers.clone(), RaftConfig::default()).0).collect();
+        while nodes[0].status().role != Role::Candidate {
+            nodes[0].tick();
+        }
+        let term = nodes[0].status().term;
+        nodes[0].step(7, RaftMessage::RequestVoteResponse { term, vote_granted: true });
+        assert_eq!(Role::Candidate, nodes[0].status().role);
+
+        deliver(&mut nodes, &[]);
+        assert_eq!(Role::Leader, nodes[0].status().role);
+        let commit_index = nodes[0].status().commit_index;
+        nodes[0].propose(put("key1")).unwrap();
+        nodes[0].step(7, RaftMessage::AppendEntriesResponse { term, success: true, match_index: 2 });
+        assert_eq!(commit_index, nodes[0].status().commit_index);
+    }
+
+    #[test]
+    fn test_install_a_snapshot_on_a_lagging_follower() {
+        let peers = vec![1, 2, 3];
+        let config = RaftConfig::default().with_snapshot_threshold(4);
+        let (node_one, _) = node(1, peers.clone(), config);
+        let (node_two, _) = node(2, peers.clone(), config);
+        let (node_three, storage_three) = node(3, peers, config);
+        let mut nodes = vec![node_one, node_two, node_three];
+        elect(&mut nodes, 0);
+
+        for index in 0..10 {
+            nodes[0].propose(put(&format!("key{}", index))).unwrap();
+            deliver(&mut nodes, &[3]);
+        }
+        assert_eq!(true, nodes[0].status().snapshot_index > 0);
+
+        nodes[0].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+        nodes[0].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+
+        assert_eq!(nodes[0].status().commit_index, nodes[2].status().last_applied);
+        assert_eq!(true, nodes[2].status().snapshot_index > 0);
+        assert_eq!(10, storage_three.read().unwrap().len());
+    }
+
+    #[test]
+    fn test_restart_rebuilds_the_state_machine() {
+        let peers = vec![1, 2, 3];
+        let (node_one, _) = node(1, peers.clone(), RaftConfig::default());
+        let (node_two, storage_two) = node(2, peers.clone(), RaftConfig::default());
+        let (node_three, _) = node(3, peers, RaftConfig::default());
+        let mut nodes = vec![node_one, node_two, node_three];
+        elect(&mut nodes, 0);
+        nodes[0].propose(put("key1")).unwrap();
+        deliver(&mut nodes, &[]);
+        nodes[0].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+
+        nodes[1].restart();
+        assert_eq!(0, nodes[1].status().last_applied);
+        assert_eq!(true, storage_two.read().unwrap().is_empty());
+        assert_eq!(1, nodes[1].status().term);
+        assert_eq!(None, nodes[1].status().leader);
+        assert_eq!(Some(1), nodes[1].voted_for);
+
+        nodes[0].tick_until_heartbeat();
+        deliver(&mut nodes, &[]);
+        assert_eq!(2, nodes[1].status().last_applied);
+        assert_eq!(true, storage_two.read().unwrap().contains_key("key1"));
+    }
+
+    impl RaftNode {
+        fn tick_until_heartbeat(&mut self) {
+            for _ in 0..self.config.heartbeat_ticks() {
+                self.tick();
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::raft::raft_message::{NodeId, RaftMessage};
+use crate::raft::raft_node::{RaftNode, RaftStatus};
+use crate::raft::raft_transport::RaftTransport;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+
+pub enum RaftInput {
+    Message {
+        from: NodeId,
+        message: RaftMessage,
+    },
+    Propose {
+        command: StorageCommand,
+        respond_back: Sender<Status>,
+    },
+    Status(Sender<RaftStatus>),
+    Stop,
+}
+
+//owns a raft node on its own thread, every input and every tick is handled by that single thread
+pub struct RaftServer {
+    id: NodeId,
+    inbox: Sender<RaftInput>,
+    runner: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Runner {
+    node: RaftNode,
+    transport: Arc<dyn RaftTransport>,
+    tick_interval: Duration,
+    pending: HashMap<u64, (u64, Sender<Status>)>,
+}
+
+impl RaftServer {
+    pub fn start(node: RaftNode, transport: Arc<dyn RaftTransport>, tick_interval: Duration) -> RaftServer {
+        let id = node.id();
+        let (inbox, receiver) = mpsc::channel();
+        let runner = Runner { node, transport, tick_interval, pending: HashMap::new() };
+        let runner = thread::spawn(move || runner.run(receiver));
+        return RaftServer { id, inbox, runner: Mutex::new(Some(runner)) };
+    }
+
+    pub fn id(&self) -> NodeId {
+        return self.id;
+    }
+
+    pub fn inbox(&self) -> Sender<RaftInput> {
+        return self.inbox.clone();
+    }
+
+    pub fn status(&self) -> Option<RaftStatus> {
+        let (sender, receiver) = mpsc::channel();
+        self.inbox.send(RaftInput::Status(sender)).ok()?;
+        return receiver.recv().ok();
+    }
+
+    pub fn stop(&self) {
+        let _ = self.inbox.send(RaftInput::Stop);
+        if let Some(runner) = self.runner.lock().unwrap().take() {
+            let _ = runner.join();
+        }
+    }
+}
+
+impl Drop for RaftServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Runner {
+    fn run(mut self, receiver: Receiver<RaftInput>) {
+        let mut next_tick = Instant::now() + self.tick_interval;
+        loop {
+            let now = Instant::now();
+            if now >= next_tick {
+                self.node.tick();
+                next_tick = now + self.tick_interval;
+            } else {
+                match receiver.recv_timeout(next_tick - now) {
+                    Ok(RaftInput::Message { from, message }) => self.node.step(from, message),
+                    Ok(RaftInput::Propose { command, respond_back }) => self.propose(command, respond_back),
+                    Ok(RaftInput::Status(respond_back)) => {
+                        let _ = respond_back.send(self.node.status());
+                    }
+                    Ok(RaftInput::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+                    Err(RecvTimeoutError::Timeout) => {}
+                }
+            }
+            self.flush();
+        }
+    }
+
+    fn propose(&mut self, command: StorageCommand, respond_back: Sender<Status>) {
+        match self.node.propose(command) {
+            Ok((index, term)) => {
+                self.pending.insert(index, (term, respond_back));
+            }
+            Err(error) => {
+                let _ = respond_back.send(Status::Failed(error));
+            }
+        }
+    }
+
+    //a proposal whose index was applied with another term was overwritten by a newer leader
+    fn flush(&mut self) {
+        for (to, message) in self.node.take_messages() {
+            self.transport.send(self.node.id(), to, message);
+        }
+        for applied in self.node.take_applied() {
+            if let Some((term, respond_back)) = self.pending.remove(&applied.index) {
+                let status = if term == applied.term { applied.status } else { Status::Failed(QueueError::NotLeader) };
+                let _ = respond_back.send(status);
+            }
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::mpsc::Sender;
+
+use crate::raft::raft_message::{NodeId, RaftMessage};
+use crate::raft::raft_server::RaftInput;
+
+//raft tolerates lost messages, so sending is fire and forget and an unreachable node simply misses the message
+pub trait RaftTransport: Send + Sync {
+    fn send(&self, from: NodeId, to: NodeId, message: RaftMessage);
+}
+
+#[derive(Default)]
+pub struct InMemoryNetwork {
+    inboxes: Mutex<HashMap<NodeId, Sender<RaftInput>>>,
+    isolated: Mutex<HashSet<NodeId>>,
+}
+
+impl InMemoryNetwork {
+    pub fn new() -> InMemoryNetwork {
+        return InMemoryNetwork::default();
+    }
+
+    pub fn register(&self, id: NodeId, inbox: Sender<RaftInput>) {
+        self.inboxes.lock().unwrap().insert(id, inbox);
+    }
+
+    //an isolated node neither sends nor receives messages until it is healed
+    pub fn isolate(&self, id: NodeId) {
+        self.isolated.lock().unwrap().insert(id);
+    }
+
+    pub fn heal(&self, id: NodeId) {
+        self.isolated.lock().unwrap().remove(&id);
+    }
+}
+
+impl RaftTransport for InMemoryNetwork {
+    fn send(&self, from: NodeId, to: NodeId, message: RaftMessage) {
+        {
+            let isolated = self.isolated.lock().unwrap();
+            if isolated.contains(&from) || isolated.contains(&to) {
+                return;
+            }
+        }
+        if let Some(inbox) = self.inboxes.lock().unwrap().get(&to) {
+            let _ = inbox.send(RaftInput::Message { from, message });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    fn heartbeat() -> RaftMessage {
+        return RaftMessage::AppendEntries { term: 1, prev_log_index: 0, prev_log_term: 0, entries: Vec::new(), leader_commit: 0 };
+    }
+
+    #[test]
+    fn test_drop_messages_of_an_isolated_node() {
+        let network = InMemoryNetwork::new();
+        let (sender, receiver) = mpsc::channel();
+        network.register(2, sender);
+
+        network.isolate(1);
+        network.send(1, 2, heartbeat());
+        assert_eq!(true, receiver.try_recv().is_err());
+
+        network.heal(1);
+        network.send(1, 2, heartbeat());
+        assert_eq!(true, matches!(receiver.try_recv(), Ok(RaftInput::Message { from: 1, .. })));
+    }
+}
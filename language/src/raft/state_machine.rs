@@ -0,0 +1,73 @@
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Status, StorageCommand};
+
+//a snapshot entry is a key, its value and its version, versions have to survive a snapshot for transactions to agree across nodes,
+//so a deleted key stays in the snapshot without a value
+pub type Snapshot = Vec<(String, Option<String>, u64)>;
+
+//a replicated state machine applies committed commands and can be captured into, or rebuilt from, a snapshot
+pub trait StateMachine: CommandHandler<StorageCommand, Status> {
+    fn snapshot(&self) -> Snapshot;
+
+    fn restore(&self, snapshot: Snapshot);
+}
+
+impl StateMachine for InMemoryStorageHandler {
+    fn snapshot(&self) -> Snapshot {
+        let versions = self.versions().unwrap();
+        let storage = self.storage().read().unwrap();
+        let mut snapshot: Snapshot = storage
+            .iter()
+            .map(|(key, value)| (key.clone(), Some(value.clone()), versions.get(key).copied().unwrap_or(0)))
+            .collect();
+        snapshot.extend(versions.iter().filter(|(key, _)| !storage.contains_key(*key)).map(|(key, version)| (key.clone(), None, *version)));
+        snapshot.sort();
+        return snapshot;
+    }
+
+    fn restore(&self, snapshot: Snapshot) {
+        self.replace(snapshot).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = InMemoryStorageHandler::new(storage.clone());
+        handler.handle(StorageCommand::Put { key: String::from("key2"), value: String::from("value2") });
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+
+        let snapshot = handler.snapshot();
+        assert_eq!(vec![
+            (String::from("key1"), Some(String::from("value1")), 1),
+            (String::from("key2"), Some(String::from("value2")), 1),
+        ], snapshot);
+
+        let other = InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new())));
+        other.restore(snapshot);
+        assert_eq!(other.snapshot(), handler.snapshot());
+        assert_eq!(Ok(1), other.version("key1"));
+    }
+
+    #[test]
+    fn test_keep_the_version_of_a_deleted_key() {
+        let handler = InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new())));
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+        handler.handle(StorageCommand::Delete { key: String::from("key1") });
+
+        let snapshot = handler.snapshot();
+        assert_eq!(vec![(String::from("key1"), None, 2)], snapshot);
+
+        let other = InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new())));
+        other.restore(snapshot);
+        assert_eq!(Ok(2), other.version("key1"));
+        assert_eq!(true, other.storage().read().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,16 @@
+use crate::singular_update_queue::write_ahead_log::LogEntry;
+
+//entries after prev_index together with the leader's commit index, an empty request only propagates the commit index
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AppendEntriesRequest {
+    pub prev_index: u64,
+    pub entries: Vec<LogEntry>,
+    pub commit_index: u64,
+}
+
+//a follower which is behind rejects the request and reports its last index so the leader can resend from there
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct AppendEntriesResponse {
+    pub success: bool,
+    pub last_index: u64,
+}
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+
+use crate::replication::append_entries::{AppendEntriesRequest, AppendEntriesResponse};
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+use crate::singular_update_queue::write_ahead_log::LogEntry;
+
+pub struct Follower {
+    log: Mutex<FollowerLog>,
+    handler: Arc<dyn CommandHandler<StorageCommand, Status>>,
+}
+
+struct FollowerLog {
+    entries: Vec<LogEntry>,
+    commit_index: u64,
+    last_applied: u64,
+}
+
+//entries are only handed to the handler once the leader reports them as committed
+impl Follower {
+    pub fn new(handler: Arc<dyn CommandHandler<StorageCommand, Status>>) -> Follower {
+        return Follower {
+            log: Mutex::new(FollowerLog { entries: Vec::new(), commit_index: 0, last_applied: 0 }),
+            handler,
+        };
+    }
+
+    pub fn append_entries(&self, request: AppendEntriesRequest) -> AppendEntriesResponse {
+        let mut log = self.log.lock().unwrap();
+        let last_index = log.entries.len() as u64;
+        if request.prev_index > last_index {
+            return AppendEntriesResponse { success: false, last_index };
+        }
+        //the request comes from the network, entries which do not follow prev_index one by one or which would replace
+        //a committed entry are rejected before anything changes
+        let contiguous = request.entries.iter().enumerate().all(|(offset, entry)| entry.index == request.prev_index + 1 + offset as u64);
+        let replaces_committed = |entry: &LogEntry| entry.index <= log.commit_index && log.entries[entry.index as usize - 1].command != entry.command;
+        if !contiguous || request.entries.iter().any(replaces_committed) {
+            return AppendEntriesResponse { success: false, last_index };
+        }
+
+        //the entries up to prev_index are known to match the leader's, the ones past it only once the leader sends them
+        let verified_index = request.prev_index + request.entries.len() as u64;
+        for entry in request.entries {
+            let position = entry.index as usize - 1;
+            if position < log.entries.len() {
+                //a retried request resends entries delivered before, a different command means this follower diverged
+                if log.entries[position].command == entry.command {
+                    continue;
+                }
+                log.entries.truncate(position);
+            }
+            log.entries.push(entry);
+        }
+
+        log.commit_index = log.commit_index.max(request.commit_index.min(verified_index));
+        while log.last_applied < log.commit_index {
+            let command = log.entries[log.last_applied as usize].command.clone();
+            self.handler.handle(command);
+            log.last_applied += 1;
+        }
+        return AppendEntriesResponse { success: true, last_index: verified_index };
+    }
+
+    pub fn last_index(&self) -> u64 {
+        return self.log.lock().unwrap().entries.len() as u64;
+    }
+
+    pub fn commit_index(&self) -> u64 {
+        return self.log.lock().unwrap().commit_index;
+    }
+
+    pub fn last_applied(&self) -> u64 {
+        return self.log.lock().unwrap().last_applied;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+
+    use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Storage};
+
+    use super::*;
+
+    fn put(index: u64, key: &str) -> LogEntry {
+        return LogEntry { index, command: StorageCommand::Put { key: String::from(key), value: String::from("value") } };
+    }
+
+    #[test]
+    fn test_apply_only_committed_entries() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Follower::new(Arc::new(InMemoryStorageHandler::new(storage.clone())));
+
+        let response = follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "key1"), put(2, "key2")], commit_index: 1 });
+
+        assert_eq!(AppendEntriesResponse { success: true, last_index: 2 }, response);
+        assert_eq!(1, follower.last_applied());
+        assert_eq!(true, storage.read().unwrap().contains_key("key1"));
+        assert_eq!(false, storage.read().unwrap().contains_key("key2"));
+
+        follower.append_entries(AppendEntriesRequest { prev_index: 2, entries: Vec::new(), commit_index: 2 });
+        assert_eq!(2, follower.commit_index());
+        assert_eq!(true, storage.read().unwrap().contains_key("key2"));
+    }
+
+    #[test]
+    fn test_reject_entries_beyond_the_last_index() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Follower::new(Arc::new(InMemoryStorageHandler::new(storage)));
+
+        let response = follower.append_entries(AppendEntriesRequest { prev_index: 3, entries: vec![put(4, "key4")], commit_index: 4 });
+
+        assert_eq!(AppendEntriesResponse { success: false, last_index: 0 }, response);
+        assert_eq!(0, follower.last_index());
+    }
+
+    #[test]
+    fn test_ignore_redelivered_entries() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Follower::new(Arc::new(InMemoryStorageHandler::new(storage)));
+
+        follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "key1")], commit_index: 0 });
+        let response = follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "key1"), put(2, "key2")], commit_index: 0 });
+
+        assert_eq!(AppendEntriesResponse { success: true, last_index: 2 }, response);
+    }
+
+    #[test]
+    fn test_replace_entries_which_diverge_from_the_leader() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Follower::new(Arc::new(InMemoryStorageHandler::new(storage.clone())));
+        follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "key1"), put(2, "stale2"), put(3, "stale3")], commit_index: 1 });
+
+        let response = follower.append_entries(AppendEntriesRequest { prev_index: 1, entries: vec![put(2, "key2")], commit_index: 2 });
+
+        assert_eq!(AppendEntriesResponse { success: true, last_index: 2 }, response);
+        assert_eq!(2, follower.last_index());
+        assert_eq!(true, storage.read().unwrap().contains_key("key2"));
+        assert_eq!(false, storage.read().unwrap().contains_key("stale2"));
+    }
+
+    #[test]
+    fn test_only_acknowledge_entries_the_leader_sent() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Follower::new(Arc::new(InMemoryStorageHandler::new(storage.clone())));
+        follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "key1"), put(2, "stale2")], commit_index: 0 });
+
+        let response = follower.append_entries(AppendEntriesRequest { prev_index: 1, entries: Vec::new(), commit_index: 2 });
+
+        assert_eq!(AppendEntriesResponse { success: true, last_index: 1 }, response);
+        assert_eq!(1, follower.commit_index());
+        assert_eq!(false, storage.read().unwrap().contains_key("stale2"));
+    }
+
+    #[test]
+    fn test_reject_a_malformed_request() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Follower::new(Arc::new(InMemoryStorageHandler::new(storage.clone())));
+        follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "key1")], commit_index: 1 });
+
+        let rejected = AppendEntriesResponse { success: false, last_index: 1 };
+        assert_eq!(rejected, follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(0, "key0")], commit_index: 1 }));
+        assert_eq!(rejected, follower.append_entries(AppendEntriesRequest { prev_index: 1, entries: vec![put(5, "key5")], commit_index: 1 }));
+        assert_eq!(rejected, follower.append_entries(AppendEntriesRequest { prev_index: 0, entries: vec![put(1, "other1")], commit_index: 1 }));
+
+        //the follower keeps serving the leader after rejecting them
+        let response = follower.append_entries(AppendEntriesRequest { prev_index: 1, entries: vec![put(2, "key2")], commit_index: 2 });
+        assert_eq!(AppendEntriesResponse { success: true, last_index: 2 }, response);
+        assert_eq!(true, storage.read().unwrap().contains_key("key1"));
+        assert_eq!(false, storage.read().unwrap().contains_key("other1"));
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::Instant;
+
+use crate::replication::append_entries::AppendEntriesRequest;
+use crate::replication::replication_config::ReplicationConfig;
+use crate::replication::transport::ReplicationTransport;
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+use crate::singular_update_queue::write_ahead_log::LogEntry;
+
+//assigns a log index to every command and applies it to the inner handler once a quorum has the entry
+pub struct ReplicatingHandler {
+    state: Arc<LeaderState>,
+    inner: Arc<dyn CommandHandler<StorageCommand, Status>>,
+    config: ReplicationConfig,
+}
+
+struct LeaderState {
+    log: Mutex<LeaderLog>,
+    changed: Condvar,
+}
+
+struct LeaderLog {
+    entries: Vec<LogEntry>,
+    commit_index: u64,
+    last_applied: u64,
+    match_indices: Vec<u64>,
+    stopped: bool,
+}
+
+struct Replicator {
+    follower: usize,
+    state: Arc<LeaderState>,
+    transport: Arc<dyn ReplicationTransport>,
+    config: ReplicationConfig,
+    next_index: u64,
+    sent_commit_index: u64,
+}
+
+impl ReplicatingHandler {
+    pub fn new(
+        inner: Arc<dyn CommandHandler<StorageCommand, Status>>,
+        followers: Vec<Arc<dyn ReplicationTransport>>,
+        config: ReplicationConfig,
+    ) -> ReplicatingHandler {
+        assert!(config.quorum() <= followers.len() + 1, "quorum can not exceed the number of nodes");
+        let state = Arc::new(LeaderState {
+            log: Mutex::new(LeaderLog {
+                entries: Vec::new(),
+                commit_index: 0,
+                last_applied: 0,
+                match_indices: vec![0; followers.len()],
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+        });
+
+        for (follower, transport) in followers.into_iter().enumerate() {
+            let replicator = Replicator {
+                follower,
+                state: state.clone(),
+                transport,
+                config,
+                next_index: 1,
+                sent_commit_index: 0,
+            };
+            thread::spawn(move || replicator.run());
+        }
+        return ReplicatingHandler { state, inner, config };
+    }
+
+    pub fn commit_index(&self) -> u64 {
+        return self.state.lock().commit_index;
+    }
+
+    pub fn match_indices(&self) -> Vec<u64> {
+        return self.state.lock().match_indices.clone();
+    }
+
+    pub fn stop(&self) {
+        self.state.lock().stopped = true;
+        self.state.changed.notify_all();
+    }
+
+    //an entry which misses the quorum in time stays in the log, it is committed and applied along with a later entry,
+    //so a timed out caller can not assume the command was dropped
+    fn replicate(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+        let count = commands.len();
+        let mut log = self.state.lock();
+        let first_index = log.entries.len() as u64 + 1;
+        for command in commands {
+            let index = log.entries.len() as u64 + 1;
+            log.entries.push(LogEntry { index, command });
+        }
+        let last_index = log.entries.len() as u64;
+        self.state.changed.notify_all();
+
+        let deadline = Instant::now() + self.config.ack_timeout();
+        while log.acknowledgements(last_index) < self.config.quorum() {
+            let now = Instant::now();
+            if now >= deadline {
+                return vec![Status::Failed(QueueError::Timeout); count];
+            }
+            log = self.state.changed.wait_timeout(log, deadline - now).unwrap().0;
+        }
+
+        log.commit_index = log.commit_index.max(last_index);
+        let mut statuses = Vec::with_capacity(count);
+        while log.last_applied < log.commit_index {
+            let entry = &log.entries[log.last_applied as usize];
+            let (index, command) = (entry.index, entry.command.clone());
+            let status = self.inner.handle(command);
+            if index >= first_index {
+                statuses.push(status);
+            }
+            log.last_applied += 1;
+        }
+        self.state.changed.notify_all();
+        return statuses;
+    }
+}
+
+//reads are answered by the leader's inner handler without replication, once the writes ahead of them in the batch are applied
+impl CommandHandler<StorageCommand, Status> for ReplicatingHandler {
+    fn handle(&self, command: StorageCommand) -> Status {
+        return self.handle_batch(vec![command]).remove(0);
+    }
+
+    fn handle_batch(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+        let mut statuses = Vec::with_capacity(commands.len());
+        let mut writes = Vec::new();
+        for command in commands {
+            if !command.is_read() {
+                writes.push(command);
+                continue;
+            }
+            if !writes.is_empty() {
+                statuses.extend(self.replicate(std::mem::take(&mut writes)));
+            }
+            statuses.push(self.inner.handle(command));
+        }
+        if !writes.is_empty() {
+            statuses.extend(self.replicate(writes));
+        }
+        return statuses;
+    }
+
+    fn command_type(&self, command: &StorageCommand) -> &'static str {
+        return command.command_type();
+    }
+}
+
+impl Drop for ReplicatingHandler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl LeaderState {
+    fn lock(&self) -> MutexGuard<'_, LeaderLog> {
+        return self.log.lock().unwrap();
+    }
+}
+
+impl LeaderLog {
+    fn acknowledgements(&self, index: u64) -> usize {
+        return 1 + self.match_indices.iter().filter(|match_index| **match_index >= index).count();
+    }
+
+    fn has_news_for(&self, next_index: u64, sent_commit_index: u64) -> bool {
+        return self.entries.len() as u64 >= next_index || self.commit_index > sent_commit_index;
+    }
+}
+
+impl Replicator {
+    fn run(mut self) {
+        loop {
+            let request = {
+                let mut log = self.state.lock();
+                while !log.stopped && !log.has_news_for(self.next_index, self.sent_commit_index) {
+                    log = self.state.changed.wait(log).unwrap();
+                }
+                if log.stopped {
+                    return;
+                }
+                let from = (self.next_index - 1) as usize;
+                let until = log.entries.len().min(from + self.config.max_entries_per_request());
+                AppendEntriesRequest {
+                    prev_index: self.next_index - 1,
+                    entries: log.entries[from..until].to_vec(),
+                    commit_index: log.commit_index,
+                }
+            };
+
+            let commit_index = request.commit_index;
+            match self.transport.append_entries(request) {
+                //a follower may report entries the leader never had, so neither index may point past the leader's log
+                Ok(response) if response.success => {
+                    let mut log = self.state.lock();
+                    let match_index = response.last_index.min(log.entries.len() as u64);
+                    self.next_index = match_index + 1;
+                    self.sent_commit_index = commit_index;
+                    log.match_indices[self.follower] = match_index;
+                    self.state.changed.notify_all();
+                }
+                Ok(response) => {
+                    let log = self.state.lock();
+                    self.next_index = response.last_index.min(log.entries.len() as u64) + 1;
+                }
+                Err(_) => {
+                    let log = self.state.lock();
+                    if log.stopped {
+                        return;
+                    }
+                    let _ = self.state.changed.wait_timeout(log, self.config.retry_interval());
+                }
+            }
+        }
+    }
+}
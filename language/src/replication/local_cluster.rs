@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::replication::follower::Follower;
+use crate::replication::leader::ReplicatingHandler;
+use crate::replication::replication_config::ReplicationConfig;
+use crate::replication::transport::{InMemoryTransport, ReplicationTransport};
+use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Status, Storage, StorageCommand};
+
+//a leader queue and its followers wired together with in-memory transports, all inside one process
+pub struct LocalCluster {
+    leader: SingularUpdateQueue<StorageCommand, Status>,
+    leader_handler: Arc<ReplicatingHandler>,
+    leader_storage: Storage,
+    followers: Vec<LocalFollower>,
+}
+
+struct LocalFollower {
+    follower: Arc<Follower>,
+    storage: Storage,
+    transport: Arc<InMemoryTransport>,
+}
+
+impl LocalCluster {
+    pub fn start(followers: usize, config: ReplicationConfig) -> LocalCluster {
+        let followers: Vec<LocalFollower> = (0..followers)
+            .map(|_| {
+                let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+                let follower = Arc::new(Follower::new(Arc::new(InMemoryStorageHandler::new(storage.clone()))));
+                let transport = Arc::new(InMemoryTransport::new(follower.clone()));
+                return LocalFollower { follower, storage, transport };
+            })
+            .collect();
+
+        let transports: Vec<Arc<dyn ReplicationTransport>> = followers
+            .iter()
+            .map(|follower| follower.transport.clone() as Arc<dyn ReplicationTransport>)
+            .collect();
+        let leader_storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let leader_handler = Arc::new(ReplicatingHandler::new(
+            Arc::new(InMemoryStorageHandler::new(leader_storage.clone())),
+            transports,
+            config,
+        ));
+        let leader = SingularUpdateQueue::init(leader_handler.clone(), Executor::Thread);
+        return LocalCluster { leader, leader_handler, leader_storage, followers };
+    }
+
+    pub fn leader(&self) -> &SingularUpdateQueue<StorageCommand, Status> {
+        return &self.leader;
+    }
+
+    pub fn leader_handler(&self) -> &ReplicatingHandler {
+        return &self.leader_handler;
+    }
+
+    pub fn leader_storage(&self) -> &Storage {
+        return &self.leader_storage;
+    }
+
+    pub fn follower(&self, follower: usize) -> &Follower {
+        return &self.followers[follower].follower;
+    }
+
+    pub fn follower_storage(&self, follower: usize) -> &Storage {
+        return &self.followers[follower].storage;
+    }
+
+    pub fn disconnect(&self, follower: usize) {
+        self.followers[follower].transport.disconnect();
+    }
+
+    pub fn reconnect(&self, follower: usize) {
+        self.followers[follower].transport.reconnect();
+    }
+
+    //followers learn about the commit index with the next request, so they apply asynchronously
+    pub fn await_applied(&self, follower: usize, index: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while self.follower(follower).last_applied() < index {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+        return true;
+    }
+}
+
+impl Drop for LocalCluster {
+    fn drop(&mut self) {
+        self.leader_handler.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replication::append_entries::AppendEntriesRequest;
+    use crate::singular_update_queue::queue_error::QueueError;
+    use crate::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+    use crate::singular_update_queue::write_ahead_log::LogEntry;
+
+    use super::*;
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    #[test]
+    fn test_replicate_to_all_followers() {
+        let cluster = LocalCluster::start(2, ReplicationConfig::new(3));
+
+        assert_eq!(Ok(Status::Ok), cluster.leader().execute(put("key1", "value1")).wait());
+        assert_eq!(Ok(Status::Ok), cluster.leader().execute(StorageCommand::Delete { key: String::from("key1") }).wait());
+        assert_eq!(Ok(Status::Ok), cluster.leader().execute(put("key2", "value2")).wait());
+
+        assert_eq!(3, cluster.leader_handler().commit_index());
+        for follower in 0..2 {
+            assert_eq!(true, cluster.await_applied(follower, 3, Duration::from_secs(5)));
+            let storage = cluster.follower_storage(follower).read().unwrap();
+            assert_eq!(None, storage.get("key1"));
+            assert_eq!("value2", storage.get("key2").unwrap());
+        }
+        assert_eq!("value2", cluster.leader_storage().read().unwrap().get("key2").unwrap());
+    }
+
+    #[test]
+    fn test_read_on_the_leader_without_replicating() {
+        let cluster = LocalCluster::start(2, ReplicationConfig::new(3));
+
+        let _ticket = cluster.leader().execute(put("key1", "value1"));
+        let status = cluster.leader().execute(StorageCommand::Get { key: String::from("key1") }).wait();
+
+        assert_eq!(Ok(Status::Value(Some(String::from("value1")))), status);
+        assert_eq!(1, cluster.leader_handler().commit_index());
+        assert_eq!(1, cluster.follower(0).last_index());
+    }
+
+    #[test]
+    fn test_replicate_a_transaction() {
+        let cluster = LocalCluster::start(2, ReplicationConfig::new(3));
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::VersionMatches { key: String::from("key1"), version: 0 })
+            .with_operation(Operation::Put { key: String::from("key1"), value: String::from("value1") })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value2") });
+
+        let status = cluster.leader().execute(StorageCommand::Transaction(transaction.clone())).wait();
+        assert_eq!(Ok(Status::Committed(vec![Status::Ok, Status::Ok])), status);
+
+        let status = cluster.leader().execute(StorageCommand::Transaction(transaction)).wait();
+        assert_eq!(Ok(Status::Failed(QueueError::PreconditionFailed(0))), status);
+
+        for follower in 0..2 {
+            assert_eq!(true, cluster.await_applied(follower, 2, Duration::from_secs(5)));
+            assert_eq!(2, cluster.follower_storage(follower).read().unwrap().len());
+        }
+    }
+
+    #[test]
+    fn test_respond_once_a_majority_acknowledges() {
+        let cluster = LocalCluster::start(2, ReplicationConfig::new(2));
+        cluster.disconnect(1);
+
+        assert_eq!(Ok(Status::Ok), cluster.leader().execute(put("key1", "value1")).wait());
+
+        assert_eq!(true, cluster.await_applied(0, 1, Duration::from_secs(5)));
+        assert_eq!(0, cluster.follower(1).last_index());
+    }
+
+    #[test]
+    fn test_fail_without_a_quorum() {
+        let config = ReplicationConfig::new(3).with_ack_timeout(Duration::from_millis(50));
+        let cluster = LocalCluster::start(2, config);
+        cluster.disconnect(1);
+
+        let status = cluster.leader().execute(put("key1", "value1")).wait();
+
+        assert_eq!(Ok(Status::Failed(QueueError::Timeout)), status);
+        assert_eq!(None, cluster.leader_storage().read().unwrap().get("key1"));
+    }
+
+    #[test]
+    fn test_catch_up_a_reconnected_follower() {
+        let config = ReplicationConfig::new(2).with_retry_interval(Duration::from_millis(5));
+        let cluster = LocalCluster::start(2, config);
+        cluster.disconnect(1);
+
+        for index in 0..10 {
+            assert_eq!(Ok(Status::Ok), cluster.leader().execute(put(&format!("key{}", index), "value")).wait());
+        }
+        cluster.reconnect(1);
+
+        assert_eq!(true, cluster.await_applied(1, 10, Duration::from_secs(5)));
+        assert_eq!(10, cluster.follower_storage(1).read().unwrap().len());
+    }
+
+    #[test]
+    fn test_converge_a_follower_ahead_of_the_leader() {
+        let config = ReplicationConfig::new(3).with_retry_interval(Duration::from_millis(5));
+        let cluster = LocalCluster::start(2, config);
+        let stale = (1..=5).map(|index| LogEntry { index, command: put(&format!("stale{}", index), "value") }).collect();
+        cluster.follower(1).append_entries(AppendEntriesRequest { prev_index: 0, entries: stale, commit_index: 0 });
+
+        assert_eq!(Ok(Status::Ok), cluster.leader().execute(put("key1", "value1")).wait());
+        assert_eq!(Ok(Status::Ok), cluster.leader().execute(put("key2", "value2")).wait());
+
+        assert_eq!(vec![2, 2], cluster.leader_handler().match_indices());
+        assert_eq!(true, cluster.await_applied(1, 2, Duration::from_secs(5)));
+        assert_eq!(2, cluster.follower(1).last_index());
+        let storage = cluster.follower_storage(1).read().unwrap();
+        assert_eq!(2, storage.len());
+        assert_eq!("value2", storage.get("key2").unwrap());
+    }
+}
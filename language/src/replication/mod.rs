@@ -0,0 +1,7 @@
+pub mod append_entries;
+pub mod replication_error;
+pub mod transport;
+pub mod replication_config;
+pub mod follower;
+pub mod leader;
+pub mod local_cluster;
\ No newline at end of file
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ReplicationConfig {
+    quorum: usize,
+    ack_timeout: Duration,
+    retry_interval: Duration,
+    max_entries_per_request: usize,
+}
+
+impl ReplicationConfig {
+    //quorum counts the leader, a quorum of 1 acknowledges as soon as the leader has the entry
+    pub fn new(quorum: usize) -> ReplicationConfig {
+        assert!(quorum > 0, "quorum must be greater than zero");
+        return ReplicationConfig {
+            quorum,
+            ack_timeout: Duration::from_secs(1),
+            retry_interval: Duration::from_millis(20),
+            max_entries_per_request: 128,
+        };
+    }
+
+    pub fn with_ack_timeout(mut self, ack_timeout: Duration) -> ReplicationConfig {
+        self.ack_timeout = ack_timeout;
+        return self;
+    }
+
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> ReplicationConfig {
+        self.retry_interval = retry_interval;
+        return self;
+    }
+
+    pub fn with_max_entries_per_request(mut self, max_entries_per_request: usize) -> ReplicationConfig {
+        assert!(max_entries_per_request > 0, "max entries per request must be greater than zero");
+        self.max_entries_per_request = max_entries_per_request;
+        return self;
+    }
+
+    pub fn quorum(&self) -> usize {
+        return self.quorum;
+    }
+
+    pub fn ack_timeout(&self) -> Duration {
+        return self.ack_timeout;
+    }
+
+    pub fn retry_interval(&self) -> Duration {
+        return self.retry_interval;
+    }
+
+    pub fn max_entries_per_request(&self) -> usize {
+        return self.max_entries_per_request;
+    }
+}
@@ -0,0 +1,16 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ReplicationError {
+    Unreachable(String),
+}
+
+impl Display for ReplicationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            ReplicationError::Unreachable(reason) => write!(f, "follower is unreachable: {}", reason),
+        };
+    }
+}
+
+impl std::error::Error for ReplicationError {}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::replication::append_entries::{AppendEntriesRequest, AppendEntriesResponse};
+use crate::replication::follower::Follower;
+use crate::replication::replication_error::ReplicationError;
+
+//one transport per follower, called from the follower's replicator thread so implementations may block
+pub trait ReplicationTransport: Send + Sync {
+    fn append_entries(&self, request: AppendEntriesRequest) -> Result<AppendEntriesResponse, ReplicationError>;
+}
+
+pub struct InMemoryTransport {
+    follower: Arc<Follower>,
+    connected: AtomicBool,
+}
+
+impl InMemoryTransport {
+    pub fn new(follower: Arc<Follower>) -> InMemoryTransport {
+        return InMemoryTransport { follower, connected: AtomicBool::new(true) };
+    }
+
+    pub fn disconnect(&self) {
+        self.connected.store(false, Ordering::SeqCst);
+    }
+
+    pub fn reconnect(&self) {
+        self.connected.store(true, Ordering::SeqCst);
+    }
+}
+
+impl ReplicationTransport for InMemoryTransport {
+    fn append_entries(&self, request: AppendEntriesRequest) -> Result<AppendEntriesResponse, ReplicationError> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(ReplicationError::Unreachable(String::from("disconnected")));
+        }
+        return Ok(self.follower.append_entries(request));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+
+    use crate::singular_update_queue::storage_command::InMemoryStorageHandler;
+
+    use super::*;
+
+    #[test]
+    fn test_disconnected_transport() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let follower = Arc::new(Follower::new(Arc::new(InMemoryStorageHandler::new(storage))));
+        let transport = InMemoryTransport::new(follower);
+
+        transport.disconnect();
+        let request = AppendEntriesRequest { prev_index: 0, entries: Vec::new(), commit_index: 0 };
+        assert_eq!(true, transport.append_entries(request.clone()).is_err());
+
+        transport.reconnect();
+        assert_eq!(Ok(AppendEntriesResponse { success: true, last_index: 0 }), transport.append_entries(request));
+    }
+}
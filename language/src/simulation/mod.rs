@@ -0,0 +1,4 @@
+pub mod seeded_random;
+pub mod simulated_network;
+pub mod raft_simulation;
+pub mod queue_simulation;
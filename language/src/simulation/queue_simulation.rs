@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::runtime::Builder;
+use tokio::task;
+
+use crate::simulation::seeded_random::SeededRandom;
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::queue_config::QueueConfig;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+use crate::singular_update_queue::storage_command::{Status, Storage, StorageCommand};
+
+//builds the handler of a fresh incarnation of the queue and the storage it serves, after a crash it has to recover the state from its own durable medium
+pub type HandlerFactory = Box<dyn Fn() -> (Arc<dyn CommandHandler<StorageCommand, Status>>, Storage)>;
+
+//runs the real queue and its handlers on a single threaded runtime where clients interleave by seeded yields instead of sleeps,
+//the queue is crashed by dropping the runtime after a seeded number of steps and every incarnation is checked against a model
+pub struct QueueSimulation {
+    seed: u64,
+    random: SeededRandom,
+    config: QueueConfig,
+    factory: HandlerFactory,
+    clients: usize,
+    keys: usize,
+    model: HashMap<String, String>,
+    rounds: usize,
+    acknowledged: usize,
+    trace: Vec<String>,
+}
+
+type Recorded = Arc<Mutex<Vec<(StorageCommand, Status)>>>;
+
+//records every command with its response in the order the worker applied them
+struct RecordingHandler {
+    inner: Arc<dyn CommandHandler<StorageCommand, Status>>,
+    recorded: Recorded,
+}
+
+impl CommandHandler<StorageCommand, Status> for RecordingHandler {
+    fn handle(&self, command: StorageCommand) -> Status {
+        let response = self.inner.handle(command.clone());
+        self.recorded.lock().unwrap().push((command, response.clone()));
+        return response;
+    }
+
+    fn handle_batch(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+        let responses = self.inner.handle_batch(commands.clone());
+        self.recorded.lock().unwrap().extend(commands.into_iter().zip(responses.iter().cloned()));
+        return responses;
+    }
+
+    fn command_type(&self, command: &StorageCommand) -> &'static str {
+        return self.inner.command_type(command);
+    }
+}
+
+impl QueueSimulation {
+    pub fn new(seed: u64, config: QueueConfig, factory: HandlerFactory) -> QueueSimulation {
+        //there is no simulated clock, a batch delay would make the batches depend on the wall clock
+        assert!(config.max_batch_delay().is_zero(), "the simulation does not support a batch delay");
+        return QueueSimulation {
+            seed,
+            random: SeededRandom::new(seed),
+            config,
+            factory,
+            clients: 4,
+            keys: 4,
+            model: HashMap::new(),
+            rounds: 0,
+            acknowledged: 0,
+            trace: Vec::new(),
+        };
+    }
+
+    pub fn with_clients(mut self, clients: usize) -> QueueSimulation {
+        assert!(clients > 0, "clients must be greater than zero");
+        self.clients = clients;
+        return self;
+    }
+
+    pub fn with_keys(mut self, keys: usize) -> QueueSimulation {
+        assert!(keys > 0, "keys must be greater than zero");
+        self.keys = keys;
+        return self;
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    //the commands applied by every incarnation, in order
+    pub fn trace(&self) -> &[String] {
+        return &self.trace;
+    }
+
+    pub fn acknowledged(&self) -> usize {
+        return self.acknowledged;
+    }
+
+    //starts a new incarnation, checks it recovered every applied write and lets the clients run, crash_after stops it after that many steps
+    pub fn run_round(&mut self, commands_per_client: usize, crash_after: Option<u64>) -> Result<(), String> {
+        let round = self.rounds;
+        self.rounds += 1;
+        let (handler, storage) = (self.factory)();
+        if *storage.read().unwrap() != self.model {
+            return Err(self.violation(round, String::from("the recovered storage differs from the applied writes")));
+        }
+
+        let plans: Vec<Vec<(u64, StorageCommand)>> = (0..self.clients)
+            .map(|client| (0..commands_per_client).map(|sequence| (self.random.between(0, 3), self.command(client, sequence))).collect())
+            .collect();
+        let recorded: Recorded = Arc::new(Mutex::new(Vec::new()));
+        let acknowledged = Arc::new(Mutex::new(0));
+        let handler = Arc::new(RecordingHandler { inner: handler, recorded: recorded.clone() });
+        let config = self.config;
+
+        let runtime = Builder::new_current_thread().build().unwrap();
+        let cloned_acknowledged = acknowledged.clone();
+        runtime.block_on(async move {
+            let singular_update_queue = SingularUpdateQueue::init_with_config(handler, Executor::Task, config);
+            let handles: Vec<_> = plans
+                .into_iter()
+                .map(|plan| {
+                    let singular_update_queue = singular_update_queue.clone();
+                    let acknowledged = cloned_acknowledged.clone();
+                    return task::spawn(async move {
+                        for (yields, command) in plan {
+                            for _ in 0..yields {
+                                task::yield_now().await;
+                            }
+                            if singular_update_queue.execute_async(command).await.is_ok() {
+                                *acknowledged.lock().unwrap() += 1;
+                            }
+                        }
+                    });
+                })
+                .collect();
+            match crash_after {
+                Some(steps) => {
+                    for _ in 0..steps {
+                        task::yield_now().await;
+                    }
+                }
+                None => {
+                    for handle in handles {
+                        let _ = handle.await;
+                    }
+                }
+            }
+        });
+        //dropping the runtime drops the worker and the clients wherever they are, like a crash of the process
+        drop(runtime);
+
+        self.acknowledged += *acknowledged.lock().unwrap();
+        let recorded = std::mem::take(&mut *recorded.lock().unwrap());
+        for (command, response) in recorded {
+            self.trace.push(format!("{} {}", round, describe(&command)));
+            let expected = self.apply(command.clone());
+            if response != expected {
+                return Err(self.violation(round, format!("{} responded {:?} instead of {:?}", describe(&command), response, expected)));
+            }
+        }
+        return Ok(());
+    }
+
+    //crashes a seeded number of times, then lets a last incarnation finish and recover once more
+    pub fn run_with_crashes(&mut self, crashes: usize, commands_per_client: usize) -> Result<(), String> {
+        for _ in 0..crashes {
+            let steps = self.random.between(1, (commands_per_client * self.clients) as u64);
+            self.run_round(commands_per_client, Some(steps))?;
+        }
+        self.run_round(commands_per_client, None)?;
+        return self.run_round(0, None);
+    }
+
+    fn command(&mut self, client: usize, sequence: usize) -> StorageCommand {
+        let key = format!("key{}", self.random.between(1, self.keys as u64));
+        return match self.random.between(0, 9) {
+            0..=5 => StorageCommand::Put { key, value: format!("client{}-{}-{}", client, self.rounds, sequence) },
+            6..=7 => StorageCommand::Delete { key },
+            _ => StorageCommand::Get { key },
+        };
+    }
+
+    fn apply(&mut self, command: StorageCommand) -> Status {
+        return match command {
+            StorageCommand::Put { key, value } => {
+                self.model.insert(key, value);
+                Status::Ok
+            }
+            StorageCommand::Delete { key } => {
+                self.model.remove(&key);
+                Status::Ok
+            }
+            StorageCommand::Get { key } => Status::Value(self.model.get(&key).cloned()),
+            StorageCommand::Transaction(_) => Status::Failed(QueueError::HandlerFailed(String::from("the simulation does not model transactions"))),
+        };
+    }
+
+    fn violation(&self, round: usize, description: String) -> String {
+        return format!("seed {} round {}: {}", self.seed, round, description);
+    }
+}
+
+fn describe(command: &StorageCommand) -> String {
+    return match command {
+        StorageCommand::Put { key, value } => format!("put {} {}", key, value),
+        StorageCommand::Delete { key } => format!("delete {}", key),
+        StorageCommand::Get { key } => format!("get {}", key),
+        StorageCommand::Transaction(transaction) => format!("transaction {:?}", transaction.keys()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::RwLock;
+
+    use crate::singular_update_queue::storage_command::InMemoryStorageHandler;
+    use crate::singular_update_queue::write_ahead_log::{FsyncPolicy, WriteAheadLog};
+    use crate::singular_update_queue::write_ahead_log_handler::WriteAheadLogHandler;
+
+    use super::*;
+
+    fn in_memory() -> HandlerFactory {
+        return Box::new(|| {
+            let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+            return (Arc::new(InMemoryStorageHandler::new(storage.clone())), storage);
+        });
+    }
+
+    fn write_ahead_logged(directory: std::path::PathBuf) -> HandlerFactory {
+        return Box::new(move || {
+            let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+            let log = WriteAheadLog::open(&directory, 1024, FsyncPolicy::Never).unwrap();
+            let handler = WriteAheadLogHandler::recover(log, Arc::new(InMemoryStorageHandler::new(storage.clone()))).unwrap();
+            return (Arc::new(handler), storage);
+        });
+    }
+
+    #[test]
+    fn test_apply_every_command_without_crashes() {
+        let mut simulation = QueueSimulation::new(1, QueueConfig::default(), in_memory());
+
+        assert_eq!(Ok(()), simulation.run_round(20, None));
+        assert_eq!(80, simulation.acknowledged());
+        assert_eq!(80, simulation.trace().len());
+    }
+
+    #[test]
+    fn test_the_same_seed_replays_the_same_order() {
+        let config = QueueConfig::default().with_capacity(2).with_max_batch_size(4);
+        let mut first = QueueSimulation::new(7, config, in_memory()).with_clients(6);
+        let mut second = QueueSimulation::new(7, config, in_memory()).with_clients(6);
+        let mut other = QueueSimulation::new(8, config, in_memory()).with_clients(6);
+
+        assert_eq!(Ok(()), first.run_round(30, None));
+        assert_eq!(Ok(()), second.run_round(30, None));
+        assert_eq!(Ok(()), other.run_round(30, None));
+
+        assert_eq!(first.trace(), second.trace());
+        assert_eq!(false, first.trace() == other.trace());
+    }
+
+    #[test]
+    fn test_recover_the_applied_writes_from_the_log() {
+        for seed in 0..10 {
+            let directory = tempfile::tempdir().unwrap();
+            let mut simulation = QueueSimulation::new(seed, QueueConfig::default().with_max_batch_size(8), write_ahead_logged(directory.path().to_path_buf()));
+            assert_eq!(Ok(()), simulation.run_with_crashes(3, 10));
+        }
+    }
+
+    #[test]
+    fn test_detect_a_handler_losing_writes_on_a_crash() {
+        let mut simulation = QueueSimulation::new(3, QueueConfig::default(), in_memory());
+
+        let result = simulation.run_with_crashes(1, 10);
+        assert_eq!(true, result.unwrap_err().contains("the recovered storage differs"));
+    }
+}
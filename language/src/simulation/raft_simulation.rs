@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::raft::raft_config::RaftConfig;
+use crate::raft::raft_message::{NodeId, RaftMessage};
+use crate::raft::raft_node::{RaftNode, RaftStatus, Role};
+use crate::simulation::seeded_random::SeededRandom;
+use crate::simulation::simulated_network::SimulatedNetwork;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Storage, StorageCommand};
+
+//raft nodes driven tick by tick on one thread, the network, the faults and the election timeouts all draw from the seed,
+//so a run which breaks an invariant replays exactly from its seed, node ids start at one
+pub struct RaftSimulation {
+    seed: u64,
+    now: u64,
+    random: SeededRandom,
+    network: SimulatedNetwork,
+    nodes: Vec<SimulatedNode>,
+    leaders: HashMap<u64, NodeId>,
+    committed: HashMap<u64, u64>,
+    trace: Vec<String>,
+}
+
+struct SimulatedNode {
+    node: RaftNode,
+    storage: Storage,
+    crashed: bool,
+}
+
+//per tick probabilities of the faults injected by run_with_faults
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct FaultPlan {
+    pub crash: f64,
+    pub restart: f64,
+    pub partition: f64,
+    pub heal: f64,
+    pub propose: f64,
+}
+
+impl Default for FaultPlan {
+    fn default() -> Self {
+        return FaultPlan { crash: 0.01, restart: 0.05, partition: 0.01, heal: 0.05, propose: 0.2 };
+    }
+}
+
+impl RaftSimulation {
+    pub fn new(seed: u64, nodes: usize, config: RaftConfig, network: SimulatedNetwork) -> RaftSimulation {
+        assert!(nodes > 0, "a simulation needs at least one node");
+        let config = config.with_seed(seed);
+        let ids: Vec<NodeId> = (1..=nodes as NodeId).collect();
+        let nodes = ids
+            .iter()
+            .map(|id| {
+                let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+                let state_machine = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+                return SimulatedNode { node: RaftNode::new(*id, ids.clone(), config, state_machine), storage, crashed: false };
+            })
+            .collect();
+        return RaftSimulation {
+            seed,
+            now: 0,
+            random: SeededRandom::new(seed),
+            network,
+            nodes,
+            leaders: HashMap::new(),
+            committed: HashMap::new(),
+            trace: Vec::new(),
+        };
+    }
+
+    pub fn seed(&self) -> u64 {
+        return self.seed;
+    }
+
+    pub fn now(&self) -> u64 {
+        return self.now;
+    }
+
+    //every delivery and fault in order, two runs of the same seed produce the same trace
+    pub fn trace(&self) -> &[String] {
+        return &self.trace;
+    }
+
+    pub fn status(&self, id: NodeId) -> RaftStatus {
+        return self.simulated(id).node.status();
+    }
+
+    pub fn storage(&self, id: NodeId) -> &Storage {
+        return &self.simulated(id).storage;
+    }
+
+    //the live leader of the highest term, a deposed leader may still believe it leads an older term
+    pub fn leader(&self) -> Option<NodeId> {
+        return self.nodes
+            .iter()
+            .filter(|simulated| !simulated.crashed && simulated.node.status().role == Role::Leader)
+            .max_by_key(|simulated| simulated.node.status().term)
+            .map(|simulated| simulated.node.id());
+    }
+
+    pub fn propose(&mut self, command: StorageCommand) -> Result<(u64, u64), QueueError> {
+        let leader = self.leader().ok_or(QueueError::NotLeader)?;
+        let proposed = self.simulated_mut(leader).node.propose(command);
+        if let Ok((index, term)) = proposed {
+            self.record(format!("{} propose on {} at index {} term {}", self.now, leader, index, term));
+        }
+        return proposed;
+    }
+
+    pub fn crash(&mut self, id: NodeId) {
+        let simulated = self.simulated_mut(id);
+        simulated.crashed = true;
+        simulated.node.take_messages();
+        self.record(format!("{} crash {}", self.now, id));
+    }
+
+    pub fn restart(&mut self, id: NodeId) {
+        let simulated = self.simulated_mut(id);
+        simulated.crashed = false;
+        simulated.node.restart();
+        self.record(format!("{} restart {}", self.now, id));
+    }
+
+    pub fn partition(&mut self, groups: &[Vec<NodeId>]) {
+        self.network.partition(groups);
+        self.record(format!("{} partition {:?}", self.now, groups));
+    }
+
+    pub fn heal(&mut self) {
+        self.network.heal();
+        self.record(format!("{} heal", self.now));
+    }
+
+    //delivers the messages due, ticks the live nodes and checks the invariants, an Err describes the first violation
+    pub fn tick(&mut self) -> Result<(), String> {
+        self.now += 1;
+        for in_flight in self.network.due(self.now) {
+            let target = self.simulated_mut(in_flight.to);
+            if target.crashed {
+                continue;
+            }
+            target.node.step(in_flight.from, in_flight.message.clone());
+            self.record(format!("{} deliver {} -> {} {}", self.now, in_flight.from, in_flight.to, describe(&in_flight.message)));
+        }
+        for simulated in self.nodes.iter_mut().filter(|simulated| !simulated.crashed) {
+            simulated.node.tick();
+        }
+        return self.flush();
+    }
+
+    pub fn run(&mut self, ticks: u64) -> Result<(), String> {
+        for _ in 0..ticks {
+            self.tick()?;
+        }
+        return Ok(());
+    }
+
+    //injects seeded faults and proposals, then heals the network, restarts every node and lets the cluster settle
+    pub fn run_with_faults(&mut self, ticks: u64, plan: FaultPlan, settle_ticks: u64) -> Result<(), String> {
+        let ids: Vec<NodeId> = self.nodes.iter().map(|simulated| simulated.node.id()).collect();
+        let mut proposals = 0;
+        for _ in 0..ticks {
+            if self.random.chance(plan.crash) {
+                let id = *self.random.pick(&ids);
+                if !self.simulated(id).crashed {
+                    self.crash(id);
+                }
+            }
+            if self.random.chance(plan.restart) {
+                let id = *self.random.pick(&ids);
+                if self.simulated(id).crashed {
+                    self.restart(id);
+                }
+            }
+            if ids.len() > 1 && self.random.chance(plan.partition) {
+                let split = self.random.between(1, ids.len() as u64 - 1) as usize;
+                let mut shuffled = ids.clone();
+                for position in (1..shuffled.len()).rev() {
+                    shuffled.swap(position, self.random.between(0, position as u64) as usize);
+                }
+                self.partition(&[shuffled[..split].to_vec(), shuffled[split..].to_vec()]);
+            }
+            if self.network.is_partitioned() && self.random.chance(plan.heal) {
+                self.heal();
+            }
+            if self.random.chance(plan.propose) {
+                proposals += 1;
+                let _ = self.propose(StorageCommand::Put { key: format!("key{}", proposals % 8), value: format!("value{}", proposals) });
+            }
+            self.tick()?;
+        }
+
+        self.heal();
+        for id in ids {
+            if self.simulated(id).crashed {
+                self.restart(id);
+            }
+        }
+        return self.run(settle_ticks);
+    }
+
+    //every node applied the same entries and holds the same storage
+    pub fn converged(&self) -> bool {
+        let first = &self.nodes[0];
+        return self.nodes.iter().all(|simulated| {
+            simulated.node.status().last_applied == first.node.status().last_applied
+                && *simulated.storage.read().unwrap() == *first.storage.read().unwrap()
+        });
+    }
+
+    fn flush(&mut self) -> Result<(), String> {
+        for position in 0..self.nodes.len() {
+            let from = self.nodes[position].node.id();
+            let messages = self.nodes[position].node.take_messages();
+            for (to, message) in messages {
+                self.network.send(self.now, from, to, message, &mut self.random);
+            }
+            let applied = self.nodes[position].node.take_applied();
+            for entry in applied {
+                let term = *self.committed.entry(entry.index).or_insert(entry.term);
+                if term != entry.term {
+                    return Err(self.violation(format!(
+                        "node {} applied index {} of term {} where another node applied term {}", from, entry.index, entry.term, term
+                    )));
+                }
+            }
+            let status = self.nodes[position].node.status();
+            if status.role == Role::Leader {
+                let leader = *self.leaders.entry(status.term).or_insert(from);
+                if leader != from {
+                    return Err(self.violation(format!("nodes {} and {} both lead term {}", leader, from, status.term)));
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    fn violation(&self, description: String) -> String {
+        return format!("seed {} tick {}: {}", self.seed, self.now, description);
+    }
+
+    fn record(&mut self, event: String) {
+        self.trace.push(event);
+    }
+
+    fn simulated(&self, id: NodeId) -> &SimulatedNode {
+        return &self.nodes[(id - 1) as usize];
+    }
+
+    fn simulated_mut(&mut self, id: NodeId) -> &mut SimulatedNode {
+        return &mut self.nodes[(id - 1) as usize];
+    }
+}
+
+fn describe(message: &RaftMessage) -> String {
+    return match message {
+        RaftMessage::RequestVote { term, .. } => format!("request vote term {}", term),
+        RaftMessage::RequestVoteResponse { term, vote_granted } => format!("vote term {} granted {}", term, vote_granted),
+        RaftMessage::AppendEntries { term, prev_log_index, entries, .. } => {
+            format!("append term {} after {} entries {}", term, prev_log_index, entries.len())
+        }
+        RaftMessage::AppendEntriesResponse { term, success, match_index } => {
+            format!("append response term {} success {} match {}", term, success, match_index)
+        }
+        RaftMessage::InstallSnapshot { term, last_included_index, .. } => format!("snapshot term {} up to {}", term, last_included_index),
+        RaftMessage::InstallSnapshotResponse { term, match_index } => format!("snapshot response term {} match {}", term, match_index),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn simulation(seed: u64, loss: f64) -> RaftSimulation {
+        let config = RaftConfig::default().with_snapshot_threshold(16);
+        return RaftSimulation::new(seed, 5, config, SimulatedNetwork::new(1, 3, loss));
+    }
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    #[test]
+    fn test_elect_a_leader_and_replicate() {
+        let mut simulation = simulation(1, 0.0);
+        assert_eq!(Ok(()), simulation.run(100));
+
+        assert_eq!(true, simulation.leader().is_some());
+        assert_eq!(true, simulation.propose(put("key1", "value1")).is_ok());
+        assert_eq!(Ok(()), simulation.run(50));
+
+        assert_eq!(true, simulation.converged());
+        assert_eq!("value1", simulation.storage(3).read().unwrap().get("key1").unwrap());
+    }
+
+    #[test]
+    fn test_the_same_seed_replays_the_same_run() {
+        let mut first = simulation(7, 0.1);
+        let mut second = simulation(7, 0.1);
+        let mut other = simulation(8, 0.1);
+
+        assert_eq!(Ok(()), first.run_with_faults(300, FaultPlan::default(), 200));
+        assert_eq!(Ok(()), second.run_with_faults(300, FaultPlan::default(), 200));
+        assert_eq!(Ok(()), other.run_with_faults(300, FaultPlan::default(), 200));
+
+        assert_eq!(first.trace(), second.trace());
+        assert_eq!(false, first.trace() == other.trace());
+    }
+
+    #[test]
+    fn test_a_minority_partition_does_not_commit() {
+        let mut simulation = simulation(3, 0.0);
+        assert_eq!(Ok(()), simulation.run(100));
+        let leader = simulation.leader().unwrap();
+        let others: Vec<NodeId> = (1..=5).filter(|id| *id != leader).collect();
+
+        simulation.partition(&[vec![leader, others[0]], others[1..].to_vec()]);
+        let (index, _) = simulation.propose(put("lost", "value")).unwrap();
+        assert_eq!(Ok(()), simulation.run(100));
+        assert_eq!(true, simulation.status(leader).commit_index < index);
+
+        simulation.heal();
+        assert_eq!(Ok(()), simulation.run(100));
+        assert_eq!(true, simulation.converged());
+        assert_eq!(None, simulation.storage(leader).read().unwrap().get("lost"));
+    }
+
+    #[test]
+    fn test_a_restarted_node_catches_up() {
+        let mut simulation = simulation(5, 0.0);
+        assert_eq!(Ok(()), simulation.run(100));
+        let follower = (1..=5).find(|id| Some(*id) != simulation.leader()).unwrap();
+
+        simulation.crash(follower);
+        for index in 0..20 {
+            simulation.propose(put(&format!("key{}", index), "value")).unwrap();
+            assert_eq!(Ok(()), simulation.run(2));
+        }
+        simulation.restart(follower);
+        assert_eq!(Ok(()), simulation.run(100));
+
+        assert_eq!(true, simulation.converged());
+        assert_eq!(20, simulation.storage(follower).read().unwrap().len());
+    }
+
+    #[test]
+    fn test_keep_the_invariants_under_faults() {
+        for seed in 0..20 {
+            let mut simulation = simulation(seed, 0.05);
+            assert_eq!(Ok(()), simulation.run_with_faults(500, FaultPlan::default(), 300));
+            assert_eq!(true, simulation.converged(), "seed {} did not converge", seed);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//splitmix64, every decision of a simulation is drawn from one of these so a seed replays the same run
+#[derive(Debug, Clone)]
+pub struct SeededRandom {
+    state: u64,
+}
+
+impl SeededRandom {
+    pub fn new(seed: u64) -> SeededRandom {
+        return SeededRandom { state: seed };
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut value = self.state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        return value ^ (value >> 31);
+    }
+
+    //a value in [low, high]
+    pub fn between(&mut self, low: u64, high: u64) -> u64 {
+        assert!(low <= high, "low must not be greater than high");
+        return low + self.next_u64() % (high - low + 1);
+    }
+
+    pub fn chance(&mut self, probability: f64) -> bool {
+        return ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability;
+    }
+
+    pub fn pick<'a, T>(&mut self, values: &'a [T]) -> &'a T {
+        assert!(!values.is_empty(), "can not pick from no values");
+        return &values[self.next_u64() as usize % values.len()];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_sequence() {
+        let mut first = SeededRandom::new(7);
+        let mut second = SeededRandom::new(7);
+        let mut other = SeededRandom::new(8);
+
+        let sequence: Vec<u64> = (0..5).map(|_| first.next_u64()).collect();
+        assert_eq!(sequence, (0..5).map(|_| second.next_u64()).collect::<Vec<u64>>());
+        assert_eq!(false, sequence == (0..5).map(|_| other.next_u64()).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn test_stay_within_bounds() {
+        let mut random = SeededRandom::new(7);
+        for _ in 0..100 {
+            let value = random.between(3, 5);
+            assert_eq!(true, (3..=5).contains(&value));
+        }
+        assert_eq!(false, random.chance(0.0));
+        assert_eq!(true, random.chance(1.0));
+    }
+}
@@ -0,0 +1,171 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+use crate::raft::raft_message::{NodeId, RaftMessage};
+use crate::simulation::seeded_random::SeededRandom;
+
+//a message on the wire, messages due at the same tick are delivered in the order they were sent
+#[derive(Debug, Clone)]
+pub struct InFlight {
+    pub deliver_at: u64,
+    pub sequence: u64,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub message: RaftMessage,
+}
+
+//delays every message by a seeded number of ticks, loses some of them and drops the ones crossing a partition
+pub struct SimulatedNetwork {
+    min_delay: u64,
+    max_delay: u64,
+    loss: f64,
+    cut: HashSet<(NodeId, NodeId)>,
+    in_flight: BinaryHeap<InFlight>,
+    sent: u64,
+    dropped: u64,
+}
+
+impl PartialEq for InFlight {
+    fn eq(&self, other: &Self) -> bool {
+        return self.sequence == other.sequence;
+    }
+}
+
+impl Eq for InFlight {}
+
+impl PartialOrd for InFlight {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        return Some(self.cmp(other));
+    }
+}
+
+//reversed, so the binary heap pops the earliest message first
+impl Ord for InFlight {
+    fn cmp(&self, other: &Self) -> Ordering {
+        return (other.deliver_at, other.sequence).cmp(&(self.deliver_at, self.sequence));
+    }
+}
+
+impl SimulatedNetwork {
+    pub fn new(min_delay: u64, max_delay: u64, loss: f64) -> SimulatedNetwork {
+        assert!(min_delay > 0 && min_delay <= max_delay, "delays must be positive and ordered");
+        assert!((0.0..1.0).contains(&loss), "loss must be at least 0 and below 1");
+        return SimulatedNetwork {
+            min_delay,
+            max_delay,
+            loss,
+            cut: HashSet::new(),
+            in_flight: BinaryHeap::new(),
+            sent: 0,
+            dropped: 0,
+        };
+    }
+
+    pub fn send(&mut self, now: u64, from: NodeId, to: NodeId, message: RaftMessage, random: &mut SeededRandom) {
+        self.sent += 1;
+        if random.chance(self.loss) {
+            self.dropped += 1;
+            return;
+        }
+        let deliver_at = now + random.between(self.min_delay, self.max_delay);
+        self.in_flight.push(InFlight { deliver_at, sequence: self.sent, from, to, message });
+    }
+
+    //messages due by now, the ones crossing a partition at delivery time are dropped
+    pub fn due(&mut self, now: u64) -> Vec<InFlight> {
+        let mut due = Vec::new();
+        while self.in_flight.peek().is_some_and(|in_flight| in_flight.deliver_at <= now) {
+            let in_flight = self.in_flight.pop().unwrap();
+            if self.cut.contains(&(in_flight.from, in_flight.to)) {
+                self.dropped += 1;
+                continue;
+            }
+            due.push(in_flight);
+        }
+        return due;
+    }
+
+    //nodes only reach the nodes of their own group
+    pub fn partition(&mut self, groups: &[Vec<NodeId>]) {
+        self.cut.clear();
+        for (position, group) in groups.iter().enumerate() {
+            for (other_position, other_group) in groups.iter().enumerate() {
+                if position == other_position {
+                    continue;
+                }
+                for from in group {
+                    for to in other_group {
+                        self.cut.insert((*from, *to));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn heal(&mut self) {
+        self.cut.clear();
+    }
+
+    pub fn is_partitioned(&self) -> bool {
+        return !self.cut.is_empty();
+    }
+
+    pub fn dropped(&self) -> u64 {
+        return self.dropped;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn heartbeat() -> RaftMessage {
+        return RaftMessage::AppendEntries { term: 1, prev_log_index: 0, prev_log_term: 0, entries: Vec::new(), leader_commit: 0 };
+    }
+
+    #[test]
+    fn test_deliver_in_time_order() {
+        let mut network = SimulatedNetwork::new(1, 5, 0.0);
+        let mut random = SeededRandom::new(3);
+        for to in 2..6 {
+            network.send(0, 1, to, heartbeat(), &mut random);
+        }
+
+        let mut delivered = Vec::new();
+        for now in 0..=5 {
+            delivered.extend(network.due(now).into_iter().map(|in_flight| in_flight.deliver_at));
+        }
+        assert_eq!(4, delivered.len());
+        assert_eq!(true, delivered.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_drop_messages_across_a_partition() {
+        let mut network = SimulatedNetwork::new(1, 1, 0.0);
+        let mut random = SeededRandom::new(3);
+        network.partition(&[vec![1, 2], vec![3]]);
+        network.send(0, 1, 2, heartbeat(), &mut random);
+        network.send(0, 1, 3, heartbeat(), &mut random);
+
+        let due = network.due(1);
+        assert_eq!(1, due.len());
+        assert_eq!(2, due[0].to);
+        assert_eq!(1, network.dropped());
+
+        network.heal();
+        assert_eq!(false, network.is_partitioned());
+    }
+
+    #[test]
+    fn test_lose_messages() {
+        let mut network = SimulatedNetwork::new(1, 1, 0.5);
+        let mut random = SeededRandom::new(3);
+        for _ in 0..100 {
+            network.send(0, 1, 2, heartbeat(), &mut random);
+        }
+
+        let delivered = network.due(1).len() as u64;
+        assert_eq!(100, delivered + network.dropped());
+        assert_eq!(true, delivered > 20 && delivered < 80);
+    }
+}
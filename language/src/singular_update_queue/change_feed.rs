@@ -0,0 +1,302 @@
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use tokio_stream::Stream;
+
+use crate::singular_update_queue::queue_error::QueueError;
+
+//a mutation applied to the storage, a put carries the new value and a delete does not
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Change {
+    pub sequence: u64,
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+}
+
+//keeps the latest changes in memory, sequence numbers start at 1 and have no gaps
+pub struct ChangeFeed {
+    state: Mutex<FeedState>,
+    appended: Condvar,
+}
+
+struct FeedState {
+    changes: VecDeque<Change>,
+    next_sequence: u64,
+    retention: usize,
+    closed: bool,
+    //one slot per pending subscription, a subscription polled again before the next change replaces its own waker
+    wakers: HashMap<u64, Waker>,
+    next_subscription: u64,
+}
+
+//yields the changes from a sequence number onwards, a subscriber which falls behind the retention gets
+//QueueError::ChangeUnavailable once and the subscription ends
+pub struct Subscription {
+    id: u64,
+    feed: Arc<ChangeFeed>,
+    next_sequence: u64,
+    ended: bool,
+}
+
+impl ChangeFeed {
+    pub fn new(retention: usize) -> ChangeFeed {
+        assert!(retention > 0, "retention must be greater than zero");
+        return ChangeFeed {
+            state: Mutex::new(FeedState {
+                changes: VecDeque::new(),
+                next_sequence: 1,
+                retention,
+                closed: false,
+                wakers: HashMap::new(),
+                next_subscription: 1,
+            }),
+            appended: Condvar::new(),
+        };
+    }
+
+    pub fn record(&self, key: String, old_value: Option<String>, new_value: Option<String>) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let sequence = state.next_sequence;
+        state.next_sequence += 1;
+        state.changes.push_back(Change { sequence, key, old_value, new_value });
+        if state.changes.len() > state.retention {
+            state.changes.pop_front();
+        }
+        state.wake();
+        self.appended.notify_all();
+        return sequence;
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        return self.state.lock().unwrap().next_sequence - 1;
+    }
+
+    //subscribers drain the changes recorded so far and then end
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.wake();
+        self.appended.notify_all();
+    }
+
+    //resuming after the last change seen means subscribing from its sequence plus one
+    pub fn subscribe(self: &Arc<Self>, from_sequence: u64) -> Result<Subscription, QueueError> {
+        let from_sequence = from_sequence.max(1);
+        let mut state = self.state.lock().unwrap();
+        if from_sequence < state.first_sequence() {
+            return Err(QueueError::ChangeUnavailable(from_sequence));
+        }
+        let id = state.next_subscription;
+        state.next_subscription += 1;
+        return Ok(Subscription { id, feed: self.clone(), next_sequence: from_sequence, ended: false });
+    }
+}
+
+impl FeedState {
+    fn first_sequence(&self) -> u64 {
+        return self.changes.front().map(|change| change.sequence).unwrap_or(self.next_sequence);
+    }
+
+    fn change(&self, sequence: u64) -> Option<Result<Change, QueueError>> {
+        if sequence < self.first_sequence() {
+            return Some(Err(QueueError::ChangeUnavailable(sequence)));
+        }
+        return self.changes.get((sequence - self.first_sequence()) as usize).cloned().map(Ok);
+    }
+
+    fn wake(&mut self) {
+        for (_, waker) in self.wakers.drain() {
+            waker.wake();
+        }
+    }
+}
+
+impl Subscription {
+    pub fn next_sequence(&self) -> u64 {
+        return self.next_sequence;
+    }
+
+    //the next change if it is already recorded, without waiting for one
+    pub fn try_next(&mut self) -> Option<Result<Change, QueueError>> {
+        if self.ended {
+            return None;
+        }
+        let change = self.feed.state.lock().unwrap().change(self.next_sequence);
+        return change.map(|change| self.advance(change));
+    }
+
+    fn advance(&mut self, change: Result<Change, QueueError>) -> Result<Change, QueueError> {
+        match &change {
+            Ok(_) => self.next_sequence += 1,
+            Err(_) => self.ended = true,
+        }
+        return change;
+    }
+}
+
+//blocks until the next change is recorded, ends once the feed is closed
+impl Iterator for Subscription {
+    type Item = Result<Change, QueueError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.ended {
+            return None;
+        }
+        let feed = self.feed.clone();
+        let mut state = feed.state.lock().unwrap();
+        loop {
+            if let Some(change) = state.change(self.next_sequence) {
+                return Some(self.advance(change));
+            }
+            if state.closed {
+                self.ended = true;
+                return None;
+            }
+            state = feed.appended.wait(state).unwrap();
+        }
+    }
+}
+
+impl Stream for Subscription {
+    type Item = Result<Change, QueueError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.ended {
+            return Poll::Ready(None);
+        }
+        let feed = self.feed.clone();
+        let mut state = feed.state.lock().unwrap();
+        if let Some(change) = state.change(self.next_sequence) {
+            return Poll::Ready(Some(self.advance(change)));
+        }
+        if state.closed {
+            self.ended = true;
+            return Poll::Ready(None);
+        }
+        if !state.wakers.get(&self.id).is_some_and(|registered| registered.will_wake(context.waker())) {
+            state.wakers.insert(self.id, context.waker().clone());
+        }
+        return Poll::Pending;
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.feed.state.lock() {
+            state.wakers.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    fn put(feed: &ChangeFeed, key: &str, value: &str) -> u64 {
+        return feed.record(String::from(key), None, Some(String::from(value)));
+    }
+
+    #[test]
+    fn test_iterate_over_recorded_changes() {
+        let feed = Arc::new(ChangeFeed::new(16));
+        put(&feed, "key1", "value1");
+        feed.record(String::from("key1"), Some(String::from("value1")), None);
+        feed.close();
+
+        let changes: Vec<Change> = feed.subscribe(0).unwrap().map(|change| change.unwrap()).collect();
+        assert_eq!(vec![
+            Change { sequence: 1, key: String::from("key1"), old_value: None, new_value: Some(String::from("value1")) },
+            Change { sequence: 2, key: String::from("key1"), old_value: Some(String::from("value1")), new_value: None },
+        ], changes);
+    }
+
+    #[test]
+    fn test_resume_from_a_sequence() {
+        let feed = Arc::new(ChangeFeed::new(16));
+        for index in 0..5 {
+            put(&feed, &format!("key{}", index), "value");
+        }
+
+        let mut subscription = feed.subscribe(4).unwrap();
+        assert_eq!(4, subscription.try_next().unwrap().unwrap().sequence);
+        assert_eq!(5, subscription.try_next().unwrap().unwrap().sequence);
+        assert_eq!(None, subscription.try_next());
+        assert_eq!(6, subscription.next_sequence());
+    }
+
+    #[test]
+    fn test_subscribe_before_the_retention() {
+        let feed = Arc::new(ChangeFeed::new(2));
+        for index in 0..5 {
+            put(&feed, &format!("key{}", index), "value");
+        }
+
+        assert_eq!(true, matches!(feed.subscribe(3), Err(QueueError::ChangeUnavailable(3))));
+        assert_eq!(4, feed.subscribe(4).unwrap().try_next().unwrap().unwrap().sequence);
+    }
+
+    #[test]
+    fn test_a_lagging_subscriber_ends() {
+        let feed = Arc::new(ChangeFeed::new(2));
+        let mut subscription = feed.subscribe(1).unwrap();
+        for index in 0..5 {
+            put(&feed, &format!("key{}", index), "value");
+        }
+
+        assert_eq!(Some(Err(QueueError::ChangeUnavailable(1))), subscription.next());
+        assert_eq!(None, subscription.next());
+    }
+
+    #[test]
+    fn test_wait_for_a_change() {
+        let feed = Arc::new(ChangeFeed::new(16));
+        let mut subscription = feed.subscribe(1).unwrap();
+
+        let recorder = feed.clone();
+        let handle = thread::spawn(move || put(&recorder, "key1", "value1"));
+
+        assert_eq!(String::from("key1"), subscription.next().unwrap().unwrap().key);
+        handle.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_stream_changes() {
+        let feed = Arc::new(ChangeFeed::new(16));
+        let mut subscription = feed.subscribe(1).unwrap();
+
+        let recorder = feed.clone();
+        tokio::spawn(async move {
+            for index in 0..3 {
+                put(&recorder, &format!("key{}", index), "value");
+                tokio::task::yield_now().await;
+            }
+            recorder.close();
+        });
+
+        let mut sequences = Vec::new();
+        while let Some(change) = tokio_stream::StreamExt::next(&mut subscription).await {
+            sequences.push(change.unwrap().sequence);
+        }
+        assert_eq!(vec![1, 2, 3], sequences);
+    }
+
+    #[test]
+    fn test_keep_one_waker_per_pending_subscription() {
+        let feed = Arc::new(ChangeFeed::new(16));
+        let mut subscription = feed.subscribe(1).unwrap();
+        let mut context = Context::from_waker(Waker::noop());
+
+        for _ in 0..10 {
+            assert_eq!(true, Pin::new(&mut subscription).poll_next(&mut context).is_pending());
+        }
+        assert_eq!(1, feed.state.lock().unwrap().wakers.len());
+
+        drop(subscription);
+        assert_eq!(0, feed.state.lock().unwrap().wakers.len());
+    }
+}
@@ -0,0 +1,13 @@
+pub trait CommandHandler<C, R>: Send + Sync {
+    fn handle(&self, command: C) -> R;
+
+    //handlers which can amortize work across commands (e.g. a single fsync) override this, one response per command in order
+    fn handle_batch(&self, commands: Vec<C>) -> Vec<R> {
+        return commands.into_iter().map(|command| self.handle(command)).collect();
+    }
+
+    //names the type of a command in the latency histograms of the queue, decorators delegate to the handler they wrap
+    fn command_type(&self, _command: &C) -> &'static str {
+        return "command";
+    }
+}
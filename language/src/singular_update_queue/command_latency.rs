@@ -0,0 +1,260 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+//bucket i holds latencies up to 2^i microseconds, the last bucket holds everything above 2^(BUCKETS - 1) microseconds
+const BUCKETS: usize = 24;
+const METRIC_NAME: &str = "singular_update_queue_command_latency_seconds";
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Stage {
+    //from execute until the worker hands the command to the handler, batching delay included
+    Wait,
+    //the time the handler took, shared by every command of a batch
+    Handle,
+    //from the handler returning until the response was sent back
+    Respond,
+    Total,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LatencyHistogram {
+    buckets: [u64; BUCKETS + 1],
+    count: u64,
+    sum: Duration,
+}
+
+//the timestamps of a command on its way through the queue
+#[derive(Debug, Copy, Clone)]
+pub struct CommandTrace {
+    enqueued_at: Instant,
+    dequeued_at: Option<Instant>,
+    applied_at: Option<Instant>,
+    responded_at: Option<Instant>,
+}
+
+//histograms per command type and stage, shared by the queue handles and the worker which records into them
+#[derive(Debug, Default)]
+pub struct CommandLatencies {
+    histograms: Mutex<BTreeMap<(&'static str, Stage), LatencyHistogram>>,
+}
+
+impl Stage {
+    pub fn all() -> [Stage; 4] {
+        return [Stage::Wait, Stage::Handle, Stage::Respond, Stage::Total];
+    }
+
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Stage::Wait => "wait",
+            Stage::Handle => "handle",
+            Stage::Respond => "respond",
+            Stage::Total => "total",
+        };
+    }
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        return LatencyHistogram { buckets: [0; BUCKETS + 1], count: 0, sum: Duration::ZERO };
+    }
+}
+
+impl LatencyHistogram {
+    pub fn record(&mut self, latency: Duration) {
+        let micros = latency.as_micros().max(1);
+        let bucket = (u128::BITS - (micros - 1).leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS)] += 1;
+        self.count += 1;
+        self.sum += latency;
+    }
+
+    pub fn count(&self) -> u64 {
+        return self.count;
+    }
+
+    pub fn sum(&self) -> Duration {
+        return self.sum;
+    }
+
+    pub fn mean(&self) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        return Duration::from_nanos((self.sum.as_nanos() / self.count as u128) as u64);
+    }
+
+    //the upper bound of the bucket holding the quantile, None when nothing was recorded or it falls in the unbounded bucket
+    pub fn quantile(&self, quantile: f64) -> Option<Duration> {
+        assert!((0.0..=1.0).contains(&quantile), "quantile must be between 0 and 1");
+        if self.count == 0 {
+            return None;
+        }
+        let rank = ((quantile * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (bucket, count) in self.buckets.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return upper_bound(bucket);
+            }
+        }
+        return None;
+    }
+}
+
+fn upper_bound(bucket: usize) -> Option<Duration> {
+    if bucket >= BUCKETS {
+        return None;
+    }
+    return Some(Duration::from_micros(1 << bucket));
+}
+
+impl CommandTrace {
+    pub fn start() -> CommandTrace {
+        return CommandTrace { enqueued_at: Instant::now(), dequeued_at: None, applied_at: None, responded_at: None };
+    }
+
+    pub fn dequeued(&mut self, at: Instant) {
+        self.dequeued_at = Some(at);
+    }
+
+    pub fn applied(&mut self, at: Instant) {
+        self.applied_at = Some(at);
+    }
+
+    pub fn responded(&mut self, at: Instant) {
+        self.responded_at = Some(at);
+    }
+
+    pub fn stage(&self, stage: Stage) -> Option<Duration> {
+        let (from, to) = match stage {
+            Stage::Wait => (Some(self.enqueued_at), self.dequeued_at),
+            Stage::Handle => (self.dequeued_at, self.applied_at),
+            Stage::Respond => (self.applied_at, self.responded_at),
+            Stage::Total => (Some(self.enqueued_at), self.responded_at),
+        };
+        return Some(to?.saturating_duration_since(from?));
+    }
+}
+
+impl CommandLatencies {
+    //stages the command has not reached are skipped
+    pub fn record(&self, command_type: &'static str, trace: &CommandTrace) {
+        let mut histograms = self.histograms.lock().unwrap();
+        for stage in Stage::all() {
+            if let Some(latency) = trace.stage(stage) {
+                histograms.entry((command_type, stage)).or_default().record(latency);
+            }
+        }
+    }
+
+    pub fn histogram(&self, command_type: &str, stage: Stage) -> Option<LatencyHistogram> {
+        let histograms = self.histograms.lock().unwrap();
+        return histograms
+            .iter()
+            .find(|((recorded_type, recorded_stage), _)| *recorded_type == command_type && *recorded_stage == stage)
+            .map(|(_, histogram)| histogram.clone());
+    }
+
+    pub fn command_types(&self) -> Vec<&'static str> {
+        let mut command_types: Vec<&'static str> = self.histograms.lock().unwrap().keys().map(|(command_type, _)| *command_type).collect();
+        command_types.dedup();
+        return command_types;
+    }
+
+    //prometheus text exposition format, buckets are cumulative
+    pub fn export_text(&self) -> String {
+        let histograms = self.histograms.lock().unwrap();
+        let mut text = String::new();
+        let _ = writeln!(text, "# HELP {} Time a command spent in each stage of the singular update queue.", METRIC_NAME);
+        let _ = writeln!(text, "# TYPE {} histogram", METRIC_NAME);
+        for ((command_type, stage), histogram) in histograms.iter() {
+            let labels = format!("command=\"{}\",stage=\"{}\"", command_type, stage.name());
+            let mut cumulative = 0;
+            for (bucket, count) in histogram.buckets.iter().enumerate() {
+                cumulative += count;
+                let bound = match upper_bound(bucket) {
+                    Some(bound) => format!("{}", bound.as_secs_f64()),
+                    None => String::from("+Inf"),
+                };
+                let _ = writeln!(text, "{}_bucket{{{},le=\"{}\"}} {}", METRIC_NAME, labels, bound, cumulative);
+            }
+            let _ = writeln!(text, "{}_sum{{{}}} {}", METRIC_NAME, labels, histogram.sum.as_secs_f64());
+            let _ = writeln!(text, "{}_count{{{}}} {}", METRIC_NAME, labels, histogram.count);
+        }
+        return text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_into_buckets() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.record(Duration::ZERO);
+        histogram.record(Duration::from_micros(3));
+        histogram.record(Duration::from_micros(4));
+        histogram.record(Duration::from_millis(5));
+
+        assert_eq!(4, histogram.count());
+        assert_eq!(Duration::from_micros(5007), histogram.sum());
+        assert_eq!(Some(Duration::from_micros(1)), histogram.quantile(0.25));
+        assert_eq!(Some(Duration::from_micros(4)), histogram.quantile(0.75));
+        assert_eq!(Some(Duration::from_micros(8192)), histogram.quantile(1.0));
+    }
+
+    #[test]
+    fn test_quantile_beyond_the_last_bucket() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(None, histogram.quantile(0.5));
+
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(None, histogram.quantile(0.5));
+        assert_eq!(Duration::from_secs(60), histogram.mean());
+    }
+
+    #[test]
+    fn test_mean_of_more_latencies_than_fit_a_u32() {
+        let count = u32::MAX as u64 + 2;
+        let histogram = LatencyHistogram { buckets: [0; BUCKETS + 1], count, sum: Duration::from_micros(count) };
+
+        assert_eq!(Duration::from_micros(1), histogram.mean());
+    }
+
+    #[test]
+    fn test_stages_of_a_trace() {
+        let mut trace = CommandTrace::start();
+        let enqueued_at = trace.enqueued_at;
+        trace.dequeued(enqueued_at + Duration::from_millis(2));
+        trace.applied(enqueued_at + Duration::from_millis(5));
+
+        assert_eq!(Some(Duration::from_millis(2)), trace.stage(Stage::Wait));
+        assert_eq!(Some(Duration::from_millis(3)), trace.stage(Stage::Handle));
+        assert_eq!(None, trace.stage(Stage::Respond));
+
+        trace.responded(enqueued_at + Duration::from_millis(6));
+        assert_eq!(Some(Duration::from_millis(6)), trace.stage(Stage::Total));
+    }
+
+    #[test]
+    fn test_export_in_text_format() {
+        let latencies = CommandLatencies::default();
+        let mut trace = CommandTrace::start();
+        let enqueued_at = trace.enqueued_at;
+        trace.dequeued(enqueued_at + Duration::from_micros(3));
+        latencies.record("put", &trace);
+
+        let text = latencies.export_text();
+
+        assert_eq!(vec!["put"], latencies.command_types());
+        assert_eq!(true, text.contains("# TYPE singular_update_queue_command_latency_seconds histogram"));
+        assert_eq!(true, text.contains("singular_update_queue_command_latency_seconds_bucket{command=\"put\",stage=\"wait\",le=\"0.000002\"} 0"));
+        assert_eq!(true, text.contains("singular_update_queue_command_latency_seconds_bucket{command=\"put\",stage=\"wait\",le=\"0.000004\"} 1"));
+        assert_eq!(true, text.contains("singular_update_queue_command_latency_seconds_bucket{command=\"put\",stage=\"wait\",le=\"+Inf\"} 1"));
+        assert_eq!(true, text.contains("singular_update_queue_command_latency_seconds_count{command=\"put\",stage=\"wait\"} 1"));
+        assert_eq!(false, text.contains("stage=\"handle\""));
+    }
+}
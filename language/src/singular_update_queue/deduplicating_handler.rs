@@ -0,0 +1,262 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::singular_update_queue::command::CommandHandler;
+
+//a client numbers its requests, a retry carries the same request id as the attempt it repeats
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct RequestId {
+    pub client_id: String,
+    pub sequence: u64,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClientCommand<C> {
+    pub request_id: RequestId,
+    pub command: C,
+}
+
+//answers a repeated request with the response of its first execution instead of applying it again,
+//every response is cached (failures included) until the retention elapses
+pub struct DeduplicatingHandler<C, R> {
+    inner: Arc<dyn CommandHandler<C, R>>,
+    retention: Duration,
+    table: Mutex<DeduplicationTable<R>>,
+}
+
+struct DeduplicationTable<R> {
+    responses: HashMap<RequestId, R>,
+    recorded: VecDeque<(Instant, RequestId)>,
+}
+
+impl<C> ClientCommand<C> {
+    pub fn new(client_id: &str, sequence: u64, command: C) -> ClientCommand<C> {
+        return ClientCommand { request_id: RequestId { client_id: String::from(client_id), sequence }, command };
+    }
+}
+
+impl<C, R: Clone> DeduplicatingHandler<C, R> {
+    pub fn new(inner: Arc<dyn CommandHandler<C, R>>, retention: Duration) -> DeduplicatingHandler<C, R> {
+        assert!(retention > Duration::ZERO, "retention must be greater than zero");
+        return DeduplicatingHandler {
+            inner,
+            retention,
+            table: Mutex::new(DeduplicationTable { responses: HashMap::new(), recorded: VecDeque::new() }),
+        };
+    }
+
+    pub fn cached(&self) -> usize {
+        return self.lock().responses.len();
+    }
+
+    //the lock is held while the inner handler runs, a panic in there leaves the table as it was before the request
+    fn lock(&self) -> MutexGuard<'_, DeduplicationTable<R>> {
+        return self.table.lock().unwrap_or_else(PoisonError::into_inner);
+    }
+}
+
+impl<R: Clone> DeduplicationTable<R> {
+    //entries are recorded in time order, so the expired ones are at the front
+    fn expire(&mut self, retention: Duration) {
+        let now = Instant::now();
+        while let Some((recorded_at, _)) = self.recorded.front() {
+            if now.duration_since(*recorded_at) < retention {
+                return;
+            }
+            let (_, request_id) = self.recorded.pop_front().unwrap();
+            self.responses.remove(&request_id);
+        }
+    }
+
+    fn record(&mut self, request_id: RequestId, response: R) {
+        self.recorded.push_back((Instant::now(), request_id.clone()));
+        self.responses.insert(request_id, response);
+    }
+}
+
+impl<C: Send + Sync, R: Clone + Send + Sync> CommandHandler<ClientCommand<C>, R> for DeduplicatingHandler<C, R> {
+    fn handle(&self, command: ClientCommand<C>) -> R {
+        let mut table = self.lock();
+        table.expire(self.retention);
+        if let Some(response) = table.responses.get(&command.request_id) {
+            return response.clone();
+        }
+        let response = self.inner.handle(command.command);
+        table.record(command.request_id, response.clone());
+        return response;
+    }
+
+    //only the requests seen for the first time reach the inner handler, as one batch, a request repeated within the batch runs once
+    fn handle_batch(&self, commands: Vec<ClientCommand<C>>) -> Vec<R> {
+        let mut table = self.lock();
+        table.expire(self.retention);
+
+        let mut fresh_ids: Vec<RequestId> = Vec::new();
+        let mut seen_ids: HashSet<RequestId> = HashSet::new();
+        let mut fresh_commands: Vec<C> = Vec::new();
+        let mut request_ids: Vec<RequestId> = Vec::with_capacity(commands.len());
+        for command in commands {
+            if !table.responses.contains_key(&command.request_id) && seen_ids.insert(command.request_id.clone()) {
+                fresh_ids.push(command.request_id.clone());
+                fresh_commands.push(command.command);
+            }
+            request_ids.push(command.request_id);
+        }
+
+        let responses = self.inner.handle_batch(fresh_commands);
+        for (request_id, response) in fresh_ids.into_iter().zip(responses) {
+            table.record(request_id, response);
+        }
+        //stops at the first request the inner handler did not answer, the queue fails it and every request after it
+        return request_ids.iter().map_while(|request_id| table.responses.get(request_id).cloned()).collect();
+    }
+
+    fn command_type(&self, command: &ClientCommand<C>) -> &'static str {
+        return self.inner.command_type(&command.command);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::panic::AssertUnwindSafe;
+    use std::sync::RwLock;
+
+    use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+    use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Status, Storage, StorageCommand};
+
+    use super::*;
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    fn handler() -> (DeduplicatingHandler<StorageCommand, Status>, Arc<InMemoryStorageHandler>) {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let inner = Arc::new(InMemoryStorageHandler::new(storage));
+        return (DeduplicatingHandler::new(inner.clone(), Duration::from_secs(60)), inner);
+    }
+
+    #[test]
+    fn test_do_not_apply_a_repeated_request() {
+        let (handler, inner) = handler();
+
+        assert_eq!(Status::Ok, handler.handle(ClientCommand::new("client1", 1, put("key1", "value1"))));
+        assert_eq!(Status::Ok, handler.handle(ClientCommand::new("client1", 2, put("key1", "value2"))));
+        assert_eq!(Status::Ok, handler.handle(ClientCommand::new("client1", 1, put("key1", "value1"))));
+
+        assert_eq!("value2", inner.storage().read().unwrap().get("key1").unwrap());
+        assert_eq!(Ok(2), inner.version("key1"));
+    }
+
+    #[test]
+    fn test_return_the_cached_response() {
+        let (handler, _) = handler();
+        handler.handle(ClientCommand::new("client1", 1, put("key1", "value1")));
+
+        let first = handler.handle(ClientCommand::new("client1", 2, StorageCommand::Get { key: String::from("key1") }));
+        handler.handle(ClientCommand::new("client1", 3, StorageCommand::Delete { key: String::from("key1") }));
+        let repeated = handler.handle(ClientCommand::new("client1", 2, StorageCommand::Get { key: String::from("key1") }));
+
+        assert_eq!(Status::Value(Some(String::from("value1"))), first);
+        assert_eq!(first, repeated);
+    }
+
+    #[test]
+    fn test_tell_clients_apart() {
+        let (handler, inner) = handler();
+
+        handler.handle(ClientCommand::new("client1", 1, put("key1", "value1")));
+        handler.handle(ClientCommand::new("client2", 1, put("key1", "value2")));
+
+        assert_eq!(Ok(2), inner.version("key1"));
+        assert_eq!(2, handler.cached());
+    }
+
+    #[test]
+    fn test_repeat_a_request_within_a_batch() {
+        let (handler, inner) = handler();
+        handler.handle(ClientCommand::new("client1", 1, put("key1", "value1")));
+
+        let statuses = handler.handle_batch(vec![
+            ClientCommand::new("client1", 2, put("key2", "value2")),
+            ClientCommand::new("client1", 1, put("key1", "value1")),
+            ClientCommand::new("client1", 2, put("key2", "value2")),
+        ]);
+
+        assert_eq!(vec![Status::Ok, Status::Ok, Status::Ok], statuses);
+        assert_eq!(Ok(1), inner.version("key1"));
+        assert_eq!(Ok(1), inner.version("key2"));
+    }
+
+    struct ShortHandler;
+
+    impl CommandHandler<StorageCommand, Status> for ShortHandler {
+        fn handle(&self, _command: StorageCommand) -> Status {
+            panic!("handler failed");
+        }
+
+        fn handle_batch(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+            return vec![Status::Ok; commands.len() - 1];
+        }
+    }
+
+    #[test]
+    fn test_keep_serving_after_the_inner_handler_panics() {
+        let handler = DeduplicatingHandler::new(Arc::new(ShortHandler), Duration::from_secs(60));
+
+        let result = std::panic::catch_unwind(AssertUnwindSafe(|| handler.handle(ClientCommand::new("client1", 1, put("key1", "value1")))));
+        assert_eq!(true, result.is_err());
+
+        let statuses = handler.handle_batch(vec![ClientCommand::new("client1", 1, put("key1", "value1"))]);
+        assert_eq!(true, statuses.is_empty());
+    }
+
+    #[test]
+    fn test_answer_only_the_requests_the_inner_handler_answered() {
+        let handler = DeduplicatingHandler::new(Arc::new(ShortHandler), Duration::from_secs(60));
+
+        let statuses = handler.handle_batch(vec![
+            ClientCommand::new("client1", 1, put("key1", "value1")),
+            ClientCommand::new("client1", 2, put("key2", "value2")),
+            ClientCommand::new("client1", 1, put("key1", "value1")),
+        ]);
+
+        assert_eq!(vec![Status::Ok], statuses);
+        assert_eq!(1, handler.cached());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_expire_old_requests() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let inner = Arc::new(InMemoryStorageHandler::new(storage));
+        let handler = DeduplicatingHandler::new(inner.clone(), Duration::from_secs(10));
+
+        handler.handle(ClientCommand::new("client1", 1, put("key1", "value1")));
+        tokio::time::advance(Duration::from_secs(6)).await;
+        handler.handle(ClientCommand::new("client1", 2, put("key2", "value2")));
+        tokio::time::advance(Duration::from_secs(6)).await;
+
+        handler.handle(ClientCommand::new("client1", 2, put("key2", "value2")));
+        assert_eq!(Ok(1), inner.version("key2"));
+        assert_eq!(1, handler.cached());
+
+        handler.handle(ClientCommand::new("client1", 1, put("key1", "value1")));
+        assert_eq!(Ok(2), inner.version("key1"));
+    }
+
+    #[test]
+    fn test_retry_through_the_queue() {
+        let (handler, inner) = handler();
+        let singular_update_queue = SingularUpdateQueue::init(Arc::new(handler), Executor::Thread);
+
+        let command = ClientCommand::new("client1", 1, put("key1", "value1"));
+        assert_eq!(Ok(Status::Ok), singular_update_queue.execute(command.clone()).wait());
+        assert_eq!(Ok(Status::Ok), singular_update_queue.execute(command).wait());
+
+        assert_eq!(Ok(1), inner.version("key1"));
+    }
+}
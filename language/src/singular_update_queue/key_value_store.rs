@@ -0,0 +1,379 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::storage_command::{Status, StorageCommand};
+use crate::singular_update_queue::transaction::{Operation, Transaction};
+
+pub type OrderedStorage = Arc<RwLock<BTreeMap<String, String>>>;
+
+//the handler keeps the version of every key it wrote under this prefix, in the same store and the same atomic apply as the key
+const VERSION_PREFIX: &str = "\u{0}version:";
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Mutation {
+    Put {
+        key: String,
+        value: String,
+    },
+    Delete {
+        key: String,
+    },
+}
+
+//a storage backend of the queue, the singular update queue is the only writer so implementations only need to make reads safe next to it
+pub trait KeyValueStore: Send + Sync {
+    fn get(&self, key: &str) -> Result<Option<String>, QueueError>;
+
+    //applies every mutation or none of them, writes are blind so that a store like the lsm tree never has to read before it writes
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<(), QueueError>;
+
+    //the entries with start <= key < end in key order, no end scans to the last key
+    fn scan(&self, start: &str, end: Option<&str>) -> Result<Vec<(String, String)>, QueueError>;
+
+    fn put(&self, key: String, value: String) -> Result<(), QueueError> {
+        return self.apply(vec![Mutation::Put { key, value }]);
+    }
+
+    fn delete(&self, key: &str) -> Result<(), QueueError> {
+        return self.apply(vec![Mutation::Delete { key: String::from(key) }]);
+    }
+}
+
+impl Mutation {
+    pub fn key(&self) -> &str {
+        return match self {
+            Mutation::Put { key, .. } => key,
+            Mutation::Delete { key } => key,
+        };
+    }
+}
+
+impl KeyValueStore for RwLock<HashMap<String, String>> {
+    fn get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        return Ok(self.read().map_err(|_| QueueError::Poisoned)?.get(key).cloned());
+    }
+
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<(), QueueError> {
+        let mut storage = self.write().map_err(|_| QueueError::Poisoned)?;
+        for mutation in mutations {
+            match mutation {
+                Mutation::Put { key, value } => storage.insert(key, value),
+                Mutation::Delete { key } => storage.remove(&key),
+            };
+        }
+        return Ok(());
+    }
+
+    //a hash map has no order, so a scan visits every entry and sorts the ones in range
+    fn scan(&self, start: &str, end: Option<&str>) -> Result<Vec<(String, String)>, QueueError> {
+        let storage = self.read().map_err(|_| QueueError::Poisoned)?;
+        let mut entries: Vec<(String, String)> = storage
+            .iter()
+            .filter(|(key, _)| key.as_str() >= start && end.is_none_or(|end| key.as_str() < end))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        entries.sort();
+        return Ok(entries);
+    }
+}
+
+impl KeyValueStore for RwLock<BTreeMap<String, String>> {
+    fn get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        return Ok(self.read().map_err(|_| QueueError::Poisoned)?.get(key).cloned());
+    }
+
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<(), QueueError> {
+        let mut storage = self.write().map_err(|_| QueueError::Poisoned)?;
+        for mutation in mutations {
+            match mutation {
+                Mutation::Put { key, value } => storage.insert(key, value),
+                Mutation::Delete { key } => storage.remove(&key),
+            };
+        }
+        return Ok(());
+    }
+
+    fn scan(&self, start: &str, end: Option<&str>) -> Result<Vec<(String, String)>, QueueError> {
+        let storage = self.read().map_err(|_| QueueError::Poisoned)?;
+        if end.is_some_and(|end| end <= start) {
+            return Ok(Vec::new());
+        }
+        let end = end.map_or(Bound::Unbounded, Bound::Excluded);
+        return Ok(storage
+            .range::<str, _>((Bound::Included(start), end))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect());
+    }
+}
+
+//runs storage commands against any KeyValueStore, versions are persisted next to the keys so that a persistent store
+//keeps them across a reopen, the keys a store holds without a version, written by someone else, start at version 1
+pub struct KeyValueStoreHandler {
+    store: Arc<dyn KeyValueStore>,
+    versions: Mutex<HashMap<String, u64>>,
+}
+
+impl KeyValueStoreHandler {
+    pub fn new(store: Arc<dyn KeyValueStore>) -> Result<KeyValueStoreHandler, QueueError> {
+        let mut versions: HashMap<String, u64> = HashMap::new();
+        //the version entries sort before every other key, so a key found later keeps its persisted version
+        for (key, value) in store.scan("", None)? {
+            match key.strip_prefix(VERSION_PREFIX) {
+                Some(key) => {
+                    let version = value.parse().map_err(|_| QueueError::HandlerFailed(format!("corrupted version of {}", key)))?;
+                    versions.insert(String::from(key), version);
+                }
+                None => {
+                    versions.entry(key).or_insert(1);
+                }
+            }
+        }
+        return Ok(KeyValueStoreHandler { store, versions: Mutex::new(versions) });
+    }
+
+    pub fn store(&self) -> &Arc<dyn KeyValueStore> {
+        return &self.store;
+    }
+
+    pub fn version(&self, key: &str) -> Result<u64, QueueError> {
+        let versions = self.versions.lock().map_err(|_| QueueError::Poisoned)?;
+        return Ok(versions.get(key).copied().unwrap_or(0));
+    }
+
+    fn write(&self, mutations: Vec<Mutation>) -> Result<(), QueueError> {
+        let mut versions = self.versions.lock().map_err(|_| QueueError::Poisoned)?;
+        let mut written: BTreeMap<String, u64> = BTreeMap::new();
+        for mutation in &mutations {
+            let key = mutation.key();
+            if key.starts_with(VERSION_PREFIX) {
+                return Err(QueueError::HandlerFailed(format!("the key {} is reserved for versions", key)));
+            }
+            let current = written.get(key).or(versions.get(key)).copied();
+            //a delete moves the version of a key which has one, a key which was never written stays at 0
+            let version = match (mutation, current) {
+                (Mutation::Put { .. }, current) => current.unwrap_or(0) + 1,
+                (Mutation::Delete { .. }, Some(current)) => current + 1,
+                (Mutation::Delete { .. }, None) => continue,
+            };
+            written.insert(String::from(key), version);
+        }
+
+        let mut mutations = mutations;
+        mutations.extend(written.iter().map(|(key, version)| Mutation::Put { key: version_key(key), value: version.to_string() }));
+        self.store.apply(mutations)?;
+        versions.extend(written);
+        return Ok(());
+    }
+
+    //reads within the transaction observe its earlier writes, the writes reach the store as one atomic apply
+    fn execute(&self, transaction: Transaction) -> Result<Status, QueueError> {
+        {
+            let versions = self.versions.lock().map_err(|_| QueueError::Poisoned)?;
+            for (position, precondition) in transaction.preconditions.iter().enumerate() {
+                let key = precondition.key();
+                let current = self.store.get(key)?;
+                if !precondition.holds_for(current.as_ref(), versions.get(key).copied().unwrap_or(0)) {
+                    return Err(QueueError::PreconditionFailed(position));
+                }
+            }
+        }
+
+        let mut written: HashMap<String, Option<String>> = HashMap::new();
+        let mut mutations = Vec::new();
+        let mut statuses = Vec::with_capacity(transaction.operations.len());
+        for operation in transaction.operations {
+            match operation {
+                Operation::Get { key } => {
+                    let value = match written.get(&key) {
+                        Some(value) => value.clone(),
+                        None => self.store.get(&key)?,
+                    };
+                    statuses.push(Status::Value(value));
+                }
+                Operation::Put { key, value } => {
+                    written.insert(key.clone(), Some(value.clone()));
+                    mutations.push(Mutation::Put { key, value });
+                    statuses.push(Status::Ok);
+                }
+                Operation::Delete { key } => {
+                    written.insert(key.clone(), None);
+                    mutations.push(Mutation::Delete { key });
+                    statuses.push(Status::Ok);
+                }
+            }
+        }
+        self.write(mutations)?;
+        return Ok(Status::Committed(statuses));
+    }
+}
+
+fn version_key(key: &str) -> String {
+    return format!("{}{}", VERSION_PREFIX, key);
+}
+
+impl CommandHandler<StorageCommand, Status> for KeyValueStoreHandler {
+    fn handle(&self, command: StorageCommand) -> Status {
+        let result = match command {
+            StorageCommand::Get { key } => self.store.get(&key).map(Status::Value),
+            StorageCommand::Put { key, value } => self.write(vec![Mutation::Put { key, value }]).map(|_| Status::Ok),
+            StorageCommand::Delete { key } => self.write(vec![Mutation::Delete { key }]).map(|_| Status::Ok),
+            StorageCommand::Transaction(transaction) => self.execute(transaction),
+        };
+        return result.unwrap_or_else(Status::Failed);
+    }
+
+    fn command_type(&self, command: &StorageCommand) -> &'static str {
+        return command.command_type();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use crate::singular_update_queue::log_structured_store::LogStructuredStore;
+    use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+    use crate::singular_update_queue::transaction::Precondition;
+    use crate::singular_update_queue::write_ahead_log::FsyncPolicy;
+
+    use super::*;
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    fn get(key: &str) -> StorageCommand {
+        return StorageCommand::Get { key: String::from(key) };
+    }
+
+    fn stores(directory: &TempDir) -> Vec<Arc<dyn KeyValueStore>> {
+        return vec![
+            Arc::new(RwLock::new(HashMap::new())),
+            Arc::new(RwLock::new(BTreeMap::new())),
+            Arc::new(LogStructuredStore::open(directory.path(), FsyncPolicy::Never).unwrap()),
+        ];
+    }
+
+    #[test]
+    fn test_put_get_and_delete_on_every_store() {
+        let directory = TempDir::new().unwrap();
+        for store in stores(&directory) {
+            assert_eq!(Ok(()), store.put(String::from("key1"), String::from("value1")));
+            assert_eq!(Ok(()), store.put(String::from("key1"), String::from("value2")));
+            assert_eq!(Ok(Some(String::from("value2"))), store.get("key1"));
+            assert_eq!(Ok(()), store.delete("key1"));
+            assert_eq!(Ok(()), store.delete("key1"));
+            assert_eq!(Ok(None), store.get("key1"));
+        }
+    }
+
+    #[test]
+    fn test_scan_a_range_in_key_order_on_every_store() {
+        let directory = TempDir::new().unwrap();
+        for store in stores(&directory) {
+            for key in ["key4", "key1", "key3", "key2"] {
+                store.put(String::from(key), format!("value of {}", key)).unwrap();
+            }
+
+            let keys: Vec<String> = store.scan("key2", Some("key4")).unwrap().into_iter().map(|(key, _)| key).collect();
+            assert_eq!(vec![String::from("key2"), String::from("key3")], keys);
+            assert_eq!(4, store.scan("", None).unwrap().len());
+            assert_eq!(0, store.scan("key3", Some("key1")).unwrap().len());
+        }
+    }
+
+    #[test]
+    fn test_drive_every_store_through_the_queue() {
+        let directory = TempDir::new().unwrap();
+        for store in stores(&directory) {
+            let handler = Arc::new(KeyValueStoreHandler::new(store.clone()).unwrap());
+            let singular_update_queue = SingularUpdateQueue::init(handler.clone(), Executor::Thread);
+
+            assert_eq!(Ok(Status::Ok), singular_update_queue.execute(put("key1", "value1")).wait());
+            assert_eq!(Ok(Status::Ok), singular_update_queue.execute(put("key1", "value2")).wait());
+            assert_eq!(Ok(Status::Value(Some(String::from("value2")))), singular_update_queue.execute(get("key1")).wait());
+            assert_eq!(Ok(2), handler.version("key1"));
+            assert_eq!(Ok(Some(String::from("value2"))), store.get("key1"));
+        }
+    }
+
+    #[test]
+    fn test_execute_a_transaction_on_a_store() {
+        let store: OrderedStorage = Arc::new(RwLock::new(BTreeMap::new()));
+        let handler = KeyValueStoreHandler::new(store.clone()).unwrap();
+        handler.handle(put("key1", "value1"));
+
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::VersionMatches { key: String::from("key1"), version: 1 })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value2") })
+            .with_operation(Operation::Get { key: String::from("key2") })
+            .with_operation(Operation::Delete { key: String::from("key1") });
+        let status = handler.handle(StorageCommand::Transaction(transaction));
+
+        assert_eq!(Status::Committed(vec![Status::Ok, Status::Value(Some(String::from("value2"))), Status::Ok]), status);
+        assert_eq!(None, store.read().unwrap().get("key1"));
+        assert_eq!(Ok(2), handler.version("key1"));
+        assert_eq!(Ok(1), handler.version("key2"));
+    }
+
+    #[test]
+    fn test_apply_nothing_when_a_precondition_fails() {
+        let store: OrderedStorage = Arc::new(RwLock::new(BTreeMap::new()));
+        let handler = KeyValueStoreHandler::new(store.clone()).unwrap();
+
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::KeyExists { key: String::from("key1") })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value2") });
+
+        assert_eq!(Status::Failed(QueueError::PreconditionFailed(0)), handler.handle(StorageCommand::Transaction(transaction)));
+        assert_eq!(true, store.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_start_existing_keys_at_version_one() {
+        let store: OrderedStorage = Arc::new(RwLock::new(BTreeMap::from([(String::from("key1"), String::from("value1"))])));
+        let handler = KeyValueStoreHandler::new(store).unwrap();
+
+        assert_eq!(Ok(1), handler.version("key1"));
+        assert_eq!(Ok(0), handler.version("key2"));
+    }
+
+    #[test]
+    fn test_keep_versions_across_a_reopen() {
+        let directory = TempDir::new().unwrap();
+        {
+            let store = Arc::new(LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap());
+            let handler = KeyValueStoreHandler::new(store).unwrap();
+            handler.handle(put("key1", "value1"));
+            handler.handle(put("key1", "value2"));
+            handler.handle(put("key2", "value1"));
+            handler.handle(StorageCommand::Delete { key: String::from("key2") });
+        }
+
+        let store = Arc::new(LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap());
+        let handler = KeyValueStoreHandler::new(store.clone()).unwrap();
+        assert_eq!(Ok(2), handler.version("key1"));
+        assert_eq!(Ok(2), handler.version("key2"));
+        assert_eq!(Ok(Some(String::from("value2"))), store.get("key1"));
+
+        //a precondition taken before the reopen must not match a key written again since
+        handler.handle(put("key1", "value3"));
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::VersionMatches { key: String::from("key1"), version: 2 })
+            .with_operation(Operation::Put { key: String::from("key1"), value: String::from("value4") });
+        assert_eq!(Status::Failed(QueueError::PreconditionFailed(0)), handler.handle(StorageCommand::Transaction(transaction)));
+    }
+
+    #[test]
+    fn test_reject_a_write_to_a_version_key() {
+        let store: OrderedStorage = Arc::new(RwLock::new(BTreeMap::new()));
+        let handler = KeyValueStoreHandler::new(store.clone()).unwrap();
+
+        let status = handler.handle(put(&version_key("key1"), "7"));
+        assert_eq!(true, matches!(status, Status::Failed(QueueError::HandlerFailed(_))));
+        assert_eq!(true, store.read().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::singular_update_queue::key_value_store::{KeyValueStore, Mutation};
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::write_ahead_log::{decode_string, decode_u32, encode_string, read_u32, FsyncPolicy};
+
+const DATA_FILE: &str = "store.data";
+const COMPACTING_FILE: &str = "store.compacting";
+const HEADER_SIZE: u64 = 8;
+const PUT_TAG: u8 = 1;
+const DELETE_TAG: u8 = 2;
+
+//an append-only data file with an ordered in-memory index from every live key to where its latest value sits in the file,
+//the data file is the only durable state, the index is rebuilt from it on open
+pub struct LogStructuredStore {
+    directory: PathBuf,
+    fsync_policy: FsyncPolicy,
+    state: Mutex<StoreState>,
+}
+
+struct StoreState {
+    file: File,
+    size: u64,
+    index: BTreeMap<String, ValuePosition>,
+    unsynced: usize,
+    stale_bytes: u64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct ValuePosition {
+    offset: u64,
+    length: u32,
+}
+
+//a record as it was decoded, positions are relative to the start of the record
+pub(crate) struct DecodedRecord {
+    pub(crate) mutations: Vec<(Mutation, Option<ValuePosition>)>,
+    pub(crate) length: u64,
+}
+
+//record layout: crc32 | payload length | mutation count | (tag, key, value)*, the crc covers the payload,
+//all the mutations of one apply share a record so that a crash keeps either all or none of them
+impl LogStructuredStore {
+    pub fn open(directory: &Path, fsync_policy: FsyncPolicy) -> io::Result<LogStructuredStore> {
+        fs::create_dir_all(directory)?;
+        let path = directory.join(DATA_FILE);
+        let bytes = if path.exists() { fs::read(&path)? } else { Vec::new() };
+
+        let mut index = BTreeMap::new();
+        let mut stale_bytes = 0;
+        let mut offset = 0;
+        while let Some(record) = decode_record(&bytes[offset as usize..]).map_err(|error| corrupted_record(&path, offset, error))? {
+            for (mutation, position) in record.mutations {
+                let replaced = match (mutation, position) {
+                    (Mutation::Put { key, .. }, Some(position)) => {
+                        index.insert(key, ValuePosition { offset: offset + position.offset, length: position.length })
+                    }
+                    (mutation, _) => index.remove(mutation.key()),
+                };
+                stale_bytes += replaced.map_or(0, |replaced| replaced.length as u64);
+            }
+            offset += record.length;
+        }
+
+        let file = OpenOptions::new().read(true).append(true).create(true).open(&path)?;
+        //a torn tail is a record which was being written when the process crashed
+        if offset < bytes.len() as u64 {
+            file.set_len(offset)?;
+        }
+        return Ok(LogStructuredStore {
+            directory: directory.to_path_buf(),
+            fsync_policy,
+            state: Mutex::new(StoreState { file, size: offset, index, unsynced: 0, stale_bytes }),
+        });
+    }
+
+    //bytes of values which were overwritten or deleted, compaction reclaims them
+    pub fn stale_bytes(&self) -> Result<u64, QueueError> {
+        return Ok(self.state.lock().map_err(|_| QueueError::Poisoned)?.stale_bytes);
+    }
+
+    pub fn size(&self) -> Result<u64, QueueError> {
+        return Ok(self.state.lock().map_err(|_| QueueError::Poisoned)?.size);
+    }
+
+    //rewrites the live entries into a new data file and renames it over the old one, a crash before the rename leaves the old file in place
+    pub fn compact(&self) -> Result<(), QueueError> {
+        let mut state = self.state.lock().map_err(|_| QueueError::Poisoned)?;
+        let compacting = self.directory.join(COMPACTING_FILE);
+        let mut file = File::create(&compacting).map_err(failed)?;
+        let mut index = BTreeMap::new();
+        let mut size = 0;
+        let positions: Vec<(String, ValuePosition)> = state.index.iter().map(|(key, position)| (key.clone(), *position)).collect();
+        for (key, position) in positions {
+            let value = read_value(&mut state.file, position)?;
+            let (record, positions) = encode_record(&[Mutation::Put { key: key.clone(), value }]);
+            file.write_all(&record).map_err(failed)?;
+            let position = positions[0].unwrap();
+            index.insert(key, ValuePosition { offset: size + position.offset, length: position.length });
+            size += record.len() as u64;
+        }
+        file.sync_all().map_err(failed)?;
+
+        let path = self.directory.join(DATA_FILE);
+        fs::rename(&compacting, &path).map_err(failed)?;
+        state.file = OpenOptions::new().read(true).append(true).open(&path).map_err(failed)?;
+        state.size = size;
+        state.index = index;
+        state.unsynced = 0;
+        state.stale_bytes = 0;
+        return Ok(());
+    }
+}
+
+impl KeyValueStore for LogStructuredStore {
+    fn get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        let mut state = self.state.lock().map_err(|_| QueueError::Poisoned)?;
+        let Some(position) = state.index.get(key).copied() else {
+            return Ok(None);
+        };
+        return read_value(&mut state.file, position).map(Some);
+    }
+
+    fn apply(&self, mutations: Vec<Mutation>) -> Result<(), QueueError> {
+        let mut state = self.state.lock().map_err(|_| QueueError::Poisoned)?;
+        let (record, positions) = encode_record(&mutations);
+        if let Err(error) = state.file.write_all(&record) {
+            //drops whatever part of the record made it to the file, the offsets in the index count on the file ending at size
+            let _ = state.file.set_len(state.size);
+            return Err(failed(error));
+        }
+        state.unsynced += 1;
+        let should_sync = match self.fsync_policy {
+            FsyncPolicy::Always => true,
+            FsyncPolicy::EveryN(count) => state.unsynced >= count,
+            FsyncPolicy::Never => false,
+        };
+        if should_sync {
+            if let Err(error) = state.file.sync_data() {
+                //the caller is told the mutations failed, so the record must not be found by a later open either
+                let _ = state.file.set_len(state.size);
+                return Err(failed(error));
+            }
+            state.unsynced = 0;
+        }
+
+        let record_offset = state.size;
+        state.size += record.len() as u64;
+        for (mutation, position) in mutations.into_iter().zip(positions) {
+            let replaced = match (mutation, position) {
+                (Mutation::Put { key, .. }, Some(position)) => {
+                    state.index.insert(key, ValuePosition { offset: record_offset + position.offset, length: position.length })
+                }
+                (mutation, _) => state.index.remove(mutation.key()),
+            };
+            state.stale_bytes += replaced.map_or(0, |replaced| replaced.length as u64);
+        }
+        return Ok(());
+    }
+
+    fn scan(&self, start: &str, end: Option<&str>) -> Result<Vec<(String, String)>, QueueError> {
+        let mut state = self.state.lock().map_err(|_| QueueError::Poisoned)?;
+        let positions: Vec<(String, ValuePosition)> = state.index
+            .range::<str, _>((Bound::Included(start), Bound::Unbounded))
+            .take_while(|(key, _)| end.is_none_or(|end| key.as_str() < end))
+            .map(|(key, position)| (key.clone(), *position))
+            .collect();
+        let mut entries = Vec::with_capacity(positions.len());
+        for (key, position) in positions {
+            entries.push((key, read_value(&mut state.file, position)?));
+        }
+        return Ok(entries);
+    }
+}
+
+//the record along with the position of each put's value within it
+pub(crate) fn encode_record(mutations: &[Mutation]) -> (Vec<u8>, Vec<Option<ValuePosition>>) {
+    let mut payload = Vec::new();
+    let mut positions = Vec::with_capacity(mutations.len());
+    payload.extend_from_slice(&(mutations.len() as u32).to_le_bytes());
+    for mutation in mutations {
+        match mutation {
+            Mutation::Put { key, value } => {
+                payload.push(PUT_TAG);
+                encode_string(&mut payload, key);
+                let offset = HEADER_SIZE + payload.len() as u64 + 4;
+                encode_string(&mut payload, value);
+                positions.push(Some(ValuePosition { offset, length: value.len() as u32 }));
+            }
+            Mutation::Delete { key } => {
+                payload.push(DELETE_TAG);
+                encode_string(&mut payload, key);
+                positions.push(None);
+            }
+        }
+    }
+
+    let mut record = Vec::with_capacity(HEADER_SIZE as usize + payload.len());
+    record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&payload);
+    return (record, positions);
+}
+
+//None once the bytes run out or the last record is cut short, a complete record which does not decode is an error
+pub(crate) fn decode_record(bytes: &[u8]) -> io::Result<Option<DecodedRecord>> {
+    if bytes.len() < HEADER_SIZE as usize {
+        return Ok(None);
+    }
+    let crc = read_u32(bytes);
+    let length = read_u32(&bytes[4..]) as usize;
+    let Some(payload) = bytes.get(HEADER_SIZE as usize..HEADER_SIZE as usize + length) else {
+        return Ok(None);
+    };
+    if crc32fast::hash(payload) != crc {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "record with a bad crc"));
+    }
+    let mutations = decode_mutations(payload).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "record with an unknown layout"))?;
+    return Ok(Some(DecodedRecord { mutations, length: HEADER_SIZE + length as u64 }));
+}
+
+fn decode_mutations(payload: &[u8]) -> Option<Vec<(Mutation, Option<ValuePosition>)>> {
+    let (count, mut rest) = decode_u32(payload)?;
+    let mut mutations = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (tag, remaining) = rest.split_first()?;
+        let (key, remaining) = decode_string(remaining)?;
+        match *tag {
+            PUT_TAG => {
+                let offset = HEADER_SIZE + (payload.len() - remaining.len()) as u64 + 4;
+                let (value, remaining) = decode_string(remaining)?;
+                let position = ValuePosition { offset, length: value.len() as u32 };
+                mutations.push((Mutation::Put { key, value }, Some(position)));
+                rest = remaining;
+            }
+            DELETE_TAG => {
+                mutations.push((Mutation::Delete { key }, None));
+                rest = remaining;
+            }
+            _ => return None,
+        }
+    }
+    return Some(mutations);
+}
+
+fn read_value(file: &mut File, position: ValuePosition) -> Result<String, QueueError> {
+    let mut value = vec![0; position.length as usize];
+    file.seek(SeekFrom::Start(position.offset)).map_err(failed)?;
+    file.read_exact(&mut value).map_err(failed)?;
+    return String::from_utf8(value).map_err(|_| QueueError::HandlerFailed(String::from("log structured store holds a value which is not utf-8")));
+}
+
+pub(crate) fn corrupted_record(path: &Path, offset: u64, error: io::Error) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, format!("{} at offset {} of {}", error, offset, path.display()));
+}
+
+fn failed(error: io::Error) -> QueueError {
+    return QueueError::HandlerFailed(format!("log structured store failed: {}", error));
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn put(key: &str, value: &str) -> Mutation {
+        return Mutation::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    #[test]
+    fn test_recover_the_entries_on_open() {
+        let directory = TempDir::new().unwrap();
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap();
+        store.apply(vec![put("key1", "value1"), put("key2", "value2")]).unwrap();
+        store.put(String::from("key1"), String::from("value3")).unwrap();
+        store.delete("key2").unwrap();
+        drop(store);
+
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap();
+        assert_eq!(Ok(Some(String::from("value3"))), store.get("key1"));
+        assert_eq!(Ok(None), store.get("key2"));
+        assert_eq!(Ok(12), store.stale_bytes());
+    }
+
+    #[test]
+    fn test_apply_mutations_of_one_key_in_order() {
+        let directory = TempDir::new().unwrap();
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Never).unwrap();
+        store.put(String::from("key1"), String::from("value1")).unwrap();
+
+        store.apply(vec![put("key1", "value2"), Mutation::Delete { key: String::from("key1") }, put("key1", "value3")]).unwrap();
+        assert_eq!(Ok(Some(String::from("value3"))), store.get("key1"));
+        drop(store);
+
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Never).unwrap();
+        assert_eq!(Ok(Some(String::from("value3"))), store.get("key1"));
+    }
+
+    #[test]
+    fn test_drop_a_torn_record_on_open() {
+        let directory = TempDir::new().unwrap();
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap();
+        store.put(String::from("key1"), String::from("value1")).unwrap();
+        store.apply(vec![put("key2", "value2"), put("key3", "value3")]).unwrap();
+        let size = store.size().unwrap();
+        drop(store);
+
+        let file = OpenOptions::new().write(true).open(directory.path().join(DATA_FILE)).unwrap();
+        file.set_len(size - 3).unwrap();
+
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap();
+        assert_eq!(Ok(Some(String::from("value1"))), store.get("key1"));
+        assert_eq!(Ok(None), store.get("key2"));
+        assert_eq!(Ok(None), store.get("key3"));
+
+        store.put(String::from("key4"), String::from("value4")).unwrap();
+        drop(store);
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap();
+        assert_eq!(Ok(Some(String::from("value4"))), store.get("key4"));
+    }
+
+    #[test]
+    fn test_detect_a_corrupted_record_on_open() {
+        let directory = TempDir::new().unwrap();
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Always).unwrap();
+        store.put(String::from("key1"), String::from("value1")).unwrap();
+        let first_record_length = store.size().unwrap() as usize;
+        store.put(String::from("key2"), String::from("value2")).unwrap();
+        store.put(String::from("key3"), String::from("value3")).unwrap();
+        drop(store);
+
+        let path = directory.path().join(DATA_FILE);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[first_record_length + HEADER_SIZE as usize] ^= 0xFF;
+        fs::write(&path, bytes).unwrap();
+
+        let result = LogStructuredStore::open(directory.path(), FsyncPolicy::Always);
+        assert_eq!(io::ErrorKind::InvalidData, result.err().unwrap().kind());
+    }
+
+    #[test]
+    fn test_compact_away_stale_values() {
+        let directory = TempDir::new().unwrap();
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Never).unwrap();
+        for round in 0..10 {
+            store.put(String::from("key1"), format!("value{}", round)).unwrap();
+            store.put(String::from("key2"), format!("value{}", round)).unwrap();
+        }
+        store.delete("key2").unwrap();
+        let size = store.size().unwrap();
+
+        store.compact().unwrap();
+
+        assert_eq!(true, store.size().unwrap() < size);
+        assert_eq!(Ok(0), store.stale_bytes());
+        assert_eq!(Ok(vec![(String::from("key1"), String::from("value9"))]), store.scan("", None));
+        drop(store);
+
+        let store = LogStructuredStore::open(directory.path(), FsyncPolicy::Never).unwrap();
+        assert_eq!(Ok(Some(String::from("value9"))), store.get("key1"));
+        assert_eq!(Ok(None), store.get("key2"));
+    }
+}
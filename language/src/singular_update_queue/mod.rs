@@ -1,2 +1,19 @@
-mod singular_update_queue;
-mod async_singular_update_queue;
+pub mod singular_update_queue;
+pub mod command;
+pub mod storage_command;
+pub mod queue_error;
+pub mod ticket;
+pub mod write_ahead_log;
+pub mod write_ahead_log_handler;
+pub mod queue_config;
+pub mod priority_lanes;
+pub mod queue_metrics;
+pub mod command_latency;
+pub mod storage_reader;
+pub mod transaction;
+pub mod partitioned_update_queue;
+pub mod snapshot_store;
+pub mod change_feed;
+pub mod deduplicating_handler;
+pub mod key_value_store;
+pub mod log_structured_store;
@@ -0,0 +1,404 @@
+use std::collections::BTreeSet;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use tokio::sync::oneshot;
+
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::command_latency::CommandLatencies;
+use crate::singular_update_queue::queue_config::QueueConfig;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::queue_metrics::QueueMetrics;
+use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+use crate::singular_update_queue::ticket::Ticket;
+
+//the keys a command touches, a command without keys goes to the first partition
+pub trait PartitionKeys {
+    fn partition_keys(&self) -> Vec<&str>;
+}
+
+type Responder<R> = oneshot::Sender<Result<R, QueueError>>;
+
+//commands of a key always land on the same singular queue, so they keep their order while other keys run on other cores,
+//a command spanning partitions is enqueued on each of them and runs once all of them have reached it
+pub struct PartitionedUpdateQueue<C, R> {
+    partitions: Vec<SingularUpdateQueue<Routed<C, R>, ()>>,
+    crossing: Arc<Mutex<()>>,
+    processed: Arc<AtomicUsize>,
+}
+
+enum Routed<C, R> {
+    Single {
+        command: C,
+        respond_back: Responder<R>,
+    },
+    Crossing(Participant<C, R>),
+}
+
+struct PartitionHandler<C, R> {
+    inner: Arc<dyn CommandHandler<C, R>>,
+    processed: Arc<AtomicUsize>,
+}
+
+struct Crossing<C, R> {
+    state: Mutex<CrossingState<C, R>>,
+    changed: Condvar,
+}
+
+struct CrossingState<C, R> {
+    command: Option<C>,
+    respond_back: Option<Responder<R>>,
+    remaining: usize,
+    finished: bool,
+}
+
+//a participant which is dropped without arriving, because its partition was closed, abandons the crossing
+struct Participant<C, R> {
+    crossing: Arc<Crossing<C, R>>,
+    arrived: bool,
+}
+
+impl<C, R> Clone for PartitionedUpdateQueue<C, R> {
+    fn clone(&self) -> Self {
+        return PartitionedUpdateQueue {
+            partitions: self.partitions.clone(),
+            crossing: self.crossing.clone(),
+            processed: self.processed.clone(),
+        };
+    }
+}
+
+impl<C: PartitionKeys + Send + 'static, R: Send + 'static> PartitionedUpdateQueue<C, R> {
+    pub fn init(handler: Arc<dyn CommandHandler<C, R>>, partitions: usize) -> PartitionedUpdateQueue<C, R> {
+        return PartitionedUpdateQueue::init_with_config(handler, partitions, QueueConfig::default());
+    }
+
+    //partitions always run on threads, a crossing command parks the workers it spans which would stall a shared runtime
+    pub fn init_with_config(handler: Arc<dyn CommandHandler<C, R>>, partitions: usize, config: QueueConfig) -> PartitionedUpdateQueue<C, R> {
+        assert!(partitions > 0, "partitions must be greater than zero");
+        let processed = Arc::new(AtomicUsize::new(0));
+        let handler = Arc::new(PartitionHandler { inner: handler, processed: processed.clone() });
+        let partitions = (0..partitions)
+            .map(|_| SingularUpdateQueue::init_with_config(handler.clone(), Executor::Thread, config))
+            .collect();
+        return PartitionedUpdateQueue { partitions, crossing: Arc::new(Mutex::new(())), processed };
+    }
+
+    pub fn partitions(&self) -> usize {
+        return self.partitions.len();
+    }
+
+    pub fn partition_of(&self, key: &str) -> usize {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        return (hasher.finish() % self.partitions.len() as u64) as usize;
+    }
+
+    pub fn execute(&self, command: C) -> Ticket<R> {
+        let partitions: BTreeSet<usize> = command.partition_keys().into_iter().map(|key| self.partition_of(key)).collect();
+        let (respond_back, receiver) = oneshot::channel();
+        if partitions.len() <= 1 {
+            let partition = partitions.into_iter().next().unwrap_or(0);
+            let _ticket = self.partitions[partition].execute(Routed::Single { command, respond_back });
+            return Ticket::new(receiver);
+        }
+
+        let crossing = Arc::new(Crossing {
+            state: Mutex::new(CrossingState {
+                command: Some(command),
+                respond_back: Some(respond_back),
+                remaining: partitions.len(),
+                finished: false,
+            }),
+            changed: Condvar::new(),
+        });
+        //crossing commands are enqueued one at a time, so every partition sees them in the same order and no two of them wait on each other
+        let _guard = self.crossing.lock().unwrap();
+        for partition in partitions {
+            let participant = Participant { crossing: crossing.clone(), arrived: false };
+            let _ticket = self.partitions[partition].execute(Routed::Crossing(participant));
+        }
+        return Ticket::new(receiver);
+    }
+
+    //resolves with the number of commands processed across the partitions, a crossing command counts once
+    pub fn shutdown(&self) -> Ticket<usize> {
+        let tickets: Vec<Ticket<usize>> = self.partitions.iter().map(|partition| partition.shutdown()).collect();
+        let processed = self.processed.clone();
+        let (respond_back, receiver) = oneshot::channel();
+        thread::spawn(move || {
+            for ticket in tickets {
+                if let Err(error) = ticket.wait() {
+                    let _ = respond_back.send(Err(error));
+                    return;
+                }
+            }
+            let _ = respond_back.send(Ok(processed.load(Ordering::SeqCst)));
+        });
+        return Ticket::new(receiver);
+    }
+
+    pub fn is_accepting(&self) -> bool {
+        return self.partitions.iter().all(|partition| partition.is_accepting());
+    }
+
+    pub fn metrics(&self) -> Vec<QueueMetrics> {
+        return self.partitions.iter().map(|partition| partition.metrics()).collect();
+    }
+
+    //a crossing command is recorded once per partition under the "crossing" type
+    pub fn latencies(&self) -> Vec<&CommandLatencies> {
+        return self.partitions.iter().map(|partition| partition.latencies()).collect();
+    }
+}
+
+impl<C: Send, R: Send> CommandHandler<Routed<C, R>, ()> for PartitionHandler<C, R> {
+    fn handle(&self, command: Routed<C, R>) {
+        self.handle_batch(vec![command]);
+    }
+
+    //consecutive single partition commands are still handed to the inner handler as one batch
+    fn handle_batch(&self, commands: Vec<Routed<C, R>>) -> Vec<()> {
+        let count = commands.len();
+        let mut singles = Vec::new();
+        for command in commands {
+            match command {
+                Routed::Single { command, respond_back } => singles.push((command, respond_back)),
+                Routed::Crossing(participant) => {
+                    self.handle_singles(std::mem::take(&mut singles));
+                    participant.arrive(self);
+                }
+            }
+        }
+        self.handle_singles(singles);
+        return vec![(); count];
+    }
+
+    fn command_type(&self, command: &Routed<C, R>) -> &'static str {
+        return match command {
+            Routed::Single { command, .. } => self.inner.command_type(command),
+            Routed::Crossing(_) => "crossing",
+        };
+    }
+}
+
+impl<C, R> PartitionHandler<C, R> {
+    fn handle_singles(&self, singles: Vec<(C, Responder<R>)>) {
+        if singles.is_empty() {
+            return;
+        }
+        let (commands, responders): (Vec<C>, Vec<Responder<R>>) = singles.into_iter().unzip();
+        let expected = commands.len();
+        let responses = panic::catch_unwind(AssertUnwindSafe(|| self.inner.handle_batch(commands)));
+        self.processed.fetch_add(expected, Ordering::SeqCst);
+        match responses {
+            Ok(responses) if responses.len() == expected => {
+                for (respond_back, response) in responders.into_iter().zip(responses) {
+                    let _ = respond_back.send(Ok(response));
+                }
+            }
+            Ok(responses) => {
+                let error = QueueError::HandlerFailed(format!("handler returned {} responses for {} commands", responses.len(), expected));
+                for respond_back in responders {
+                    let _ = respond_back.send(Err(error.clone()));
+                }
+            }
+            Err(_) => {
+                for respond_back in responders {
+                    let _ = respond_back.send(Err(QueueError::HandlerFailed(String::from("handler panicked"))));
+                }
+            }
+        }
+    }
+
+    fn handle_crossing(&self, command: C) -> Result<R, QueueError> {
+        let response = panic::catch_unwind(AssertUnwindSafe(|| self.inner.handle(command)));
+        self.processed.fetch_add(1, Ordering::SeqCst);
+        return response.map_err(|_| QueueError::HandlerFailed(String::from("handler panicked")));
+    }
+}
+
+impl<C, R> Participant<C, R> {
+    //the last partition to arrive runs the command, the others wait for it so none of them runs ahead
+    fn arrive(mut self, handler: &PartitionHandler<C, R>) {
+        self.arrived = true;
+        let mut state = self.crossing.state.lock().unwrap();
+        state.remaining -= 1;
+        if state.remaining == 0 && !state.finished {
+            if let (Some(command), Some(respond_back)) = (state.command.take(), state.respond_back.take()) {
+                let _ = respond_back.send(handler.handle_crossing(command));
+            }
+            state.finished = true;
+            self.crossing.changed.notify_all();
+            return;
+        }
+        while !state.finished {
+            state = self.crossing.changed.wait(state).unwrap();
+        }
+    }
+}
+
+impl<C, R> Drop for Participant<C, R> {
+    fn drop(&mut self) {
+        if self.arrived {
+            return;
+        }
+        let mut state = self.crossing.state.lock().unwrap();
+        state.finished = true;
+        state.command = None;
+        if let Some(respond_back) = state.respond_back.take() {
+            let _ = respond_back.send(Err(QueueError::Closed));
+        }
+        self.crossing.changed.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+    use std::time::Duration;
+
+    use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Status, Storage, StorageCommand};
+    use crate::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+
+    use super::*;
+
+    fn queue(partitions: usize) -> (PartitionedUpdateQueue<StorageCommand, Status>, Storage) {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let queue = PartitionedUpdateQueue::init(Arc::new(InMemoryStorageHandler::new(storage.clone())), partitions);
+        return (queue, storage);
+    }
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    //two keys which hash to different partitions
+    fn keys_on_different_partitions(queue: &PartitionedUpdateQueue<StorageCommand, Status>) -> (String, String) {
+        let first = String::from("key0");
+        let second = (1..).map(|index| format!("key{}", index)).find(|key| queue.partition_of(key) != queue.partition_of(&first)).unwrap();
+        return (first, second);
+    }
+
+    #[test]
+    fn test_keep_the_order_of_a_key() {
+        let (queue, storage) = queue(4);
+        let tickets: Vec<Ticket<Status>> = (0..100)
+            .flat_map(|version| (0..8).map(move |key| (version, key)))
+            .map(|(version, key)| queue.execute(put(&format!("key{}", key), &format!("value{}", version))))
+            .collect();
+        for ticket in tickets {
+            assert_eq!(Ok(Status::Ok), ticket.wait());
+        }
+
+        let storage = storage.read().unwrap();
+        for key in 0..8 {
+            assert_eq!("value99", storage.get(&format!("key{}", key)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_spread_keys_over_partitions() {
+        let (queue, _) = queue(4);
+        for index in 0..200 {
+            queue.execute(put(&format!("key{}", index), "value")).wait().unwrap();
+        }
+        assert_eq!(Ok(200), queue.shutdown().wait());
+
+        let metrics = queue.metrics();
+        assert_eq!(4, metrics.len());
+        assert_eq!(true, metrics.iter().all(|metrics| metrics.processed > 0));
+        assert_eq!(200, metrics.iter().map(|metrics| metrics.processed).sum::<usize>());
+    }
+
+    #[test]
+    fn test_run_a_transaction_across_partitions() {
+        let (queue, storage) = queue(4);
+        let (first, second) = keys_on_different_partitions(&queue);
+        let _first_ticket = queue.execute(put(&first, "value1"));
+        let _second_ticket = queue.execute(put(&second, "value2"));
+
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::ValueEquals { key: first.clone(), value: String::from("value1") })
+            .with_precondition(Precondition::ValueEquals { key: second.clone(), value: String::from("value2") })
+            .with_operation(Operation::Put { key: first.clone(), value: String::from("value2") })
+            .with_operation(Operation::Put { key: second.clone(), value: String::from("value1") });
+        let status = queue.execute(StorageCommand::Transaction(transaction)).wait();
+        let after = queue.execute(StorageCommand::Get { key: second.clone() }).wait();
+
+        assert_eq!(Ok(Status::Committed(vec![Status::Ok, Status::Ok])), status);
+        assert_eq!(Ok(Status::Value(Some(String::from("value1")))), after);
+        assert_eq!("value2", storage.read().unwrap().get(&first).unwrap());
+    }
+
+    #[test]
+    fn test_concurrent_crossing_commands_do_not_deadlock() {
+        let (queue, _) = queue(4);
+        let (first, second) = keys_on_different_partitions(&queue);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let queue = queue.clone();
+                let (first, second) = (first.clone(), second.clone());
+                return thread::spawn(move || {
+                    for _ in 0..50 {
+                        let transaction = Transaction::new()
+                            .with_operation(Operation::Put { key: first.clone(), value: String::from("value") })
+                            .with_operation(Operation::Put { key: second.clone(), value: String::from("value") });
+                        let status = queue.execute(StorageCommand::Transaction(transaction)).wait_timeout(Duration::from_secs(5));
+                        assert_eq!(Ok(Status::Committed(vec![Status::Ok, Status::Ok])), status);
+                    }
+                });
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(Ok(200), queue.shutdown().wait());
+    }
+
+    struct SlowHandler {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+    }
+
+    impl CommandHandler<StorageCommand, Status> for SlowHandler {
+        fn handle(&self, _command: StorageCommand) -> Status {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(5));
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            return Status::Ok;
+        }
+    }
+
+    #[test]
+    fn test_handle_partitions_in_parallel() {
+        let handler = Arc::new(SlowHandler { running: AtomicUsize::new(0), max_running: AtomicUsize::new(0) });
+        let queue = PartitionedUpdateQueue::init(handler.clone(), 4);
+
+        let tickets: Vec<Ticket<Status>> = (0..64).map(|index| queue.execute(put(&format!("key{}", index), "value"))).collect();
+        for ticket in tickets {
+            assert_eq!(Ok(Status::Ok), ticket.wait());
+        }
+        assert_eq!(true, handler.max_running.load(Ordering::SeqCst) > 1);
+    }
+
+    #[test]
+    fn test_execute_after_shutdown() {
+        let (queue, _) = queue(2);
+        assert_eq!(Ok(Status::Ok), queue.execute(put("key1", "value1")).wait());
+        assert_eq!(Ok(1), queue.shutdown().wait());
+
+        let transaction = Transaction::new()
+            .with_operation(Operation::Put { key: String::from("key1"), value: String::from("value") })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value") });
+        assert_eq!(false, queue.is_accepting());
+        assert_eq!(Err(QueueError::Closed), queue.execute(put("key1", "value1")).wait());
+        assert_eq!(Err(QueueError::Closed), queue.execute(StorageCommand::Transaction(transaction)).wait());
+    }
+}
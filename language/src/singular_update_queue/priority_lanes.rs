@@ -0,0 +1,183 @@
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::Receiver;
+
+pub const LANES: usize = 3;
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum SchedulingPolicy {
+    //always serves the highest lane with a pending command, a steady stream of urgent commands starves the lower lanes
+    #[default]
+    Strict,
+    //serves up to weight commands of each lane per round, every lane with pending commands makes progress
+    WeightedFair { high: usize, normal: usize, low: usize },
+}
+
+//the receiving ends of the lanes, owned by the single worker which decides the order commands are applied in
+pub(crate) struct Lanes<T> {
+    receivers: [Receiver<T>; LANES],
+    policy: SchedulingPolicy,
+    credits: [usize; LANES],
+}
+
+impl Priority {
+    pub fn lane(&self) -> usize {
+        return match self {
+            Priority::High => 0,
+            Priority::Normal => 1,
+            Priority::Low => 2,
+        };
+    }
+}
+
+impl SchedulingPolicy {
+    //strict priority is a round in which no lane ever runs out of credits
+    pub fn weights(&self) -> [usize; LANES] {
+        return match self {
+            SchedulingPolicy::Strict => [usize::MAX; LANES],
+            SchedulingPolicy::WeightedFair { high, normal, low } => [*high, *normal, *low],
+        };
+    }
+}
+
+impl<T> Lanes<T> {
+    pub(crate) fn new(receivers: [Receiver<T>; LANES], policy: SchedulingPolicy) -> Lanes<T> {
+        return Lanes { receivers, policy, credits: policy.weights() };
+    }
+
+    //Disconnected only once every lane lost its senders and is drained, a closed lane may still report Empty
+    pub(crate) fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        for lane in 0..LANES {
+            if self.credits[lane] == 0 {
+                continue;
+            }
+            if let Ok(value) = self.receivers[lane].try_recv() {
+                self.credits[lane] -= 1;
+                return Ok(value);
+            }
+        }
+
+        //the lanes with credits left are empty, a new round starts
+        self.credits = self.policy.weights();
+        let mut disconnected = 0;
+        for lane in 0..LANES {
+            match self.receivers[lane].try_recv() {
+                Ok(value) => {
+                    self.credits[lane] -= 1;
+                    return Ok(value);
+                }
+                Err(TryRecvError::Disconnected) => disconnected += 1,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        return Err(if disconnected == LANES { TryRecvError::Disconnected } else { TryRecvError::Empty });
+    }
+
+    //waits for the first command on any lane, meant to be called once try_recv found all of them empty
+    pub(crate) async fn recv(&mut self) -> Option<T> {
+        let [high, normal, low] = &mut self.receivers;
+        let (lane, value) = tokio::select! {
+            biased;
+            Some(value) = high.recv() => (0, value),
+            Some(value) = normal.recv() => (1, value),
+            Some(value) = low.recv() => (2, value),
+            else => return None,
+        };
+        self.credits[lane] = self.credits[lane].saturating_sub(1);
+        return Some(value);
+    }
+
+    pub(crate) fn close(&mut self) {
+        for receiver in self.receivers.iter_mut() {
+            receiver.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc::{self, Sender};
+
+    use super::*;
+
+    fn lanes(policy: SchedulingPolicy) -> (Lanes<u64>, [Sender<u64>; LANES]) {
+        let (high_sender, high) = mpsc::channel(16);
+        let (normal_sender, normal) = mpsc::channel(16);
+        let (low_sender, low) = mpsc::channel(16);
+        return (Lanes::new([high, normal, low], policy), [high_sender, normal_sender, low_sender]);
+    }
+
+    fn drain(lanes: &mut Lanes<u64>) -> Vec<u64> {
+        let mut values = Vec::new();
+        while let Ok(value) = lanes.try_recv() {
+            values.push(value);
+        }
+        return values;
+    }
+
+    #[test]
+    fn test_strict_priority() {
+        let (mut lanes, senders) = lanes(SchedulingPolicy::Strict);
+        senders[Priority::Low.lane()].try_send(30).unwrap();
+        senders[Priority::Normal.lane()].try_send(20).unwrap();
+        senders[Priority::High.lane()].try_send(10).unwrap();
+        senders[Priority::High.lane()].try_send(11).unwrap();
+
+        assert_eq!(vec![10, 11, 20, 30], drain(&mut lanes));
+    }
+
+    #[test]
+    fn test_weighted_fair() {
+        let (mut lanes, senders) = lanes(SchedulingPolicy::WeightedFair { high: 2, normal: 1, low: 1 });
+        for value in 10..15 {
+            senders[Priority::High.lane()].try_send(value).unwrap();
+        }
+        for value in 20..22 {
+            senders[Priority::Normal.lane()].try_send(value).unwrap();
+        }
+        for value in 30..32 {
+            senders[Priority::Low.lane()].try_send(value).unwrap();
+        }
+
+        assert_eq!(vec![10, 11, 20, 30, 12, 13, 21, 31, 14], drain(&mut lanes));
+    }
+
+    #[tokio::test]
+    async fn test_drain_closed_lanes() {
+        let (mut lanes, senders) = lanes(SchedulingPolicy::Strict);
+        senders[Priority::Low.lane()].try_send(30).unwrap();
+        lanes.close();
+
+        assert_eq!(true, senders[Priority::High.lane()].try_send(10).is_err());
+        assert_eq!(Ok(30), lanes.try_recv());
+        assert_eq!(None, lanes.recv().await);
+    }
+
+    #[test]
+    fn test_disconnected_once_every_sender_is_dropped() {
+        let (mut lanes, senders) = lanes(SchedulingPolicy::Strict);
+        senders[Priority::Low.lane()].try_send(30).unwrap();
+        drop(senders);
+
+        assert_eq!(Ok(30), lanes.try_recv());
+        assert_eq!(Err(TryRecvError::Disconnected), lanes.try_recv());
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_a_command_on_any_lane() {
+        let (mut lanes, senders) = lanes(SchedulingPolicy::Strict);
+        let low = senders[Priority::Low.lane()].clone();
+        tokio::spawn(async move { low.send(30).await.unwrap() });
+
+        assert_eq!(Some(30), lanes.recv().await);
+        drop(senders);
+        assert_eq!(None, lanes.recv().await);
+    }
+}
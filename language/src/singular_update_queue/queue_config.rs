@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::singular_update_queue::priority_lanes::SchedulingPolicy;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct QueueConfig {
+    capacity: usize,
+    max_batch_size: usize,
+    max_batch_delay: Duration,
+    scheduling: SchedulingPolicy,
+}
+
+impl Default for QueueConfig {
+    fn default() -> Self {
+        return QueueConfig { capacity: 1024, max_batch_size: 1, max_batch_delay: Duration::ZERO, scheduling: SchedulingPolicy::Strict };
+    }
+}
+
+impl QueueConfig {
+    //number of commands which can wait in each priority lane before execute starts applying backpressure
+    pub fn with_capacity(mut self, capacity: usize) -> QueueConfig {
+        assert!(capacity > 0, "capacity must be greater than zero");
+        self.capacity = capacity;
+        return self;
+    }
+
+    pub fn with_max_batch_size(mut self, max_batch_size: usize) -> QueueConfig {
+        assert!(max_batch_size > 0, "max batch size must be greater than zero");
+        self.max_batch_size = max_batch_size;
+        return self;
+    }
+
+    //how long the worker waits for more commands before handing a partially filled batch to the handler
+    pub fn with_max_batch_delay(mut self, max_batch_delay: Duration) -> QueueConfig {
+        self.max_batch_delay = max_batch_delay;
+        return self;
+    }
+
+    pub fn with_scheduling(mut self, scheduling: SchedulingPolicy) -> QueueConfig {
+        assert!(scheduling.weights().iter().all(|weight| *weight > 0), "every lane must have a weight greater than zero");
+        self.scheduling = scheduling;
+        return self;
+    }
+
+    pub fn capacity(&self) -> usize {
+        return self.capacity;
+    }
+
+    pub fn max_batch_size(&self) -> usize {
+        return self.max_batch_size;
+    }
+
+    pub fn max_batch_delay(&self) -> Duration {
+        return self.max_batch_delay;
+    }
+
+    pub fn scheduling(&self) -> SchedulingPolicy {
+        return self.scheduling;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config_handles_one_command_at_a_time() {
+        let config = QueueConfig::default();
+        assert_eq!(1, config.max_batch_size());
+        assert_eq!(Duration::ZERO, config.max_batch_delay());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reject_a_zero_capacity() {
+        let _ = QueueConfig::default().with_capacity(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reject_an_empty_batch() {
+        let _ = QueueConfig::default().with_max_batch_size(0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_reject_a_lane_without_weight() {
+        let _ = QueueConfig::default().with_scheduling(SchedulingPolicy::WeightedFair { high: 4, normal: 1, low: 0 });
+    }
+}
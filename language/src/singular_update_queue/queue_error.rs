@@ -0,0 +1,41 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum QueueError {
+    Closed,
+    Full,
+    HandlerFailed(String),
+    Poisoned,
+    Timeout,
+    NotLeader,
+    PreconditionFailed(usize),
+    ChangeUnavailable(u64),
+}
+
+impl Display for QueueError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            QueueError::Closed => write!(f, "singular update queue is closed"),
+            QueueError::Full => write!(f, "singular update queue is full"),
+            QueueError::HandlerFailed(reason) => write!(f, "command handler failed: {}", reason),
+            QueueError::Poisoned => write!(f, "storage lock is poisoned"),
+            QueueError::Timeout => write!(f, "timed out waiting for the response"),
+            QueueError::NotLeader => write!(f, "node is not the leader"),
+            QueueError::PreconditionFailed(index) => write!(f, "precondition {} of the transaction does not hold", index),
+            QueueError::ChangeUnavailable(sequence) => write!(f, "change {} is no longer retained", sequence),
+        };
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_a_handler_failure() {
+        let error = QueueError::HandlerFailed(String::from("boom"));
+        assert_eq!("command handler failed: boom", error.to_string());
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct QueueMetrics {
+    pub capacity: usize,
+    pub depth: usize,
+    pub enqueued: usize,
+    pub rejected: usize,
+    pub processed: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct QueueCounters {
+    enqueued: AtomicUsize,
+    rejected: AtomicUsize,
+    processed: AtomicUsize,
+}
+
+impl QueueCounters {
+    pub fn enqueued(&self) {
+        self.enqueued.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn rejected(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn processed(&self, count: usize) {
+        self.processed.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn processed_count(&self) -> usize {
+        return self.processed.load(Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, capacity: usize, depth: usize) -> QueueMetrics {
+        return QueueMetrics {
+            capacity,
+            depth,
+            enqueued: self.enqueued.load(Ordering::Relaxed),
+            rejected: self.rejected.load(Ordering::Relaxed),
+            processed: self.processed_count(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_counters() {
+        let counters = QueueCounters::default();
+        counters.enqueued();
+        counters.enqueued();
+        counters.rejected();
+        counters.processed(2);
+
+        let metrics = counters.snapshot(8, 0);
+        assert_eq!(QueueMetrics { capacity: 8, depth: 0, enqueued: 2, rejected: 1, processed: 2 }, metrics);
+    }
+}
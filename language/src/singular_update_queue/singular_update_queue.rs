@@ -1,120 +1,403 @@
-use std::collections::HashMap;
-use std::sync::{Arc, mpsc, RwLock};
-use std::sync::mpsc::{Receiver, Sender};
+use std::any::Any;
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant as StdInstant};
+
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{mpsc, oneshot};
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::error::{SendTimeoutError, TryRecvError, TrySendError};
+use tokio::time::{self, Instant};
+
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::command_latency::{CommandLatencies, CommandTrace};
+use crate::singular_update_queue::priority_lanes::{Lanes, Priority, LANES};
+use crate::singular_update_queue::queue_config::QueueConfig;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::queue_metrics::{QueueCounters, QueueMetrics};
+use crate::singular_update_queue::ticket::Ticket;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Executor {
+    Thread,
+    Task,
+}
+
+type ShutdownListener = oneshot::Sender<Result<usize, QueueError>>;
+
+//drives the timers of execute_timeout and Ticket::wait_timeout for every queue, so that a call does not have to build a runtime of its own
+static TIMEOUT_RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+pub(crate) fn timeout_runtime() -> &'static Runtime {
+    return TIMEOUT_RUNTIME.get_or_init(|| {
+        return Builder::new_multi_thread().worker_threads(1).thread_name("queue-timeout").enable_time().build().unwrap();
+    });
+}
+
+pub struct SingularUpdateQueue<C, R> {
+    senders: [Sender<Envelope<C, R>>; LANES],
+    shutdown_sender: UnboundedSender<ShutdownListener>,
+    accepting: Arc<AtomicBool>,
+    counters: Arc<QueueCounters>,
+    latencies: Arc<CommandLatencies>,
+    response: PhantomData<fn() -> R>,
+}
 
-type Storage = Arc<RwLock<HashMap<String, String>>>;
+struct Envelope<C, R> {
+    command: C,
+    respond_back: oneshot::Sender<Result<R, QueueError>>,
+    trace: CommandTrace,
+}
 
-trait CommandHandler: Send + Sync {
-    fn handle(&self, command: Command) -> Status;
+struct Worker<C, R> {
+    handler: Arc<dyn CommandHandler<C, R>>,
+    lanes: Lanes<Envelope<C, R>>,
+    shutdown_receiver: UnboundedReceiver<ShutdownListener>,
+    config: QueueConfig,
+    counters: Arc<QueueCounters>,
+    latencies: Arc<CommandLatencies>,
+    shutdown_listeners: Vec<ShutdownListener>,
 }
 
-struct InMemoryStorageHandler {
-    storage: Storage,
+impl<C, R> Clone for SingularUpdateQueue<C, R> {
+    fn clone(&self) -> Self {
+        return SingularUpdateQueue {
+            senders: self.senders.clone(),
+            shutdown_sender: self.shutdown_sender.clone(),
+            accepting: self.accepting.clone(),
+            counters: self.counters.clone(),
+            latencies: self.latencies.clone(),
+            response: PhantomData,
+        };
+    }
 }
 
-impl CommandHandler for InMemoryStorageHandler {
-    fn handle(&self, command: Command) -> Status {
-        let cloned = self.storage.clone();
-        return match command {
-            Command::Put { key, value, respond_back: _ } => {
-                cloned.write().unwrap().insert(key, value);
-                Status::Ok
+impl<C: Send + 'static, R: Send + 'static> SingularUpdateQueue<C, R> {
+    pub fn init(handler: Arc<dyn CommandHandler<C, R>>, executor: Executor) -> SingularUpdateQueue<C, R> {
+        return SingularUpdateQueue::spin_receiver(handler, executor, QueueConfig::default());
+    }
+
+    pub fn init_with_config(handler: Arc<dyn CommandHandler<C, R>>, executor: Executor, config: QueueConfig) -> SingularUpdateQueue<C, R> {
+        return SingularUpdateQueue::spin_receiver(handler, executor, config);
+    }
+
+    //shutdown requests travel on their own unbounded channel so that a full queue can not hold them back
+    fn spin_receiver(handler: Arc<dyn CommandHandler<C, R>>, executor: Executor, config: QueueConfig) -> SingularUpdateQueue<C, R> {
+        let mut receivers = Vec::with_capacity(LANES);
+        let senders: [Sender<Envelope<C, R>>; LANES] = std::array::from_fn(|_| {
+            let (sender, receiver) = mpsc::channel(config.capacity());
+            receivers.push(receiver);
+            return sender;
+        });
+        let receivers: [Receiver<Envelope<C, R>>; LANES] = receivers.try_into().ok().unwrap();
+        let (shutdown_sender, shutdown_receiver) = mpsc::unbounded_channel();
+        let counters = Arc::new(QueueCounters::default());
+        let latencies = Arc::new(CommandLatencies::default());
+        let singular_update_queue = SingularUpdateQueue {
+            senders,
+            shutdown_sender,
+            accepting: Arc::new(AtomicBool::new(true)),
+            counters: counters.clone(),
+            latencies: latencies.clone(),
+            response: PhantomData,
+        };
+        let lanes = Lanes::new(receivers, config.scheduling());
+        let worker = Worker { handler, lanes, shutdown_receiver, config, counters, latencies, shutdown_listeners: Vec::new() };
+
+        match executor {
+            Executor::Thread => {
+                thread::spawn(move || {
+                    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+                    runtime.block_on(worker.run());
+                });
             }
-            Command::Delete { key, respond_back: _ } => {
-                cloned.write().unwrap().remove(&key);
-                Status::Ok
+            Executor::Task => {
+                tokio::spawn(worker.run());
             }
+        }
+        return singular_update_queue;
+    }
+
+    //blocks the calling thread while the queue is full, async callers should use execute_async instead
+    pub fn execute(&self, command: C) -> Ticket<R> {
+        return self.execute_with_priority(command, Priority::Normal);
+    }
+
+    //the order between lanes is up to the scheduling policy, commands of a lane are applied in the order they were enqueued
+    pub fn execute_with_priority(&self, command: C, priority: Priority) -> Ticket<R> {
+        if !self.is_accepting() {
+            return Ticket::closed();
+        }
+        let sender = &self.senders[priority.lane()];
+        let (envelope, ticket) = Envelope::new(command);
+        let envelope = match sender.try_send(envelope) {
+            Ok(()) => {
+                self.counters.enqueued();
+                return ticket;
+            }
+            Err(TrySendError::Closed(_)) => return ticket,
+            Err(TrySendError::Full(envelope)) => envelope,
         };
+        if sender.blocking_send(envelope).is_ok() {
+            self.counters.enqueued();
+        }
+        return ticket;
     }
-}
 
-#[derive(Clone)]
-struct SingularUpdateQueue {
-    sender: Sender<Command>,
-}
+    pub fn try_execute(&self, command: C) -> Result<Ticket<R>, QueueError> {
+        return self.try_execute_with_priority(command, Priority::Normal);
+    }
 
-#[derive(Debug)]
-enum Command {
-    Put {
-        key: String,
-        value: String,
-        respond_back: Sender<Status>,
-    },
-    Delete {
-        key: String,
-        respond_back: Sender<Status>,
-    },
-}
+    pub fn try_execute_with_priority(&self, command: C, priority: Priority) -> Result<Ticket<R>, QueueError> {
+        if !self.is_accepting() {
+            return Err(QueueError::Closed);
+        }
+        let (envelope, ticket) = Envelope::new(command);
+        return match self.senders[priority.lane()].try_send(envelope) {
+            Ok(()) => {
+                self.counters.enqueued();
+                Ok(ticket)
+            }
+            Err(TrySendError::Full(_)) => {
+                self.counters.rejected();
+                Err(QueueError::Full)
+            }
+            Err(TrySendError::Closed(_)) => Err(QueueError::Closed),
+        };
+    }
+
+    //waits at most timeout for room in the queue, like execute it must not be called from within an async context
+    pub fn execute_timeout(&self, command: C, timeout: Duration) -> Result<Ticket<R>, QueueError> {
+        return self.execute_timeout_with_priority(command, timeout, Priority::Normal);
+    }
 
-impl Command {
-    fn get_respond_back(&self) -> Sender<Status> {
-        return match self {
-            Command::Put { key: _key, value: _value, respond_back } => {
-                respond_back.clone()
+    pub fn execute_timeout_with_priority(&self, command: C, timeout: Duration, priority: Priority) -> Result<Ticket<R>, QueueError> {
+        if !self.is_accepting() {
+            return Err(QueueError::Closed);
+        }
+        let sender = &self.senders[priority.lane()];
+        let (envelope, ticket) = Envelope::new(command);
+        let envelope = match sender.try_send(envelope) {
+            Ok(()) => {
+                self.counters.enqueued();
+                return Ok(ticket);
+            }
+            Err(TrySendError::Closed(_)) => return Err(QueueError::Closed),
+            Err(TrySendError::Full(envelope)) => envelope,
+        };
+        return match timeout_runtime().block_on(sender.send_timeout(envelope, timeout)) {
+            Ok(()) => {
+                self.counters.enqueued();
+                Ok(ticket)
             }
-            Command::Delete { key: _key, respond_back } => {
-                respond_back.clone()
+            Err(SendTimeoutError::Timeout(_)) => {
+                self.counters.rejected();
+                Err(QueueError::Timeout)
             }
+            Err(SendTimeoutError::Closed(_)) => Err(QueueError::Closed),
         };
     }
+
+    pub async fn execute_async(&self, command: C) -> Result<R, QueueError> {
+        return self.execute_async_with_priority(command, Priority::Normal).await;
+    }
+
+    pub async fn execute_async_with_priority(&self, command: C, priority: Priority) -> Result<R, QueueError> {
+        if !self.is_accepting() {
+            return Err(QueueError::Closed);
+        }
+        let (envelope, ticket) = Envelope::new(command);
+        if self.senders[priority.lane()].send(envelope).await.is_err() {
+            return Err(QueueError::Closed);
+        }
+        self.counters.enqueued();
+        return ticket.await;
+    }
+
+    //stops accepting commands, the worker drains the ones already enqueued and resolves the ticket with the number of commands it processed
+    pub fn shutdown(&self) -> Ticket<usize> {
+        self.accepting.store(false, Ordering::SeqCst);
+        let (respond_back, receiver) = oneshot::channel();
+        let _ = self.shutdown_sender.send(respond_back);
+        return Ticket::new(receiver);
+    }
+
+    pub fn is_accepting(&self) -> bool {
+        return self.accepting.load(Ordering::SeqCst);
+    }
+
+    //the capacity is the one of each lane, the depth counts the commands waiting in all of them
+    pub fn metrics(&self) -> QueueMetrics {
+        let capacity = self.senders[0].max_capacity();
+        let depth = self.senders.iter().map(|sender| sender.max_capacity() - sender.capacity()).sum();
+        return self.counters.snapshot(capacity, depth);
+    }
+
+    //latency histograms per command type, named by CommandHandler::command_type
+    pub fn latencies(&self) -> &CommandLatencies {
+        return &self.latencies;
+    }
 }
 
-#[derive(Debug, Eq, PartialEq)]
-enum Status {
-    Ok
+impl<C, R> Envelope<C, R> {
+    //dropping an envelope which could not be enqueued drops its responder, which resolves the ticket with QueueError::Closed
+    fn new(command: C) -> (Envelope<C, R>, Ticket<R>) {
+        let (respond_back, receiver) = oneshot::channel();
+        return (Envelope { command, respond_back, trace: CommandTrace::start() }, Ticket::new(receiver));
+    }
 }
 
-impl SingularUpdateQueue {
-    fn init(handler: Arc<dyn CommandHandler>) -> SingularUpdateQueue {
-        return SingularUpdateQueue::spin_receiver(handler);
+impl<C: Send + 'static, R: Send + 'static> Worker<C, R> {
+    //after a shutdown request the lanes are closed, they keep yielding the enqueued commands and then recv returns None
+    async fn run(mut self) {
+        loop {
+            if self.shutdown_listeners.is_empty() {
+                if let Ok(listener) = self.shutdown_receiver.try_recv() {
+                    self.begin_shutdown(listener);
+                }
+            }
+            let envelope = match self.lanes.try_recv() {
+                Ok(envelope) => Some(envelope),
+                Err(TryRecvError::Disconnected) => None,
+                Err(TryRecvError::Empty) => tokio::select! {
+                    biased;
+                    Some(listener) = self.shutdown_receiver.recv(), if self.shutdown_listeners.is_empty() => {
+                        self.begin_shutdown(listener);
+                        continue;
+                    }
+                    envelope = self.lanes.recv() => envelope,
+                },
+            };
+            let Some(envelope) = envelope else {
+                break;
+            };
+            let mut batch = Vec::with_capacity(self.config.max_batch_size());
+            batch.push(envelope);
+            self.fill(&mut batch).await;
+            self.handle(batch);
+        }
+        while let Ok(listener) = self.shutdown_receiver.try_recv() {
+            self.shutdown_listeners.push(listener);
+        }
+        let processed = self.counters.processed_count();
+        for respond_back in self.shutdown_listeners.drain(..) {
+            let _ = respond_back.send(Ok(processed));
+        }
     }
 
-    //loop pending
-    fn spin_receiver(handler: Arc<dyn CommandHandler>) -> SingularUpdateQueue {
-        let (sender, receiver): (Sender<Command>, Receiver<Command>) = mpsc::channel();
-        let singular_update_queue = SingularUpdateQueue { sender };
-        let handler_clone = handler.clone();
+    fn begin_shutdown(&mut self, listener: ShutdownListener) {
+        self.shutdown_listeners.push(listener);
+        self.lanes.close();
+    }
 
-        thread::spawn(move || {
-            for (_, command) in receiver.into_iter().enumerate() {
-                let respond_back = &command.get_respond_back();
-                let status = handler_clone.handle(command);
-                respond_back.send(status).unwrap();
+    async fn fill(&mut self, batch: &mut Vec<Envelope<C, R>>) {
+        let deadline = Instant::now() + self.config.max_batch_delay();
+        while batch.len() < self.config.max_batch_size() {
+            match self.lanes.try_recv() {
+                Ok(envelope) => batch.push(envelope),
+                Err(TryRecvError::Disconnected) => return,
+                Err(TryRecvError::Empty) => {
+                    if let Ok(listener) = self.shutdown_receiver.try_recv() {
+                        self.begin_shutdown(listener);
+                    }
+                    if !self.shutdown_listeners.is_empty() || Instant::now() >= deadline {
+                        return;
+                    }
+                    match time::timeout_at(deadline, self.lanes.recv()).await {
+                        Ok(Some(envelope)) => batch.push(envelope),
+                        _ => return,
+                    }
+                }
             }
-        });
-        return singular_update_queue;
+        }
+    }
+
+    //a panicking handler fails only the commands of its own batch, the worker keeps serving the rest of the queue
+    fn handle(&mut self, batch: Vec<Envelope<C, R>>) {
+        let dequeued_at = StdInstant::now();
+        let mut commands = Vec::with_capacity(batch.len());
+        let mut responders = Vec::with_capacity(batch.len());
+        for mut envelope in batch {
+            envelope.trace.dequeued(dequeued_at);
+            responders.push((self.handler.command_type(&envelope.command), envelope.respond_back, envelope.trace));
+            commands.push(envelope.command);
+        }
+        let expected = commands.len();
+
+        let handler = &self.handler;
+        let responses = panic::catch_unwind(AssertUnwindSafe(|| handler.handle_batch(commands)));
+        let applied_at = StdInstant::now();
+        self.counters.processed(expected);
+        match responses {
+            Ok(responses) => {
+                let received = responses.len();
+                let mut responses = responses.into_iter();
+                for (command_type, respond_back, trace) in responders {
+                    let response = responses.next().ok_or_else(|| QueueError::HandlerFailed(
+                        format!("handler returned {} responses for {} commands", received, expected)
+                    ));
+                    let _ = respond_back.send(response);
+                    self.record(command_type, trace, applied_at);
+                }
+            }
+            Err(cause) => {
+                let error = QueueError::HandlerFailed(panic_message(cause));
+                for (command_type, respond_back, trace) in responders {
+                    let _ = respond_back.send(Err(error.clone()));
+                    self.record(command_type, trace, applied_at);
+                }
+            }
+        }
     }
 
-    fn execute(&self, command: Command) {
-        return self.sender.clone().send(command).unwrap();
+    fn record(&self, command_type: &'static str, mut trace: CommandTrace, applied_at: StdInstant) {
+        trace.applied(applied_at);
+        trace.responded(StdInstant::now());
+        self.latencies.record(command_type, &trace);
     }
 }
 
+fn panic_message(cause: Box<dyn Any + Send>) -> String {
+    if let Some(message) = cause.downcast_ref::<&str>() {
+        return String::from(*message);
+    }
+    if let Some(message) = cause.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return String::from("handler panicked");
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashMap;
+    use std::sync::{mpsc as std_mpsc, Mutex, RwLock};
+    use std::sync::atomic::AtomicU64;
+    use std::time::Duration;
+
+    use crate::singular_update_queue::command_latency::Stage;
+    use crate::singular_update_queue::priority_lanes::SchedulingPolicy;
+    use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Status, StorageCommand};
+
     use super::*;
 
     #[test]
     fn test_get_with_insert_by_a_single_task() {
         let storage = Arc::new(RwLock::new(HashMap::new()));
         let cloned_storage = storage.clone();
-        let handler = Arc::new(InMemoryStorageHandler { storage: storage.clone() });
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
 
-        let singular_update_queue = SingularUpdateQueue::init(handler);
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
         let cloned_queue = singular_update_queue.clone();
 
-        let (sender, receiver) = mpsc::channel();
-        let respond_back = sender.clone();
-
         let handle = thread::spawn(move || {
-            cloned_queue.execute(Command::Put {
+            let ticket = cloned_queue.execute(StorageCommand::Put {
                 key: String::from("key1"),
                 value: String::from("value1"),
-                respond_back,
             });
-            assert_eq!(Status::Ok, receiver.recv().unwrap());
+            assert_eq!(Ok(Status::Ok), ticket.wait());
         });
 
         let _ = handle.join();
@@ -126,34 +409,26 @@ mod tests {
     fn test_get_with_insert_by_multiple_tasks() {
         let storage = Arc::new(RwLock::new(HashMap::new()));
         let cloned_storage = storage.clone();
-        let handler = Arc::new(InMemoryStorageHandler { storage: storage.clone() });
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
 
-        let singular_update_queue = SingularUpdateQueue::init(handler);
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
         let cloned_queue_one = singular_update_queue.clone();
         let cloned_queue_two = singular_update_queue.clone();
 
-        let (sender, receiver) = mpsc::channel();
-        let respond_back = sender.clone();
-
         let handle_one = thread::spawn( move || {
-            cloned_queue_one.execute(Command::Put {
+            let ticket = cloned_queue_one.execute(StorageCommand::Put {
                 key: String::from("key1"),
                 value: String::from("value1"),
-                respond_back,
             });
-            assert_eq!(Status::Ok, receiver.recv().unwrap());
+            assert_eq!(Ok(Status::Ok), ticket.wait());
         });
 
-        let (sender, receiver) = mpsc::channel();
-        let respond_back = sender.clone();
-
         let handle_two = thread::spawn( move || {
-            cloned_queue_two.execute(Command::Put {
+            let ticket = cloned_queue_two.execute(StorageCommand::Put {
                 key: String::from("key2"),
                 value: String::from("value2"),
-                respond_back,
             });
-            assert_eq!(Status::Ok, receiver.recv().unwrap());
+            assert_eq!(Ok(Status::Ok), ticket.wait());
         });
 
         let _ = handle_one.join();
@@ -168,41 +443,549 @@ mod tests {
     fn test_get_with_insert_and_delete_by_multiple_tasks() {
         let storage = Arc::new(RwLock::new(HashMap::new()));
         let cloned_storage = storage.clone();
-        let handler = Arc::new(InMemoryStorageHandler { storage: storage.clone() });
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
 
-        let singular_update_queue = SingularUpdateQueue::init(handler);
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
         let cloned_queue_one = singular_update_queue.clone();
         let cloned_queue_two = singular_update_queue.clone();
 
-        let (sender, receiver) = mpsc::channel();
-        let respond_back = sender.clone();
-
         let handle_one = thread::spawn( move || {
-            cloned_queue_one.execute(Command::Put {
+            let ticket = cloned_queue_one.execute(StorageCommand::Put {
                 key: String::from("key1"),
                 value: String::from("value1"),
-                respond_back,
             });
-            assert_eq!(Status::Ok, receiver.recv().unwrap());
+            assert_eq!(Ok(Status::Ok), ticket.wait());
         });
-
-        thread::sleep(Duration::from_millis(30));
-
-        let (sender, receiver) = mpsc::channel();
-        let respond_back = sender.clone();
+        //the put has to be applied before the delete is enqueued, the simulation module covers the interleavings
+        let _ = handle_one.join();
 
         let handle_two = thread::spawn( move || {
-            cloned_queue_two.execute(Command::Delete {
+            let ticket = cloned_queue_two.execute(StorageCommand::Delete {
                 key: String::from("key1"),
-                respond_back,
             });
-            assert_eq!(Status::Ok, receiver.recv().unwrap());
+            assert_eq!(Ok(Status::Ok), ticket.wait());
         });
 
-        let _ = handle_one.join();
         let _ = handle_two.join();
 
         let read_storage = cloned_storage.read().unwrap();
         assert_eq!(None, read_storage.get("key1"));
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_get_with_insert_by_a_single_tokio_task() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let cloned_storage = storage.clone();
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        let singular_update_queue = Arc::new(SingularUpdateQueue::init(handler, Executor::Task));
+        let cloned_queue = singular_update_queue.clone();
+
+        let handle = tokio::spawn(async move {
+            let status = cloned_queue.execute_async(StorageCommand::Put {
+                key: String::from("key1"),
+                value: String::from("value1"),
+            }).await;
+            assert_eq!(Ok(Status::Ok), status);
+        });
+
+        let _ = handle.await;
+        let read_storage = cloned_storage.read().unwrap();
+        assert_eq!("value1", read_storage.get("key1").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_get_with_insert_by_multiple_tokio_tasks() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let cloned_storage = storage.clone();
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        let singular_update_queue = Arc::new(SingularUpdateQueue::init(handler, Executor::Task));
+        let cloned_queue_one = singular_update_queue.clone();
+        let cloned_queue_two = singular_update_queue.clone();
+
+        let handle_one = tokio::spawn(async move {
+            let status = cloned_queue_one.execute_async(StorageCommand::Put {
+                key: String::from("key1"),
+                value: String::from("value1"),
+            }).await;
+            assert_eq!(Ok(Status::Ok), status);
+        });
+        let handle_two = tokio::spawn(async move {
+            let status = cloned_queue_two.execute_async(StorageCommand::Put {
+                key: String::from("key2"),
+                value: String::from("value2"),
+            }).await;
+            assert_eq!(Ok(Status::Ok), status);
+        });
+
+        let _ = tokio::join!(handle_one, handle_two);
+        let read_storage = cloned_storage.read().unwrap();
+        assert_eq!("value1", read_storage.get("key1").unwrap());
+        assert_eq!("value2", read_storage.get("key2").unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_get_with_insert_and_delete_by_multiple_tokio_tasks() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let cloned_storage = storage.clone();
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        let singular_update_queue = Arc::new(SingularUpdateQueue::init(handler, Executor::Task));
+        let cloned_queue_one = singular_update_queue.clone();
+        let cloned_queue_two = singular_update_queue.clone();
+
+        let handle_one = tokio::spawn(async move {
+            let status = cloned_queue_one.execute_async(StorageCommand::Put {
+                key: String::from("key1"),
+                value: String::from("value1"),
+            }).await;
+            assert_eq!(Ok(Status::Ok), status);
+        });
+        let handle_two = tokio::spawn(async move {
+            let status = cloned_queue_two.execute_async(StorageCommand::Delete {
+                key: String::from("key1"),
+            }).await;
+            assert_eq!(Ok(Status::Ok), status);
+        });
+
+        let _ = tokio::join!(handle_one, handle_two);
+        let read_storage = cloned_storage.read().unwrap();
+        assert_eq!(None, read_storage.get("key1"));
+    }
+
+    #[tokio::test]
+    async fn test_await_a_ticket() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Task);
+
+        let ticket = singular_update_queue.execute(StorageCommand::Put {
+            key: String::from("key1"),
+            value: String::from("value1"),
+        });
+
+        assert_eq!(Ok(Status::Ok), ticket.await);
+        assert_eq!("value1", storage.read().unwrap().get("key1").unwrap());
+    }
+
+    enum CounterCommand {
+        Increment { by: u64 }
+    }
+
+    struct CounterHandler {
+        counter: AtomicU64,
+    }
+
+    impl CommandHandler<CounterCommand, u64> for CounterHandler {
+        fn handle(&self, command: CounterCommand) -> u64 {
+            return match command {
+                CounterCommand::Increment { by } => self.counter.fetch_add(by, Ordering::SeqCst) + by
+            };
+        }
+    }
+
+    #[test]
+    fn test_execute_a_user_defined_command() {
+        let handler = Arc::new(CounterHandler { counter: AtomicU64::new(0) });
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+        let first = singular_update_queue.execute(CounterCommand::Increment { by: 5 });
+        let second = singular_update_queue.execute(CounterCommand::Increment { by: 10 });
+
+        assert_eq!(Ok(5), first.wait());
+        assert_eq!(Ok(15), second.wait());
+    }
+
+    #[test]
+    fn test_shutdown_drains_enqueued_commands() {
+        let handler = Arc::new(CounterHandler { counter: AtomicU64::new(0) });
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+        let tickets: Vec<Ticket<u64>> = (1..=10)
+            .map(|_| singular_update_queue.execute(CounterCommand::Increment { by: 1 }))
+            .collect();
+        let shutdown = singular_update_queue.shutdown();
+
+        assert_eq!(Ok(10), shutdown.wait());
+        for (index, ticket) in tickets.into_iter().enumerate() {
+            assert_eq!(Ok(index as u64 + 1), ticket.wait());
+        }
+    }
+
+    #[test]
+    fn test_execute_after_shutdown() {
+        let handler = Arc::new(CounterHandler { counter: AtomicU64::new(0) });
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+        let cloned_queue = singular_update_queue.clone();
+
+        assert_eq!(Ok(0), singular_update_queue.shutdown().wait());
+        assert_eq!(false, cloned_queue.is_accepting());
+        assert_eq!(Err(QueueError::Closed), cloned_queue.execute(CounterCommand::Increment { by: 1 }).wait());
+    }
+
+    #[test]
+    fn test_shutdown_twice() {
+        let handler = Arc::new(CounterHandler { counter: AtomicU64::new(0) });
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+        let _ticket = singular_update_queue.execute(CounterCommand::Increment { by: 1 });
+        let first = singular_update_queue.shutdown();
+        let second = singular_update_queue.shutdown();
+
+        assert_eq!(Ok(1), first.wait());
+        let second = second.wait();
+        assert_eq!(true, second == Ok(1) || second == Err(QueueError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_a_tokio_task() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Task);
+
+        let put = singular_update_queue.execute(StorageCommand::Put {
+            key: String::from("key1"),
+            value: String::from("value1"),
+        });
+        let shutdown = singular_update_queue.shutdown();
+
+        assert_eq!(Ok(1), shutdown.await);
+        assert_eq!(Ok(Status::Ok), put.await);
+        assert_eq!(Err(QueueError::Closed), singular_update_queue.execute_async(StorageCommand::Delete {
+            key: String::from("key1"),
+        }).await);
+        assert_eq!("value1", storage.read().unwrap().get("key1").unwrap());
+    }
+
+    struct PanickingHandler;
+
+    impl CommandHandler<u64, u64> for PanickingHandler {
+        fn handle(&self, command: u64) -> u64 {
+            if command == 0 {
+                panic!("can not handle zero");
+            }
+            return command;
+        }
+    }
+
+    #[test]
+    fn test_worker_survives_a_panicking_handler() {
+        let singular_update_queue = SingularUpdateQueue::init(Arc::new(PanickingHandler), Executor::Thread);
+
+        let failed = singular_update_queue.execute(0);
+        let succeeded = singular_update_queue.execute(1);
+
+        assert_eq!(Err(QueueError::HandlerFailed(String::from("can not handle zero"))), failed.wait());
+        assert_eq!(Ok(1), succeeded.wait());
+        assert_eq!(Ok(2), singular_update_queue.shutdown().wait());
+    }
+
+    #[tokio::test]
+    async fn test_tokio_worker_survives_a_panicking_handler() {
+        let singular_update_queue = SingularUpdateQueue::init(Arc::new(PanickingHandler), Executor::Task);
+
+        assert_eq!(Err(QueueError::HandlerFailed(String::from("can not handle zero"))), singular_update_queue.execute_async(0).await);
+        assert_eq!(Ok(1), singular_update_queue.execute_async(1).await);
+    }
+
+    #[test]
+    fn test_caller_dropping_its_ticket() {
+        let handler = Arc::new(CounterHandler { counter: AtomicU64::new(0) });
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+        drop(singular_update_queue.execute(CounterCommand::Increment { by: 1 }));
+        assert_eq!(Ok(2), singular_update_queue.execute(CounterCommand::Increment { by: 1 }).wait());
+    }
+
+    struct BatchRecordingHandler {
+        batch_sizes: Mutex<Vec<usize>>,
+    }
+
+    impl CommandHandler<u64, u64> for BatchRecordingHandler {
+        fn handle(&self, command: u64) -> u64 {
+            return command;
+        }
+
+        fn handle_batch(&self, commands: Vec<u64>) -> Vec<u64> {
+            self.batch_sizes.lock().unwrap().push(commands.len());
+            return commands.into_iter().map(|command| self.handle(command)).collect();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handle_pending_commands_in_batches() {
+        let handler = Arc::new(BatchRecordingHandler { batch_sizes: Mutex::new(Vec::new()) });
+        let config = QueueConfig::default().with_max_batch_size(4);
+        let singular_update_queue = SingularUpdateQueue::init_with_config(handler.clone(), Executor::Task, config);
+
+        let tickets: Vec<Ticket<u64>> = (0..10).map(|command| singular_update_queue.execute(command)).collect();
+        for (command, ticket) in tickets.into_iter().enumerate() {
+            assert_eq!(Ok(command as u64), ticket.await);
+        }
+
+        assert_eq!(vec![4, 4, 2], *handler.batch_sizes.lock().unwrap());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_for_a_batch_to_fill_up_to_the_max_delay() {
+        let handler = Arc::new(BatchRecordingHandler { batch_sizes: Mutex::new(Vec::new()) });
+        let config = QueueConfig::default().with_max_batch_size(4).with_max_batch_delay(Duration::from_millis(50));
+        let singular_update_queue = SingularUpdateQueue::init_with_config(handler.clone(), Executor::Task, config);
+
+        let started_at = Instant::now();
+        assert_eq!(Ok(1), singular_update_queue.execute_async(1).await);
+
+        assert_eq!(true, started_at.elapsed() >= Duration::from_millis(50));
+        assert_eq!(vec![1], *handler.batch_sizes.lock().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_in_batches() {
+        let handler = Arc::new(BatchRecordingHandler { batch_sizes: Mutex::new(Vec::new()) });
+        let config = QueueConfig::default().with_max_batch_size(8);
+        let singular_update_queue = SingularUpdateQueue::init_with_config(handler.clone(), Executor::Task, config);
+
+        let tickets: Vec<Ticket<u64>> = (0..5).map(|command| singular_update_queue.execute(command)).collect();
+        assert_eq!(Ok(5), singular_update_queue.shutdown().await);
+        for ticket in tickets {
+            assert_eq!(true, ticket.await.is_ok());
+        }
+        assert_eq!(vec![5], *handler.batch_sizes.lock().unwrap());
+    }
+
+    struct ShortBatchHandler;
+
+    impl CommandHandler<u64, u64> for ShortBatchHandler {
+        fn handle(&self, command: u64) -> u64 {
+            return command;
+        }
+
+        fn handle_batch(&self, commands: Vec<u64>) -> Vec<u64> {
+            return commands.into_iter().take(1).collect();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fail_commands_without_a_response() {
+        let config = QueueConfig::default().with_max_batch_size(2);
+        let singular_update_queue = SingularUpdateQueue::init_with_config(Arc::new(ShortBatchHandler), Executor::Task, config);
+
+        let first = singular_update_queue.execute(1);
+        let second = singular_update_queue.execute(2);
+
+        assert_eq!(Ok(1), first.await);
+        assert_eq!(Err(QueueError::HandlerFailed(String::from("handler returned 1 responses for 2 commands"))), second.await);
+    }
+
+    struct GatedHandler {
+        gate: Mutex<std_mpsc::Receiver<()>>,
+    }
+
+    impl CommandHandler<u64, u64> for GatedHandler {
+        fn handle(&self, command: u64) -> u64 {
+            let _ = self.gate.lock().unwrap().recv();
+            return command;
+        }
+    }
+
+    //the worker is parked inside the handler with the first command, the rest of the commands fill the queue
+    fn full_queue(capacity: usize) -> (SingularUpdateQueue<u64, u64>, std_mpsc::Sender<()>, Vec<Ticket<u64>>) {
+        let (open, gate) = std_mpsc::channel();
+        let handler = Arc::new(GatedHandler { gate: Mutex::new(gate) });
+        let config = QueueConfig::default().with_capacity(capacity);
+        let singular_update_queue = SingularUpdateQueue::init_with_config(handler, Executor::Thread, config);
+
+        let mut tickets = vec![singular_update_queue.execute(0)];
+        while singular_update_queue.metrics().depth > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        for command in 1..=capacity as u64 {
+            tickets.push(singular_update_queue.execute(command));
+        }
+        return (singular_update_queue, open, tickets);
+    }
+
+    #[test]
+    fn test_try_execute_on_a_full_queue() {
+        let (singular_update_queue, open, tickets) = full_queue(2);
+
+        assert_eq!(Err(QueueError::Full), singular_update_queue.try_execute(3).map(|_| ()));
+        let metrics = singular_update_queue.metrics();
+        assert_eq!(QueueMetrics { capacity: 2, depth: 2, enqueued: 3, rejected: 1, processed: 0 }, metrics);
+
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        for (command, ticket) in tickets.into_iter().enumerate() {
+            assert_eq!(Ok(command as u64), ticket.wait());
+        }
+        assert_eq!(3, singular_update_queue.metrics().processed);
+        assert_eq!(0, singular_update_queue.metrics().depth);
+    }
+
+    #[test]
+    fn test_execute_timeout_on_a_full_queue() {
+        let (singular_update_queue, open, _tickets) = full_queue(1);
+
+        let result = singular_update_queue.execute_timeout(2, Duration::from_millis(20));
+        assert_eq!(Err(QueueError::Timeout), result.map(|_| ()));
+
+        open.send(()).unwrap();
+        let ticket = singular_update_queue.execute_timeout(2, Duration::from_secs(5)).unwrap();
+        open.send(()).unwrap();
+        open.send(()).unwrap();
+        assert_eq!(Ok(2), ticket.wait());
+    }
+
+    #[test]
+    fn test_execute_timeout_in_another_lane_of_a_full_queue() {
+        let (singular_update_queue, open, _tickets) = full_queue(1);
+
+        let ticket = singular_update_queue.execute_timeout_with_priority(2, Duration::from_millis(20), Priority::High).unwrap();
+        let result = singular_update_queue.execute_timeout_with_priority(3, Duration::from_millis(20), Priority::High);
+        assert_eq!(Err(QueueError::Timeout), result.map(|_| ()));
+
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        assert_eq!(Ok(2), ticket.wait());
+    }
+
+    #[test]
+    fn test_execute_blocks_until_there_is_room() {
+        let (singular_update_queue, open, _tickets) = full_queue(1);
+        let cloned_queue = singular_update_queue.clone();
+
+        let handle = thread::spawn(move || cloned_queue.execute(2).wait());
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(false, handle.is_finished());
+
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        assert_eq!(Ok(2), handle.join().unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_execute_async_waits_for_room() {
+        let (singular_update_queue, open, _tickets) = full_queue(1);
+
+        let waiting = tokio::time::timeout(Duration::from_millis(20), singular_update_queue.execute_async(2)).await;
+        assert_eq!(true, waiting.is_err());
+
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        assert_eq!(Ok(3), singular_update_queue.execute_async(3).await);
+    }
+
+    #[test]
+    fn test_shutdown_a_full_queue() {
+        let (singular_update_queue, open, _tickets) = full_queue(2);
+
+        let shutdown = singular_update_queue.shutdown();
+        assert_eq!(Err(QueueError::Closed), singular_update_queue.try_execute(3).map(|_| ()));
+        for _ in 0..3 {
+            open.send(()).unwrap();
+        }
+        assert_eq!(Ok(3), shutdown.wait());
+    }
+
+    struct OrderRecordingHandler {
+        gate: Mutex<std_mpsc::Receiver<()>>,
+        order: Mutex<Vec<u64>>,
+    }
+
+    impl CommandHandler<u64, u64> for OrderRecordingHandler {
+        fn handle(&self, command: u64) -> u64 {
+            let _ = self.gate.lock().unwrap().recv();
+            self.order.lock().unwrap().push(command);
+            return command;
+        }
+    }
+
+    //the worker is parked inside the handler with a low priority command while the rest of the commands are enqueued
+    fn apply_in_order(config: QueueConfig, commands: Vec<(u64, Priority)>) -> Vec<u64> {
+        let (open, gate) = std_mpsc::channel();
+        let handler = Arc::new(OrderRecordingHandler { gate: Mutex::new(gate), order: Mutex::new(Vec::new()) });
+        let singular_update_queue = SingularUpdateQueue::init_with_config(handler.clone(), Executor::Thread, config);
+
+        let mut tickets = vec![singular_update_queue.execute_with_priority(0, Priority::Low)];
+        while singular_update_queue.metrics().depth > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        for (command, priority) in commands {
+            tickets.push(singular_update_queue.execute_with_priority(command, priority));
+        }
+        for _ in 0..tickets.len() {
+            open.send(()).unwrap();
+        }
+        for ticket in tickets {
+            assert_eq!(true, ticket.wait().is_ok());
+        }
+        return handler.order.lock().unwrap().clone();
+    }
+
+    #[test]
+    fn test_apply_urgent_commands_first() {
+        let commands = vec![(30, Priority::Low), (20, Priority::Normal), (31, Priority::Low), (10, Priority::High)];
+
+        let order = apply_in_order(QueueConfig::default(), commands);
+
+        assert_eq!(vec![0, 10, 20, 30, 31], order);
+    }
+
+    #[test]
+    fn test_share_the_worker_between_lanes_by_weight() {
+        let config = QueueConfig::default().with_scheduling(SchedulingPolicy::WeightedFair { high: 2, normal: 1, low: 1 });
+        let mut commands: Vec<(u64, Priority)> = (30..33).map(|command| (command, Priority::Low)).collect();
+        commands.extend((10..15).map(|command| (command, Priority::High)));
+
+        let order = apply_in_order(config, commands);
+
+        assert_eq!(vec![0, 10, 11, 12, 13, 30, 14, 31, 32], order);
+    }
+
+    #[tokio::test]
+    async fn test_execute_async_with_priority() {
+        let handler = Arc::new(BatchRecordingHandler { batch_sizes: Mutex::new(Vec::new()) });
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Task);
+
+        assert_eq!(Ok(1), singular_update_queue.execute_async_with_priority(1, Priority::High).await);
+        assert_eq!(Ok(2), singular_update_queue.try_execute_with_priority(2, Priority::Low).unwrap().await);
+        assert_eq!(Ok(2), singular_update_queue.shutdown().await);
+    }
+
+    #[test]
+    fn test_record_latencies_per_command_type() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = Arc::new(InMemoryStorageHandler::new(storage));
+        let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+        for index in 0..3 {
+            let ticket = singular_update_queue.execute(StorageCommand::Put { key: format!("key{}", index), value: String::from("value") });
+            assert_eq!(Ok(Status::Ok), ticket.wait());
+        }
+        let ticket = singular_update_queue.execute(StorageCommand::Get { key: String::from("key1") });
+        assert_eq!(Ok(Status::Value(Some(String::from("value")))), ticket.wait());
+        assert_eq!(Ok(4), singular_update_queue.shutdown().wait());
+
+        let latencies = singular_update_queue.latencies();
+        assert_eq!(vec!["get", "put"], latencies.command_types());
+        for stage in Stage::all() {
+            assert_eq!(3, latencies.histogram("put", stage).unwrap().count());
+            assert_eq!(1, latencies.histogram("get", stage).unwrap().count());
+        }
+        let total = latencies.histogram("put", Stage::Total).unwrap();
+        assert_eq!(true, total.sum() >= latencies.histogram("put", Stage::Handle).unwrap().sum());
+        assert_eq!(true, latencies.export_text().contains("singular_update_queue_command_latency_seconds_count{command=\"get\",stage=\"total\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_record_latencies_of_a_panicking_handler() {
+        let singular_update_queue = SingularUpdateQueue::init(Arc::new(PanickingHandler), Executor::Task);
+
+        assert_eq!(true, singular_update_queue.execute_async(0).await.is_err());
+        assert_eq!(Ok(1), singular_update_queue.shutdown().await);
+
+        assert_eq!(1, singular_update_queue.latencies().histogram("command", Stage::Total).unwrap().count());
+    }
+}
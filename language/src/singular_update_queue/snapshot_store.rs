@@ -0,0 +1,164 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use crate::raft::state_machine::Snapshot;
+use crate::singular_update_queue::write_ahead_log::{decode_string, decode_u32, encode_string, read_u32, read_u64};
+
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+const TEMPORARY_EXTENSION: &str = "tmp";
+
+//a point-in-time copy of the storage along with the index of the last log entry applied to it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StorageSnapshot {
+    pub last_index: u64,
+    pub entries: Snapshot,
+}
+
+//file layout: crc32 | last index | entry count | (key, has value, value?, version)*, the crc covers everything after it
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    directory: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn open(directory: &Path) -> io::Result<SnapshotStore> {
+        fs::create_dir_all(directory)?;
+        return Ok(SnapshotStore { directory: directory.to_path_buf() });
+    }
+
+    //written to a temporary file and renamed, so a crash never leaves a partial snapshot behind, older snapshots are removed
+    pub fn save(&self, snapshot: &StorageSnapshot) -> io::Result<()> {
+        let body = encode_snapshot(snapshot);
+        let temporary = self.directory.join(format!("{:020}.{}", snapshot.last_index, TEMPORARY_EXTENSION));
+        let mut file = File::create(&temporary)?;
+        file.write_all(&crc32fast::hash(&body).to_le_bytes())?;
+        file.write_all(&body)?;
+        file.sync_all()?;
+        fs::rename(&temporary, self.directory.join(format!("{:020}.{}", snapshot.last_index, SNAPSHOT_EXTENSION)))?;
+
+        for (last_index, path) in self.snapshot_paths()? {
+            if last_index < snapshot.last_index {
+                fs::remove_file(path)?;
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn latest(&self) -> io::Result<Option<StorageSnapshot>> {
+        let path = match self.snapshot_paths()?.pop() {
+            Some((_, path)) => path,
+            None => return Ok(None),
+        };
+        let bytes = fs::read(&path)?;
+        if bytes.len() < 4 || crc32fast::hash(&bytes[4..]) != read_u32(&bytes) {
+            return Err(corrupted(&path));
+        }
+        return decode_snapshot(&bytes[4..]).map(Some).ok_or_else(|| corrupted(&path));
+    }
+
+    fn snapshot_paths(&self) -> io::Result<Vec<(u64, PathBuf)>> {
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(SNAPSHOT_EXTENSION) {
+                continue;
+            }
+            if let Some(last_index) = path.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok()) {
+                snapshots.push((last_index, path));
+            }
+        }
+        snapshots.sort_by_key(|(last_index, _)| *last_index);
+        return Ok(snapshots);
+    }
+}
+
+fn encode_snapshot(snapshot: &StorageSnapshot) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&snapshot.last_index.to_le_bytes());
+    body.extend_from_slice(&(snapshot.entries.len() as u32).to_le_bytes());
+    for (key, value, version) in &snapshot.entries {
+        encode_string(&mut body, key);
+        match value {
+            Some(value) => {
+                body.push(1);
+                encode_string(&mut body, value);
+            }
+            None => body.push(0),
+        }
+        body.extend_from_slice(&version.to_le_bytes());
+    }
+    return body;
+}
+
+fn decode_snapshot(body: &[u8]) -> Option<StorageSnapshot> {
+    if body.len() < 8 {
+        return None;
+    }
+    let last_index = read_u64(body);
+    let (count, mut rest) = decode_u32(&body[8..])?;
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let (key, remaining) = decode_string(rest)?;
+        let (value, remaining) = match remaining.split_first()? {
+            (1, remaining) => {
+                let (value, remaining) = decode_string(remaining)?;
+                (Some(value), remaining)
+            }
+            (0, remaining) => (None, remaining),
+            _ => return None,
+        };
+        if remaining.len() < 8 {
+            return None;
+        }
+        entries.push((key, value, read_u64(remaining)));
+        rest = &remaining[8..];
+    }
+    return Some(StorageSnapshot { last_index, entries });
+}
+
+fn corrupted(snapshot: &Path) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, format!("corrupted snapshot {}", snapshot.display()));
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn snapshot(last_index: u64) -> StorageSnapshot {
+        return StorageSnapshot {
+            last_index,
+            entries: vec![(String::from("key1"), Some(String::from("value1")), 1), (String::from("key2"), None, 3)],
+        };
+    }
+
+    #[test]
+    fn test_save_and_load_the_latest_snapshot() {
+        let directory = TempDir::new().unwrap();
+        let store = SnapshotStore::open(directory.path()).unwrap();
+        assert_eq!(None, store.latest().unwrap());
+
+        store.save(&snapshot(4)).unwrap();
+        store.save(&snapshot(9)).unwrap();
+
+        assert_eq!(Some(snapshot(9)), store.latest().unwrap());
+        assert_eq!(1, fs::read_dir(directory.path()).unwrap().count());
+    }
+
+    #[test]
+    fn test_detect_a_corrupted_snapshot() {
+        let directory = TempDir::new().unwrap();
+        let store = SnapshotStore::open(directory.path()).unwrap();
+        store.save(&snapshot(4)).unwrap();
+
+        let (_, path) = store.snapshot_paths().unwrap().pop().unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(&path, bytes).unwrap();
+
+        assert_eq!(io::ErrorKind::InvalidData, store.latest().err().unwrap().kind());
+    }
+}
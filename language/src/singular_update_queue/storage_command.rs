@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::singular_update_queue::change_feed::ChangeFeed;
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::partitioned_update_queue::PartitionKeys;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::transaction::{Operation, Transaction};
+
+pub type Storage = Arc<RwLock<HashMap<String, String>>>;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StorageCommand {
+    Put {
+        key: String,
+        value: String,
+    },
+    Delete {
+        key: String,
+    },
+    Get {
+        key: String,
+    },
+    Transaction(Transaction),
+}
+
+impl StorageCommand {
+    //a read leaves the storage untouched, so it never needs to be logged or replicated
+    pub fn is_read(&self) -> bool {
+        return matches!(self, StorageCommand::Get { .. });
+    }
+
+    pub fn command_type(&self) -> &'static str {
+        return match self {
+            StorageCommand::Put { .. } => "put",
+            StorageCommand::Delete { .. } => "delete",
+            StorageCommand::Get { .. } => "get",
+            StorageCommand::Transaction(_) => "transaction",
+        };
+    }
+}
+
+impl PartitionKeys for StorageCommand {
+    fn partition_keys(&self) -> Vec<&str> {
+        return match self {
+            StorageCommand::Put { key, .. } => vec![key],
+            StorageCommand::Delete { key } => vec![key],
+            StorageCommand::Get { key } => vec![key],
+            StorageCommand::Transaction(transaction) => transaction.keys(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Status {
+    Ok,
+    Value(Option<String>),
+    //a committed transaction responds with the status of every operation, in order
+    Committed(Vec<Status>),
+    Failed(QueueError),
+}
+
+//versions are tracked next to the storage so that transactions can compare and set
+pub struct InMemoryStorageHandler {
+    storage: Storage,
+    versions: RwLock<HashMap<String, u64>>,
+    change_feed: Option<Arc<ChangeFeed>>,
+}
+
+impl InMemoryStorageHandler {
+    pub fn new(storage: Storage) -> InMemoryStorageHandler {
+        return InMemoryStorageHandler { storage, versions: RwLock::new(HashMap::new()), change_feed: None };
+    }
+
+    //every applied put and every delete of a present key is recorded on the feed, in the order it is applied
+    pub fn with_change_feed(mut self, change_feed: Arc<ChangeFeed>) -> InMemoryStorageHandler {
+        self.change_feed = Some(change_feed);
+        return self;
+    }
+
+    pub fn storage(&self) -> &Storage {
+        return &self.storage;
+    }
+
+    pub fn version(&self, key: &str) -> Result<u64, QueueError> {
+        let versions = self.versions.read().map_err(|_| QueueError::Poisoned)?;
+        return Ok(versions.get(key).copied().unwrap_or(0));
+    }
+
+    pub fn versions(&self) -> Result<HashMap<String, u64>, QueueError> {
+        return Ok(self.versions.read().map_err(|_| QueueError::Poisoned)?.clone());
+    }
+
+    //replaces the storage along with the versions, used when a node installs a snapshot
+    pub fn replace(&self, entries: Vec<(String, Option<String>, u64)>) -> Result<(), QueueError> {
+        let mut storage = self.storage.write().map_err(|_| QueueError::Poisoned)?;
+        let mut versions = self.versions.write().map_err(|_| QueueError::Poisoned)?;
+        storage.clear();
+        versions.clear();
+        for (key, value, version) in entries {
+            versions.insert(key.clone(), version);
+            if let Some(value) = value {
+                storage.insert(key, value);
+            }
+        }
+        return Ok(());
+    }
+
+    fn read(&self, key: &str) -> Result<Status, QueueError> {
+        let storage = self.storage.read().map_err(|_| QueueError::Poisoned)?;
+        return Ok(Status::Value(storage.get(key).cloned()));
+    }
+
+    fn write(&self, operation: Operation) -> Result<Status, QueueError> {
+        let mut storage = self.storage.write().map_err(|_| QueueError::Poisoned)?;
+        let mut versions = self.versions.write().map_err(|_| QueueError::Poisoned)?;
+        return Ok(apply(&mut storage, &mut versions, self.change_feed.as_deref(), operation));
+    }
+
+    fn execute(&self, transaction: Transaction) -> Result<Status, QueueError> {
+        let mut storage = self.storage.write().map_err(|_| QueueError::Poisoned)?;
+        let mut versions = self.versions.write().map_err(|_| QueueError::Poisoned)?;
+        if let Some(precondition) = transaction.failed_precondition(&storage, &versions) {
+            return Err(QueueError::PreconditionFailed(precondition));
+        }
+        let statuses = transaction.operations
+            .into_iter()
+            .map(|operation| apply(&mut storage, &mut versions, self.change_feed.as_deref(), operation))
+            .collect();
+        return Ok(Status::Committed(statuses));
+    }
+}
+
+impl CommandHandler<StorageCommand, Status> for InMemoryStorageHandler {
+    fn handle(&self, command: StorageCommand) -> Status {
+        let result = match command {
+            StorageCommand::Get { key } => self.read(&key),
+            StorageCommand::Put { key, value } => self.write(Operation::Put { key, value }),
+            StorageCommand::Delete { key } => self.write(Operation::Delete { key }),
+            StorageCommand::Transaction(transaction) => self.execute(transaction),
+        };
+        return result.unwrap_or_else(Status::Failed);
+    }
+
+    fn command_type(&self, command: &StorageCommand) -> &'static str {
+        return command.command_type();
+    }
+}
+
+fn apply(
+    storage: &mut HashMap<String, String>,
+    versions: &mut HashMap<String, u64>,
+    change_feed: Option<&ChangeFeed>,
+    operation: Operation,
+) -> Status {
+    return match operation {
+        Operation::Put { key, value } => {
+            *versions.entry(key.clone()).or_insert(0) += 1;
+            let old_value = storage.insert(key.clone(), value.clone());
+            if let Some(change_feed) = change_feed {
+                change_feed.record(key, old_value, Some(value));
+            }
+            Status::Ok
+        }
+        Operation::Delete { key } => {
+            //the version outlives the value, so a key put again after a delete never repeats a version it had before
+            if let Some(version) = versions.get_mut(&key) {
+                *version += 1;
+            }
+            let old_value = storage.remove(&key);
+            if let (Some(change_feed), Some(old_value)) = (change_feed, old_value) {
+                change_feed.record(key, Some(old_value), None);
+            }
+            Status::Ok
+        }
+        Operation::Get { key } => Status::Value(storage.get(&key).cloned()),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::singular_update_queue::transaction::Precondition;
+
+    use super::*;
+
+    #[test]
+    fn test_handle_put() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = InMemoryStorageHandler::new(storage.clone());
+
+        let status = handler.handle(StorageCommand::Put {
+            key: String::from("key1"),
+            value: String::from("value1"),
+        });
+
+        assert_eq!(Status::Ok, status);
+        assert_eq!("value1", storage.read().unwrap().get("key1").unwrap());
+    }
+
+    #[test]
+    fn test_handle_delete() {
+        let storage = Arc::new(RwLock::new(HashMap::from([(String::from("key1"), String::from("value1"))])));
+        let handler = InMemoryStorageHandler::new(storage.clone());
+
+        let status = handler.handle(StorageCommand::Delete { key: String::from("key1") });
+
+        assert_eq!(Status::Ok, status);
+        assert_eq!(None, storage.read().unwrap().get("key1"));
+    }
+
+    #[test]
+    fn test_handle_get() {
+        let storage = Arc::new(RwLock::new(HashMap::from([(String::from("key1"), String::from("value1"))])));
+        let handler = InMemoryStorageHandler::new(storage);
+
+        assert_eq!(Status::Value(Some(String::from("value1"))), handler.handle(StorageCommand::Get { key: String::from("key1") }));
+        assert_eq!(Status::Value(None), handler.handle(StorageCommand::Get { key: String::from("key2") }));
+    }
+
+    #[test]
+    fn test_commit_a_transaction() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = InMemoryStorageHandler::new(storage.clone());
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::ValueEquals { key: String::from("key1"), value: String::from("value1") })
+            .with_precondition(Precondition::VersionMatches { key: String::from("key1"), version: 1 })
+            .with_operation(Operation::Put { key: String::from("key1"), value: String::from("value2") })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value1") })
+            .with_operation(Operation::Get { key: String::from("key1") });
+        let status = handler.handle(StorageCommand::Transaction(transaction));
+
+        assert_eq!(Status::Committed(vec![Status::Ok, Status::Ok, Status::Value(Some(String::from("value2")))]), status);
+        assert_eq!("value2", storage.read().unwrap().get("key1").unwrap());
+        assert_eq!(Ok(2), handler.version("key1"));
+        assert_eq!(Ok(1), handler.version("key2"));
+    }
+
+    #[test]
+    fn test_reject_a_transaction_as_a_whole() {
+        let storage = Arc::new(RwLock::new(HashMap::new()));
+        let handler = InMemoryStorageHandler::new(storage.clone());
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::KeyExists { key: String::from("key1") })
+            .with_precondition(Precondition::VersionMatches { key: String::from("key1"), version: 5 })
+            .with_operation(Operation::Delete { key: String::from("key1") })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value2") });
+        let status = handler.handle(StorageCommand::Transaction(transaction));
+
+        assert_eq!(Status::Failed(QueueError::PreconditionFailed(1)), status);
+        assert_eq!(1, storage.read().unwrap().len());
+        assert_eq!(Ok(1), handler.version("key1"));
+    }
+
+    #[test]
+    fn test_delete_keeps_the_version_moving() {
+        let handler = InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new())));
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value2") });
+        assert_eq!(Ok(2), handler.version("key1"));
+
+        handler.handle(StorageCommand::Delete { key: String::from("key1") });
+        assert_eq!(Ok(3), handler.version("key1"));
+
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+        assert_eq!(Ok(4), handler.version("key1"));
+        handler.handle(StorageCommand::Delete { key: String::from("key2") });
+        assert_eq!(Ok(0), handler.version("key2"));
+    }
+
+    #[test]
+    fn test_record_applied_mutations() {
+        let change_feed = Arc::new(ChangeFeed::new(16));
+        let handler = InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new()))).with_change_feed(change_feed.clone());
+
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+        handler.handle(StorageCommand::Put { key: String::from("key1"), value: String::from("value2") });
+        handler.handle(StorageCommand::Delete { key: String::from("key2") });
+        handler.handle(StorageCommand::Get { key: String::from("key1") });
+        handler.handle(StorageCommand::Delete { key: String::from("key1") });
+        change_feed.close();
+
+        let changes: Vec<(u64, Option<String>, Option<String>)> = change_feed.subscribe(1)
+            .unwrap()
+            .map(|change| change.unwrap())
+            .map(|change| (change.sequence, change.old_value, change.new_value))
+            .collect();
+        assert_eq!(vec![
+            (1, None, Some(String::from("value1"))),
+            (2, Some(String::from("value1")), Some(String::from("value2"))),
+            (3, Some(String::from("value2")), None),
+        ], changes);
+    }
+
+    #[test]
+    fn test_handle_put_on_a_poisoned_storage() {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let cloned_storage = storage.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = cloned_storage.write().unwrap();
+            panic!("poison the storage");
+        }).join();
+
+        let handler = InMemoryStorageHandler::new(storage.clone());
+        let status = handler.handle(StorageCommand::Put {
+            key: String::from("key1"),
+            value: String::from("value1"),
+        });
+
+        assert_eq!(Status::Failed(QueueError::Poisoned), status);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::singular_update_queue::priority_lanes::Priority;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::singular_update_queue::SingularUpdateQueue;
+use crate::singular_update_queue::storage_command::{Status, Storage, StorageCommand};
+
+//a command is applied behind the earlier commands of its own lane only, so a linearizable read first waits for a read
+//through each of these lanes and then reads through the low lane, behind everything enqueued before it on any lane
+const HIGHER_LANES: [Priority; 2] = [Priority::High, Priority::Normal];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ReadConsistency {
+    //goes through the queue behind every command enqueued before it on any lane, so it observes all of them
+    Linearizable,
+    //reads the storage directly, fast but it may miss commands which are still enqueued
+    Stale,
+}
+
+pub struct StorageReader {
+    queue: SingularUpdateQueue<StorageCommand, Status>,
+    storage: Storage,
+}
+
+impl StorageReader {
+    pub fn new(queue: SingularUpdateQueue<StorageCommand, Status>, storage: Storage) -> StorageReader {
+        return StorageReader { queue, storage };
+    }
+
+    pub fn get(&self, key: &str, consistency: ReadConsistency) -> Result<Option<String>, QueueError> {
+        return match consistency {
+            ReadConsistency::Linearizable => {
+                for priority in HIGHER_LANES {
+                    to_value(self.queue.execute_with_priority(get(key), priority).wait()?)?;
+                }
+                to_value(self.queue.execute_with_priority(get(key), Priority::Low).wait()?)
+            }
+            ReadConsistency::Stale => self.stale_get(key),
+        };
+    }
+
+    pub async fn get_async(&self, key: &str, consistency: ReadConsistency) -> Result<Option<String>, QueueError> {
+        return match consistency {
+            ReadConsistency::Linearizable => {
+                for priority in HIGHER_LANES {
+                    to_value(self.queue.execute_async_with_priority(get(key), priority).await?)?;
+                }
+                to_value(self.queue.execute_async_with_priority(get(key), Priority::Low).await?)
+            }
+            ReadConsistency::Stale => self.stale_get(key),
+        };
+    }
+
+    fn stale_get(&self, key: &str) -> Result<Option<String>, QueueError> {
+        let storage = self.storage.read().map_err(|_| QueueError::Poisoned)?;
+        return Ok(storage.get(key).cloned());
+    }
+}
+
+fn get(key: &str) -> StorageCommand {
+    return StorageCommand::Get { key: String::from(key) };
+}
+
+fn to_value(status: Status) -> Result<Option<String>, QueueError> {
+    return match status {
+        Status::Value(value) => Ok(value),
+        Status::Failed(error) => Err(error),
+        Status::Ok | Status::Committed(_) => Err(QueueError::HandlerFailed(String::from("a read was answered without a value"))),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, RwLock};
+
+    use crate::singular_update_queue::singular_update_queue::Executor;
+    use crate::singular_update_queue::storage_command::InMemoryStorageHandler;
+
+    use super::*;
+
+    fn reader(executor: Executor) -> (StorageReader, SingularUpdateQueue<StorageCommand, Status>) {
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let queue = SingularUpdateQueue::init(Arc::new(InMemoryStorageHandler::new(storage.clone())), executor);
+        return (StorageReader::new(queue.clone(), storage), queue);
+    }
+
+    #[test]
+    fn test_linearizable_read_observes_an_enqueued_write() {
+        let (reader, queue) = reader(Executor::Thread);
+
+        let _ticket = queue.execute(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") });
+
+        assert_eq!(Ok(Some(String::from("value1"))), reader.get("key1", ReadConsistency::Linearizable));
+    }
+
+    //the worker task only runs once the test awaits, so both commands are enqueued before either is applied
+    #[tokio::test]
+    async fn test_linearizable_read_observes_a_write_enqueued_on_a_lower_lane() {
+        let (reader, queue) = reader(Executor::Task);
+
+        let put = StorageCommand::Put { key: String::from("key1"), value: String::from("value1") };
+        let ticket = queue.execute_with_priority(put, Priority::Low);
+
+        assert_eq!(Ok(Some(String::from("value1"))), reader.get_async("key1", ReadConsistency::Linearizable).await);
+        assert_eq!(Ok(Status::Ok), ticket.await);
+    }
+
+    #[test]
+    fn test_stale_read() {
+        let (reader, queue) = reader(Executor::Thread);
+
+        assert_eq!(Ok(None), reader.get("key1", ReadConsistency::Stale));
+        let status = queue.execute(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") }).wait();
+
+        assert_eq!(Ok(Status::Ok), status);
+        assert_eq!(Ok(Some(String::from("value1"))), reader.get("key1", ReadConsistency::Stale));
+    }
+
+    #[test]
+    fn test_linearizable_read_on_a_closed_queue() {
+        let (reader, queue) = reader(Executor::Thread);
+        assert_eq!(Ok(0), queue.shutdown().wait());
+
+        assert_eq!(Err(QueueError::Closed), reader.get("key1", ReadConsistency::Linearizable));
+        assert_eq!(Ok(None), reader.get("key1", ReadConsistency::Stale));
+    }
+
+    #[tokio::test]
+    async fn test_linearizable_read_from_a_task() {
+        let (reader, queue) = reader(Executor::Task);
+
+        let status = queue.execute_async(StorageCommand::Put { key: String::from("key1"), value: String::from("value1") }).await;
+
+        assert_eq!(Ok(Status::Ok), status);
+        assert_eq!(Ok(Some(String::from("value1"))), reader.get_async("key1", ReadConsistency::Linearizable).await);
+        assert_eq!(Ok(Some(String::from("value1"))), reader.get_async("key1", ReadConsistency::Stale).await);
+    }
+}
@@ -0,0 +1,115 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::singular_update_queue::timeout_runtime;
+
+//returned by execute, can either be waited on from a thread or awaited from an async task
+pub struct Ticket<R> {
+    receiver: oneshot::Receiver<Result<R, QueueError>>,
+}
+
+impl<R> Ticket<R> {
+    pub fn new(receiver: oneshot::Receiver<Result<R, QueueError>>) -> Ticket<R> {
+        return Ticket { receiver };
+    }
+
+    pub fn closed() -> Ticket<R> {
+        let (_, receiver) = oneshot::channel();
+        return Ticket { receiver };
+    }
+
+    pub fn wait(self) -> Result<R, QueueError> {
+        return self.receiver.blocking_recv().unwrap_or(Err(QueueError::Closed));
+    }
+
+    //like wait, must not be called from within an async context
+    pub fn wait_timeout(self, timeout: Duration) -> Result<R, QueueError> {
+        return timeout_runtime().block_on(async move {
+            return match tokio::time::timeout(timeout, self).await {
+                Ok(response) => response,
+                Err(_) => Err(QueueError::Timeout),
+            };
+        });
+    }
+}
+
+impl<R> Future for Ticket<R> {
+    type Output = Result<R, QueueError>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        return Pin::new(&mut self.receiver)
+            .poll(context)
+            .map(|response| response.unwrap_or(Err(QueueError::Closed)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn test_wait_for_a_response() {
+        let (sender, receiver) = oneshot::channel();
+        let ticket = Ticket::new(receiver);
+
+        thread::spawn(move || sender.send(Ok(10)).unwrap());
+        assert_eq!(Ok(10), ticket.wait());
+    }
+
+    #[test]
+    fn test_wait_after_the_responder_is_dropped() {
+        let (sender, receiver) = oneshot::channel::<Result<u64, QueueError>>();
+        let ticket = Ticket::new(receiver);
+
+        drop(sender);
+        assert_eq!(Err(QueueError::Closed), ticket.wait());
+    }
+
+    #[test]
+    fn test_wait_on_a_closed_ticket() {
+        let ticket: Ticket<u64> = Ticket::closed();
+        assert_eq!(Err(QueueError::Closed), ticket.wait());
+    }
+
+    #[test]
+    fn test_wait_for_a_failure() {
+        let (sender, receiver) = oneshot::channel::<Result<u64, QueueError>>();
+        let ticket = Ticket::new(receiver);
+
+        sender.send(Err(QueueError::Poisoned)).unwrap();
+        assert_eq!(Err(QueueError::Poisoned), ticket.wait());
+    }
+
+    #[test]
+    fn test_wait_timeout_without_a_response() {
+        let (_sender, receiver) = oneshot::channel::<Result<u64, QueueError>>();
+        let ticket = Ticket::new(receiver);
+
+        assert_eq!(Err(QueueError::Timeout), ticket.wait_timeout(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_wait_timeout_with_a_response() {
+        let (sender, receiver) = oneshot::channel();
+        let ticket = Ticket::new(receiver);
+
+        sender.send(Ok(10)).unwrap();
+        assert_eq!(Ok(10), ticket.wait_timeout(Duration::from_millis(10)));
+    }
+
+    #[tokio::test]
+    async fn test_await_a_response() {
+        let (sender, receiver) = oneshot::channel();
+        let ticket = Ticket::new(receiver);
+
+        tokio::spawn(async move { sender.send(Ok(10)).unwrap() });
+        assert_eq!(Ok(10), ticket.await);
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+//a key which was never written has version 0, every put and every delete of a key written before increments the version
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Precondition {
+    KeyExists {
+        key: String,
+    },
+    ValueEquals {
+        key: String,
+        value: String,
+    },
+    VersionMatches {
+        key: String,
+        version: u64,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Operation {
+    Put {
+        key: String,
+        value: String,
+    },
+    Delete {
+        key: String,
+    },
+    Get {
+        key: String,
+    },
+}
+
+//operations are applied in order only when every precondition holds, otherwise none of them is applied
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Transaction {
+    pub preconditions: Vec<Precondition>,
+    pub operations: Vec<Operation>,
+}
+
+impl Precondition {
+    pub fn key(&self) -> &str {
+        return match self {
+            Precondition::KeyExists { key } => key,
+            Precondition::ValueEquals { key, .. } => key,
+            Precondition::VersionMatches { key, .. } => key,
+        };
+    }
+
+    pub fn holds(&self, storage: &HashMap<String, String>, versions: &HashMap<String, u64>) -> bool {
+        let key = self.key();
+        return self.holds_for(storage.get(key), versions.get(key).copied().unwrap_or(0));
+    }
+
+    //evaluated against the current value and version of the key, for stores which are not a HashMap
+    pub fn holds_for(&self, current: Option<&String>, current_version: u64) -> bool {
+        return match self {
+            Precondition::KeyExists { .. } => current.is_some(),
+            Precondition::ValueEquals { value, .. } => current == Some(value),
+            Precondition::VersionMatches { version, .. } => current_version == *version,
+        };
+    }
+}
+
+impl Operation {
+    pub fn key(&self) -> &str {
+        return match self {
+            Operation::Put { key, .. } => key,
+            Operation::Delete { key } => key,
+            Operation::Get { key } => key,
+        };
+    }
+}
+
+impl Transaction {
+    pub fn new() -> Transaction {
+        return Transaction::default();
+    }
+
+    pub fn with_precondition(mut self, precondition: Precondition) -> Transaction {
+        self.preconditions.push(precondition);
+        return self;
+    }
+
+    pub fn with_operation(mut self, operation: Operation) -> Transaction {
+        self.operations.push(operation);
+        return self;
+    }
+
+    pub fn keys(&self) -> Vec<&str> {
+        let preconditions = self.preconditions.iter().map(|precondition| precondition.key());
+        return preconditions.chain(self.operations.iter().map(|operation| operation.key())).collect();
+    }
+
+    //the index of the first precondition which does not hold
+    pub fn failed_precondition(&self, storage: &HashMap<String, String>, versions: &HashMap<String, u64>) -> Option<usize> {
+        return self.preconditions.iter().position(|precondition| !precondition.holds(storage, versions));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_preconditions() {
+        let storage = HashMap::from([(String::from("key1"), String::from("value1"))]);
+        let versions = HashMap::from([(String::from("key1"), 2)]);
+
+        assert_eq!(true, Precondition::KeyExists { key: String::from("key1") }.holds(&storage, &versions));
+        assert_eq!(false, Precondition::KeyExists { key: String::from("key2") }.holds(&storage, &versions));
+        assert_eq!(true, Precondition::ValueEquals { key: String::from("key1"), value: String::from("value1") }.holds(&storage, &versions));
+        assert_eq!(false, Precondition::ValueEquals { key: String::from("key1"), value: String::from("value2") }.holds(&storage, &versions));
+        assert_eq!(true, Precondition::VersionMatches { key: String::from("key1"), version: 2 }.holds(&storage, &versions));
+        assert_eq!(true, Precondition::VersionMatches { key: String::from("key2"), version: 0 }.holds(&storage, &versions));
+    }
+
+    #[test]
+    fn test_find_the_failed_precondition() {
+        let storage = HashMap::from([(String::from("key1"), String::from("value1"))]);
+        let transaction = Transaction::new()
+            .with_precondition(Precondition::KeyExists { key: String::from("key1") })
+            .with_precondition(Precondition::KeyExists { key: String::from("key2") })
+            .with_operation(Operation::Delete { key: String::from("key1") });
+
+        assert_eq!(Some(1), transaction.failed_precondition(&storage, &HashMap::new()));
+    }
+}
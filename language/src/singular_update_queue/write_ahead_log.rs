@@ -0,0 +1,648 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::singular_update_queue::storage_command::StorageCommand;
+use crate::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+
+const SEGMENT_EXTENSION: &str = "log";
+const HEADER_SIZE: usize = 16;
+const PUT_TAG: u8 = 1;
+const DELETE_TAG: u8 = 2;
+const GET_TAG: u8 = 3;
+const TRANSACTION_TAG: u8 = 4;
+const KEY_EXISTS_TAG: u8 = 1;
+const VALUE_EQUALS_TAG: u8 = 2;
+const VERSION_MATCHES_TAG: u8 = 3;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FsyncPolicy {
+    Always,
+    EveryN(usize),
+    Never,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LogEntry {
+    pub index: u64,
+    pub command: StorageCommand,
+}
+
+pub struct WriteAheadLog {
+    directory: PathBuf,
+    segment_size: u64,
+    fsync_policy: FsyncPolicy,
+    active: Mutex<ActiveSegment>,
+}
+
+struct ActiveSegment {
+    file: File,
+    size: u64,
+    next_index: u64,
+    unsynced: usize,
+}
+
+struct ScannedSegment {
+    entries: Vec<LogEntry>,
+    valid_length: u64,
+    torn: bool,
+}
+
+//record layout: crc32 | payload length | index | payload, the crc covers the index and the payload
+impl WriteAheadLog {
+    pub fn open(directory: &Path, segment_size: u64, fsync_policy: FsyncPolicy) -> io::Result<WriteAheadLog> {
+        fs::create_dir_all(directory)?;
+        let segments = segment_paths(directory)?;
+
+        //a log truncated up to its active segment has no entries left, the name of that segment carries the next index
+        let mut next_index = segments.last().and_then(|segment| first_index(segment)).unwrap_or(1);
+        for (position, segment) in segments.iter().enumerate() {
+            let scanned = scan_segment(segment, position == segments.len() - 1)?;
+            if scanned.torn {
+                if position != segments.len() - 1 {
+                    return Err(corrupted(segment));
+                }
+                //a torn tail is a record which was being written when the process crashed
+                OpenOptions::new().write(true).open(segment)?.set_len(scanned.valid_length)?;
+            }
+            if let Some(last) = scanned.entries.last() {
+                next_index = last.index + 1;
+            }
+        }
+
+        let file = match segments.last() {
+            Some(segment) => OpenOptions::new().append(true).open(segment)?,
+            None => create_segment(directory, next_index)?,
+        };
+        let size = file.metadata()?.len();
+        return Ok(WriteAheadLog {
+            directory: directory.to_path_buf(),
+            segment_size,
+            fsync_policy,
+            active: Mutex::new(ActiveSegment { file, size, next_index, unsynced: 0 }),
+        });
+    }
+
+    pub fn append(&self, command: &StorageCommand) -> io::Result<u64> {
+        let indices = self.append_batch(std::slice::from_ref(command))?;
+        return Ok(indices[0]);
+    }
+
+    //the fsync policy is applied once per batch, which is what makes group commit cheaper than appending one by one
+    pub fn append_batch(&self, commands: &[StorageCommand]) -> io::Result<Vec<u64>> {
+        let mut active = self.active.lock().map_err(|_| poisoned())?;
+        let mut indices = Vec::with_capacity(commands.len());
+        for command in commands {
+            if active.size >= self.segment_size {
+                self.roll(&mut active)?;
+            }
+            let index = active.next_index;
+            let record = encode_record(index, command);
+            //a record written in part would hide every record appended after it, so it is dropped again
+            if let Err(error) = active.file.write_all(&record) {
+                let size = active.size;
+                let _ = active.file.set_len(size);
+                return Err(error);
+            }
+            active.size += record.len() as u64;
+            active.next_index += 1;
+            active.unsynced += 1;
+            indices.push(index);
+        }
+
+        let should_sync = match self.fsync_policy {
+            FsyncPolicy::Always => active.unsynced > 0,
+            FsyncPolicy::EveryN(count) => active.unsynced >= count,
+            FsyncPolicy::Never => false,
+        };
+        if should_sync {
+            active.file.sync_data()?;
+            active.unsynced = 0;
+        }
+        return Ok(indices);
+    }
+
+    pub fn sync(&self) -> io::Result<()> {
+        let mut active = self.active.lock().map_err(|_| poisoned())?;
+        active.file.sync_data()?;
+        active.unsynced = 0;
+        return Ok(());
+    }
+
+    pub fn entries(&self) -> io::Result<Vec<LogEntry>> {
+        let _active = self.active.lock().map_err(|_| poisoned())?;
+        let mut entries = Vec::new();
+        for segment in segment_paths(&self.directory)? {
+            let scanned = scan_segment(&segment, false)?;
+            if scanned.torn {
+                return Err(corrupted(&segment));
+            }
+            entries.extend(scanned.entries);
+        }
+        return Ok(entries);
+    }
+
+    pub fn last_index(&self) -> u64 {
+        return self.active.lock().map(|active| active.next_index - 1).unwrap_or(0);
+    }
+
+    pub fn segments(&self) -> io::Result<Vec<PathBuf>> {
+        return segment_paths(&self.directory);
+    }
+
+    //removes the segments whose entries are all covered by a snapshot taken at last_index, the active segment is kept
+    pub fn truncate(&self, last_index: u64) -> io::Result<usize> {
+        let _active = self.active.lock().map_err(|_| poisoned())?;
+        let segments = segment_paths(&self.directory)?;
+        let mut removed = 0;
+        for pair in segments.windows(2) {
+            match first_index(&pair[1]) {
+                Some(next_first_index) if next_first_index <= last_index + 1 => {
+                    fs::remove_file(&pair[0])?;
+                    removed += 1;
+                }
+                _ => break,
+            }
+        }
+        return Ok(removed);
+    }
+
+    fn roll(&self, active: &mut ActiveSegment) -> io::Result<()> {
+        active.file.sync_data()?;
+        active.file = create_segment(&self.directory, active.next_index)?;
+        active.size = 0;
+        active.unsynced = 0;
+        return Ok(());
+    }
+}
+
+fn segment_paths(directory: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut segments: Vec<(u64, PathBuf)> = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) != Some(SEGMENT_EXTENSION) {
+            continue;
+        }
+        if let Some(first_index) = first_index(&path) {
+            segments.push((first_index, path));
+        }
+    }
+    segments.sort_by_key(|(first_index, _)| *first_index);
+    return Ok(segments.into_iter().map(|(_, path)| path).collect());
+}
+
+fn first_index(segment: &Path) -> Option<u64> {
+    return segment.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse().ok());
+}
+
+fn create_segment(directory: &Path, first_index: u64) -> io::Result<File> {
+    let path = directory.join(format!("{:020}.{}", first_index, SEGMENT_EXTENSION));
+    return OpenOptions::new().create(true).append(true).open(path);
+}
+
+//the tail of the active segment may hold records which were never synced before a crash, their bytes can be zeros or garbage
+fn scan_segment(segment: &Path, active: bool) -> io::Result<ScannedSegment> {
+    let bytes = fs::read(segment)?;
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        if bytes.len() - offset < HEADER_SIZE {
+            return Ok(ScannedSegment { entries, valid_length: offset as u64, torn: true });
+        }
+        let crc = read_u32(&bytes[offset..]);
+        let length = read_u32(&bytes[offset + 4..]) as usize;
+        let body_start = offset + 8;
+        let body_end = offset + HEADER_SIZE + length;
+        //a record running past the end of the segment was cut short by a crash
+        if body_end > bytes.len() {
+            return Ok(ScannedSegment { entries, valid_length: offset as u64, torn: true });
+        }
+        //a complete record with a bad crc is damage, unless it starts the unsynced tail of the active segment
+        if crc32fast::hash(&bytes[body_start..body_end]) != crc {
+            if active && !valid_record_follows(&bytes, body_end) {
+                return Ok(ScannedSegment { entries, valid_length: offset as u64, torn: true });
+            }
+            return Err(corrupted(segment));
+        }
+
+        let index = read_u64(&bytes[body_start..]);
+        let command = decode_command(&bytes[offset + HEADER_SIZE..body_end]).ok_or_else(|| corrupted(segment))?;
+        entries.push(LogEntry { index, command });
+        offset = body_end;
+    }
+    return Ok(ScannedSegment { entries, valid_length: offset as u64, torn: false });
+}
+
+fn valid_record_follows(bytes: &[u8], mut offset: usize) -> bool {
+    while bytes.len() - offset >= HEADER_SIZE {
+        let crc = read_u32(&bytes[offset..]);
+        let body_end = offset + HEADER_SIZE + read_u32(&bytes[offset + 4..]) as usize;
+        if body_end > bytes.len() {
+            return false;
+        }
+        if crc32fast::hash(&bytes[offset + 8..body_end]) == crc {
+            return true;
+        }
+        offset = body_end;
+    }
+    return false;
+}
+
+fn encode_record(index: u64, command: &StorageCommand) -> Vec<u8> {
+    let payload = encode_command(command);
+    let mut body = Vec::with_capacity(8 + payload.len());
+    body.extend_from_slice(&index.to_le_bytes());
+    body.extend_from_slice(&payload);
+
+    let mut record = Vec::with_capacity(8 + body.len());
+    record.extend_from_slice(&crc32fast::hash(&body).to_le_bytes());
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&body);
+    return record;
+}
+
+fn encode_command(command: &StorageCommand) -> Vec<u8> {
+    let mut payload = Vec::new();
+    match command {
+        StorageCommand::Put { key, value } => encode_operation(&mut payload, &Operation::Put { key: key.clone(), value: value.clone() }),
+        StorageCommand::Delete { key } => encode_operation(&mut payload, &Operation::Delete { key: key.clone() }),
+        StorageCommand::Get { key } => encode_operation(&mut payload, &Operation::Get { key: key.clone() }),
+        StorageCommand::Transaction(transaction) => {
+            payload.push(TRANSACTION_TAG);
+            payload.extend_from_slice(&(transaction.preconditions.len() as u32).to_le_bytes());
+            for precondition in &transaction.preconditions {
+                encode_precondition(&mut payload, precondition);
+            }
+            payload.extend_from_slice(&(transaction.operations.len() as u32).to_le_bytes());
+            for operation in &transaction.operations {
+                encode_operation(&mut payload, operation);
+            }
+        }
+    }
+    return payload;
+}
+
+fn encode_operation(payload: &mut Vec<u8>, operation: &Operation) {
+    match operation {
+        Operation::Put { key, value } => {
+            payload.push(PUT_TAG);
+            encode_string(payload, key);
+            encode_string(payload, value);
+        }
+        Operation::Delete { key } => {
+            payload.push(DELETE_TAG);
+            encode_string(payload, key);
+        }
+        Operation::Get { key } => {
+            payload.push(GET_TAG);
+            encode_string(payload, key);
+        }
+    }
+}
+
+fn encode_precondition(payload: &mut Vec<u8>, precondition: &Precondition) {
+    match precondition {
+        Precondition::KeyExists { key } => {
+            payload.push(KEY_EXISTS_TAG);
+            encode_string(payload, key);
+        }
+        Precondition::ValueEquals { key, value } => {
+            payload.push(VALUE_EQUALS_TAG);
+            encode_string(payload, key);
+            encode_string(payload, value);
+        }
+        Precondition::VersionMatches { key, version } => {
+            payload.push(VERSION_MATCHES_TAG);
+            encode_string(payload, key);
+            payload.extend_from_slice(&version.to_le_bytes());
+        }
+    }
+}
+
+pub(crate) fn encode_string(payload: &mut Vec<u8>, value: &str) {
+    payload.extend_from_slice(&(value.len() as u32).to_le_bytes());
+    payload.extend_from_slice(value.as_bytes());
+}
+
+fn decode_command(payload: &[u8]) -> Option<StorageCommand> {
+    if payload.first() != Some(&TRANSACTION_TAG) {
+        let (operation, _) = decode_operation(payload)?;
+        return Some(match operation {
+            Operation::Put { key, value } => StorageCommand::Put { key, value },
+            Operation::Delete { key } => StorageCommand::Delete { key },
+            Operation::Get { key } => StorageCommand::Get { key },
+        });
+    }
+    let mut transaction = Transaction::new();
+    let (count, mut rest) = decode_u32(&payload[1..])?;
+    for _ in 0..count {
+        let (precondition, remaining) = decode_precondition(rest)?;
+        transaction = transaction.with_precondition(precondition);
+        rest = remaining;
+    }
+    let (count, mut rest) = decode_u32(rest)?;
+    for _ in 0..count {
+        let (operation, remaining) = decode_operation(rest)?;
+        transaction = transaction.with_operation(operation);
+        rest = remaining;
+    }
+    return Some(StorageCommand::Transaction(transaction));
+}
+
+fn decode_operation(bytes: &[u8]) -> Option<(Operation, &[u8])> {
+    let (tag, rest) = bytes.split_first()?;
+    return match *tag {
+        PUT_TAG => {
+            let (key, rest) = decode_string(rest)?;
+            let (value, rest) = decode_string(rest)?;
+            Some((Operation::Put { key, value }, rest))
+        }
+        DELETE_TAG => {
+            let (key, rest) = decode_string(rest)?;
+            Some((Operation::Delete { key }, rest))
+        }
+        GET_TAG => {
+            let (key, rest) = decode_string(rest)?;
+            Some((Operation::Get { key }, rest))
+        }
+        _ => None,
+    };
+}
+
+fn decode_precondition(bytes: &[u8]) -> Option<(Precondition, &[u8])> {
+    let (tag, rest) = bytes.split_first()?;
+    return match *tag {
+        KEY_EXISTS_TAG => {
+            let (key, rest) = decode_string(rest)?;
+            Some((Precondition::KeyExists { key }, rest))
+        }
+        VALUE_EQUALS_TAG => {
+            let (key, rest) = decode_string(rest)?;
+            let (value, rest) = decode_string(rest)?;
+            Some((Precondition::ValueEquals { key, value }, rest))
+        }
+        VERSION_MATCHES_TAG => {
+            let (key, rest) = decode_string(rest)?;
+            if rest.len() < 8 {
+                return None;
+            }
+            Some((Precondition::VersionMatches { key, version: read_u64(rest) }, &rest[8..]))
+        }
+        _ => None,
+    };
+}
+
+pub(crate) fn decode_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    return Some((read_u32(bytes), &bytes[4..]));
+}
+
+pub(crate) fn decode_string(bytes: &[u8]) -> Option<(String, &[u8])> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    let length = read_u32(bytes) as usize;
+    let value = bytes.get(4..4 + length)?;
+    return Some((String::from_utf8(value.to_vec()).ok()?, &bytes[4 + length..]));
+}
+
+pub(crate) fn read_u32(bytes: &[u8]) -> u32 {
+    return u32::from_le_bytes(bytes[..4].try_into().unwrap());
+}
+
+pub(crate) fn read_u64(bytes: &[u8]) -> u64 {
+    return u64::from_le_bytes(bytes[..8].try_into().unwrap());
+}
+
+fn corrupted(segment: &Path) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, format!("corrupted write ahead log segment {}", segment.display()));
+}
+
+fn poisoned() -> io::Error {
+    return io::Error::other("write ahead log lock is poisoned");
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn put(key: &str, value: &str) -> StorageCommand {
+        return StorageCommand::Put { key: String::from(key), value: String::from(value) };
+    }
+
+    #[test]
+    fn test_append_and_read_entries() {
+        let directory = TempDir::new().unwrap();
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+
+        assert_eq!(1, log.append(&put("key1", "value1")).unwrap());
+        assert_eq!(2, log.append(&StorageCommand::Delete { key: String::from("key1") }).unwrap());
+
+        let entries = log.entries().unwrap();
+        assert_eq!(vec![
+            LogEntry { index: 1, command: put("key1", "value1") },
+            LogEntry { index: 2, command: StorageCommand::Delete { key: String::from("key1") } },
+        ], entries);
+    }
+
+    #[test]
+    fn test_append_a_batch() {
+        let directory = TempDir::new().unwrap();
+        let log = WriteAheadLog::open(directory.path(), 64, FsyncPolicy::Always).unwrap();
+
+        let indices = log.append_batch(&[put("key1", "value1"), put("key2", "value2"), put("key3", "value3")]).unwrap();
+
+        assert_eq!(vec![1, 2, 3], indices);
+        assert_eq!(3, log.entries().unwrap().len());
+        assert_eq!(true, log.segments().unwrap().len() > 1);
+    }
+
+    #[test]
+    fn test_reopen_continues_the_index() {
+        let directory = TempDir::new().unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Never).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+            log.sync().unwrap();
+        }
+
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Never).unwrap();
+        assert_eq!(1, log.last_index());
+        assert_eq!(2, log.append(&put("key2", "value2")).unwrap());
+        assert_eq!(2, log.entries().unwrap().len());
+    }
+
+    #[test]
+    fn test_roll_over_segments() {
+        let directory = TempDir::new().unwrap();
+        let log = WriteAheadLog::open(directory.path(), 64, FsyncPolicy::EveryN(2)).unwrap();
+
+        for index in 0..10 {
+            log.append(&put(&format!("key{}", index), "value")).unwrap();
+        }
+
+        assert_eq!(true, log.segments().unwrap().len() > 1);
+        let indices: Vec<u64> = log.entries().unwrap().iter().map(|entry| entry.index).collect();
+        assert_eq!((1..=10).collect::<Vec<u64>>(), indices);
+    }
+
+    #[test]
+    fn test_truncate_segments_covered_by_a_snapshot() {
+        let directory = TempDir::new().unwrap();
+        let log = WriteAheadLog::open(directory.path(), 64, FsyncPolicy::Never).unwrap();
+        for index in 0..10 {
+            log.append(&put(&format!("key{}", index), "value")).unwrap();
+        }
+        let segments = log.segments().unwrap().len();
+
+        let removed = log.truncate(6).unwrap();
+
+        assert_eq!(true, removed > 0);
+        assert_eq!(segments - removed, log.segments().unwrap().len());
+        let first = log.entries().unwrap()[0].index;
+        assert_eq!(true, first > 1 && first <= 7);
+        assert_eq!(segments - 1, log.truncate(10).unwrap() + removed);
+    }
+
+    #[test]
+    fn test_reopen_a_log_truncated_up_to_an_empty_active_segment() {
+        let directory = TempDir::new().unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 32, FsyncPolicy::Always).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+            log.append(&put("key2", "value2")).unwrap();
+            log.append(&put("key3", "value3")).unwrap();
+        }
+        //the process crashed right after rolling to the third segment
+        let segment = segment_paths(directory.path()).unwrap().pop().unwrap();
+        OpenOptions::new().write(true).open(&segment).unwrap().set_len(0).unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 32, FsyncPolicy::Always).unwrap();
+            assert_eq!(2, log.truncate(2).unwrap());
+        }
+
+        let log = WriteAheadLog::open(directory.path(), 32, FsyncPolicy::Always).unwrap();
+        assert_eq!(2, log.last_index());
+        assert_eq!(3, log.append(&put("key3", "value3")).unwrap());
+    }
+
+    #[test]
+    fn test_truncate_a_torn_tail() {
+        let directory = TempDir::new().unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+            log.append(&put("key2", "value2")).unwrap();
+        }
+        let segment = segment_paths(directory.path()).unwrap().pop().unwrap();
+        let length = fs::metadata(&segment).unwrap().len();
+        OpenOptions::new().write(true).open(&segment).unwrap().set_len(length - 3).unwrap();
+
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+        assert_eq!(vec![LogEntry { index: 1, command: put("key1", "value1") }], log.entries().unwrap());
+        assert_eq!(2, log.append(&put("key3", "value3")).unwrap());
+    }
+
+    #[test]
+    fn test_detect_a_corrupted_record() {
+        let directory = TempDir::new().unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 32, FsyncPolicy::Always).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+            log.append(&put("key2", "value2")).unwrap();
+        }
+        let segment = segment_paths(directory.path()).unwrap().remove(0);
+        let mut bytes = fs::read(&segment).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(&segment, bytes).unwrap();
+
+        let result = WriteAheadLog::open(directory.path(), 32, FsyncPolicy::Always);
+        assert_eq!(io::ErrorKind::InvalidData, result.err().unwrap().kind());
+    }
+
+    #[test]
+    fn test_detect_a_corrupted_record_in_the_last_segment() {
+        let directory = TempDir::new().unwrap();
+        let first_record_length;
+        {
+            let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+            first_record_length = fs::metadata(segment_paths(directory.path()).unwrap().pop().unwrap()).unwrap().len() as usize;
+            log.append(&put("key2", "value2")).unwrap();
+            log.append(&put("key3", "value3")).unwrap();
+        }
+        let segment = segment_paths(directory.path()).unwrap().pop().unwrap();
+        let mut bytes = fs::read(&segment).unwrap();
+        bytes[first_record_length + HEADER_SIZE] ^= 0xFF;
+        fs::write(&segment, bytes).unwrap();
+
+        let result = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always);
+        assert_eq!(io::ErrorKind::InvalidData, result.err().unwrap().kind());
+    }
+
+    #[test]
+    fn test_truncate_a_tail_record_with_a_bad_crc() {
+        let directory = TempDir::new().unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Never).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+            log.append(&put("key2", "value2")).unwrap();
+        }
+        let segment = segment_paths(directory.path()).unwrap().pop().unwrap();
+        let mut bytes = fs::read(&segment).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xFF;
+        fs::write(&segment, bytes).unwrap();
+
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Never).unwrap();
+        assert_eq!(vec![LogEntry { index: 1, command: put("key1", "value1") }], log.entries().unwrap());
+        assert_eq!(2, log.append(&put("key3", "value3")).unwrap());
+    }
+
+    #[test]
+    fn test_truncate_a_zeroed_tail() {
+        let directory = TempDir::new().unwrap();
+        {
+            let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Never).unwrap();
+            log.append(&put("key1", "value1")).unwrap();
+        }
+        //the file grew but the data of the unsynced records never reached the disk
+        let segment = segment_paths(directory.path()).unwrap().pop().unwrap();
+        let mut bytes = fs::read(&segment).unwrap();
+        bytes.extend_from_slice(&[0; 100]);
+        fs::write(&segment, bytes).unwrap();
+
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Never).unwrap();
+        assert_eq!(vec![LogEntry { index: 1, command: put("key1", "value1") }], log.entries().unwrap());
+        assert_eq!(2, log.append(&put("key2", "value2")).unwrap());
+    }
+
+    #[test]
+    fn test_encode_and_decode_a_command() {
+        let command = put("key", "value");
+        assert_eq!(Some(command.clone()), decode_command(&encode_command(&command)));
+        assert_eq!(None, decode_command(&[9]));
+    }
+
+    #[test]
+    fn test_encode_and_decode_a_transaction() {
+        let command = StorageCommand::Transaction(Transaction::new()
+            .with_precondition(Precondition::KeyExists { key: String::from("key1") })
+            .with_precondition(Precondition::ValueEquals { key: String::from("key1"), value: String::from("value1") })
+            .with_precondition(Precondition::VersionMatches { key: String::from("key2"), version: 7 })
+            .with_operation(Operation::Put { key: String::from("key2"), value: String::from("value2") })
+            .with_operation(Operation::Delete { key: String::from("key1") })
+            .with_operation(Operation::Get { key: String::from("key2") }));
+
+        let payload = encode_command(&command);
+        assert_eq!(Some(command), decode_command(&payload));
+        assert_eq!(None, decode_command(&payload[..payload.len() - 1]));
+    }
+}
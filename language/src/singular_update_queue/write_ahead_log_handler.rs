@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, RwLock};
+use std::thread::{self, JoinHandle};
+
+use crate::raft::state_machine::StateMachine;
+use crate::singular_update_queue::command::CommandHandler;
+use crate::singular_update_queue::queue_error::QueueError;
+use crate::singular_update_queue::snapshot_store::{SnapshotStore, StorageSnapshot};
+use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Status, StorageCommand};
+use crate::singular_update_queue::write_ahead_log::WriteAheadLog;
+
+//appends every write to the log before handing it to the inner handler, reads go straight to the inner handler
+pub struct WriteAheadLogHandler {
+    log: Arc<WriteAheadLog>,
+    inner: Arc<dyn CommandHandler<StorageCommand, Status>>,
+    snapshotter: Option<Snapshotter>,
+}
+
+//the worker only asks for a snapshot at a log index, a background thread keeps its own copy of the state by replaying
+//the log up to that index, so the cut is consistent without copying anything on the worker, at the cost of holding the state twice
+struct Snapshotter {
+    every_writes: usize,
+    writes: AtomicUsize,
+    last_index: Arc<AtomicU64>,
+    sender: Option<Sender<u64>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl WriteAheadLogHandler {
+    pub fn new(log: WriteAheadLog, inner: Arc<dyn CommandHandler<StorageCommand, Status>>) -> WriteAheadLogHandler {
+        return WriteAheadLogHandler { log: Arc::new(log), inner, snapshotter: None };
+    }
+
+    //replays the log into the inner handler, meant to be called at startup before the queue accepts commands
+    pub fn recover(log: WriteAheadLog, inner: Arc<dyn CommandHandler<StorageCommand, Status>>) -> io::Result<WriteAheadLogHandler> {
+        replay(&log, inner.as_ref(), 0, u64::MAX)?;
+        return Ok(WriteAheadLogHandler::new(log, inner));
+    }
+
+    //restores the latest snapshot and replays only the entries written after it
+    pub fn recover_from_snapshot(log: WriteAheadLog, store: &SnapshotStore, state_machine: Arc<dyn StateMachine>) -> io::Result<WriteAheadLogHandler> {
+        let mut last_index = 0;
+        if let Some(snapshot) = store.latest()? {
+            last_index = snapshot.last_index;
+            state_machine.restore(snapshot.entries);
+        }
+        replay(&log, state_machine.as_ref(), last_index, u64::MAX)?;
+        return Ok(WriteAheadLogHandler::new(log, state_machine));
+    }
+
+    //takes a snapshot after every_writes logged writes and truncates the segments it covers, the snapshot starts from the
+    //latest one in the store, so the handler has to be recovered from that store
+    pub fn with_snapshots(mut self, store: SnapshotStore, every_writes: usize) -> WriteAheadLogHandler {
+        assert!(every_writes > 0, "snapshots must be taken every one write or more");
+        let (sender, receiver) = mpsc::channel::<u64>();
+        let last_index = Arc::new(AtomicU64::new(0));
+
+        let log = self.log.clone();
+        let saved_index = last_index.clone();
+        let handle = thread::spawn(move || {
+            let copy = InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new())));
+            let mut copy_index = 0;
+            match store.latest() {
+                Ok(Some(snapshot)) => {
+                    copy_index = snapshot.last_index;
+                    copy.restore(snapshot.entries);
+                }
+                Ok(None) => {}
+                //without the latest snapshot the log alone can not rebuild the state, so no snapshot is taken
+                Err(_) => return,
+            }
+            for requested_index in receiver {
+                if replay(&log, &copy, copy_index, requested_index).is_err() {
+                    return;
+                }
+                copy_index = requested_index;
+                let snapshot = StorageSnapshot { last_index: copy_index, entries: copy.snapshot() };
+                //a snapshot which cannot be saved leaves the log untouched, the next one tries again
+                if store.save(&snapshot).is_err() {
+                    continue;
+                }
+                saved_index.store(snapshot.last_index, Ordering::SeqCst);
+                let _ = log.truncate(snapshot.last_index);
+            }
+        });
+
+        self.snapshotter = Some(Snapshotter {
+            every_writes,
+            writes: AtomicUsize::new(0),
+            last_index,
+            sender: Some(sender),
+            handle: Some(handle),
+        });
+        return self;
+    }
+
+    pub fn log(&self) -> &WriteAheadLog {
+        return &self.log;
+    }
+
+    //the index covered by the latest saved snapshot, 0 when none was saved yet
+    pub fn last_snapshot_index(&self) -> u64 {
+        return self.snapshotter.as_ref().map(|snapshotter| snapshotter.last_index.load(Ordering::SeqCst)).unwrap_or(0);
+    }
+
+    fn after_writes(&self, writes: usize) {
+        if let Some(snapshotter) = &self.snapshotter {
+            snapshotter.after_writes(writes, self.log.last_index());
+        }
+    }
+}
+
+impl Snapshotter {
+    fn after_writes(&self, writes: usize, last_index: u64) {
+        if self.writes.fetch_add(writes, Ordering::SeqCst) + writes < self.every_writes {
+            return;
+        }
+        self.writes.store(0, Ordering::SeqCst);
+        if let Some(sender) = &self.sender {
+            let _ = sender.send(last_index);
+        }
+    }
+}
+
+impl Drop for Snapshotter {
+    fn drop(&mut self) {
+        self.sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn replay(log: &WriteAheadLog, handler: &dyn CommandHandler<StorageCommand, Status>, after_index: u64, until_index: u64) -> io::Result<()> {
+    for entry in log.entries()?.into_iter().filter(|entry| entry.index > after_index && entry.index <= until_index) {
+        //a rejected transaction is rejected again on replay, which leaves the storage as it was before the crash
+        match handler.handle(entry.command) {
+            Status::Failed(QueueError::PreconditionFailed(_)) => {}
+            Status::Failed(error) => return Err(io::Error::other(format!("replay of entry {} failed: {}", entry.index, error))),
+            _ => {}
+        }
+    }
+    return Ok(());
+}
+
+impl CommandHandler<StorageCommand, Status> for WriteAheadLogHandler {
+    fn handle(&self, command: StorageCommand) -> Status {
+        if command.is_read() {
+            return self.inner.handle(command);
+        }
+        if let Err(error) = self.log.append(&command) {
+            return Status::Failed(QueueError::HandlerFailed(format!("write ahead log append failed: {}", error)));
+        }
+        let status = self.inner.handle(command);
+        self.after_writes(1);
+        return status;
+    }
+
+    fn handle_batch(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+        let writes: Vec<StorageCommand> = commands.iter().filter(|command| !command.is_read()).cloned().collect();
+        if writes.is_empty() {
+            return self.inner.handle_batch(commands);
+        }
+        if let Err(error) = self.log.append_batch(&writes) {
+            let status = Status::Failed(QueueError::HandlerFailed(format!("write ahead log append failed: {}", error)));
+            return vec![status; commands.len()];
+        }
+        let statuses = self.inner.handle_batch(commands);
+        self.after_writes(writes.len());
+        return statuses;
+    }
+
+    fn command_type(&self, command: &StorageCommand) -> &'static str {
+        return command.command_type();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::RwLock;
+    use std::time::{Duration, Instant};
+
+    use tempfile::TempDir;
+
+    use crate::singular_update_queue::queue_config::QueueConfig;
+    use crate::singular_update_queue::singular_update_queue::{Executor, SingularUpdateQueue};
+    use crate::singular_update_queue::storage_command::{InMemoryStorageHandler, Storage};
+    use crate::singular_update_queue::ticket::Ticket;
+    use crate::singular_update_queue::transaction::{Operation, Precondition, Transaction};
+    use crate::singular_update_queue::write_ahead_log::FsyncPolicy;
+
+    use super::*;
+
+    #[test]
+    fn test_log_before_applying() {
+        let directory = TempDir::new().unwrap();
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+        let handler = WriteAheadLogHandler::new(log, Arc::new(InMemoryStorageHandler::new(storage.clone())));
+
+        let status = handler.handle(StorageCommand::Put {
+            key: String::from("key1"),
+            value: String::from("value1"),
+        });
+
+        assert_eq!(Status::Ok, status);
+        assert_eq!(1, handler.log().entries().unwrap().len());
+        assert_eq!("value1", storage.read().unwrap().get("key1").unwrap());
+    }
+
+    #[test]
+    fn test_recover_storage_after_a_restart() {
+        let directory = TempDir::new().unwrap();
+        {
+            let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+            let log = WriteAheadLog::open(directory.path(), 64, FsyncPolicy::Always).unwrap();
+            let handler = Arc::new(WriteAheadLogHandler::new(log, Arc::new(InMemoryStorageHandler::new(storage))));
+            let singular_update_queue = SingularUpdateQueue::init(handler, Executor::Thread);
+
+            for index in 0..5 {
+                let ticket = singular_update_queue.execute(StorageCommand::Put {
+                    key: format!("key{}", index),
+                    value: format!("value{}", index),
+                });
+                assert_eq!(Ok(Status::Ok), ticket.wait());
+            }
+            let ticket = singular_update_queue.execute(StorageCommand::Delete { key: String::from("key0") });
+            assert_eq!(Ok(Status::Ok), ticket.wait());
+            assert_eq!(Ok(6), singular_update_queue.shutdown().wait());
+        }
+
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let log = WriteAheadLog::open(directory.path(), 64, FsyncPolicy::Always).unwrap();
+        let handler = WriteAheadLogHandler::recover(log, Arc::new(InMemoryStorageHandler::new(storage.clone()))).unwrap();
+
+        let read_storage = storage.read().unwrap();
+        assert_eq!(4, read_storage.len());
+        assert_eq!(None, read_storage.get("key0"));
+        assert_eq!("value4", read_storage.get("key4").unwrap());
+        assert_eq!(6, handler.log().last_index());
+    }
+
+    #[test]
+    fn test_handle_a_batch() {
+        let directory = TempDir::new().unwrap();
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+        let handler = WriteAheadLogHandler::new(log, Arc::new(InMemoryStorageHandler::new(storage.clone())));
+
+        let statuses = handler.handle_batch(vec![
+            StorageCommand::Put { key: String::from("key1"), value: String::from("value1") },
+            StorageCommand::Delete { key: String::from("key1") },
+        ]);
+
+        assert_eq!(vec![Status::Ok, Status::Ok], statuses);
+        assert_eq!(2, handler.log().last_index());
+        assert_eq!(None, storage.read().unwrap().get("key1"));
+    }
+
+    #[test]
+    fn test_do_not_log_reads() {
+        let directory = TempDir::new().unwrap();
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let log = WriteAheadLog::open(directory.path(), 1024, FsyncPolicy::Always).unwrap();
+        let handler = WriteAheadLogHandler::new(log, Arc::new(InMemoryStorageHandler::new(storage)));
+
+        let statuses = handler.handle_batch(vec![
+            StorageCommand::Put { key: String::from("key1"), value: String::from("value1") },
+            StorageCommand::Get { key: String::from("key1") },
+        ]);
+        let status = handler.handle(StorageCommand::Get { key: String::from("key1") });
+
+        assert_eq!(vec![Status::Ok, Status::Value(Some(String::from("value1")))], statuses);
+        assert_eq!(Status::Value(Some(String::from("value1"))), status);
+        assert_eq!(1, handler.log().last_index());
+    }
+
+    #[test]
+    fn test_snapshot_and_truncate_the_log() {
+        let log_directory = TempDir::new().unwrap();
+        let snapshot_directory = TempDir::new().unwrap();
+        {
+            let state_machine = Arc::new(InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new()))));
+            let log = WriteAheadLog::open(log_directory.path(), 64, FsyncPolicy::Always).unwrap();
+            let store = SnapshotStore::open(snapshot_directory.path()).unwrap();
+            let handler = WriteAheadLogHandler::new(log, state_machine).with_snapshots(store, 5);
+
+            for index in 0..10 {
+                handler.handle(StorageCommand::Put { key: format!("key{}", index), value: format!("value{}", index) });
+            }
+            handler.handle(StorageCommand::Delete { key: String::from("key0") });
+            handler.handle(StorageCommand::Transaction(Transaction::new()
+                .with_precondition(Precondition::KeyExists { key: String::from("key0") })
+                .with_operation(Operation::Delete { key: String::from("key1") })));
+            drop(handler);
+        }
+
+        let store = SnapshotStore::open(snapshot_directory.path()).unwrap();
+        assert_eq!(10, store.latest().unwrap().unwrap().last_index);
+        let log = WriteAheadLog::open(log_directory.path(), 64, FsyncPolicy::Always).unwrap();
+        assert_eq!(11, log.entries().unwrap()[0].index);
+
+        let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+        let state_machine = Arc::new(InMemoryStorageHandler::new(storage.clone()));
+        let handler = WriteAheadLogHandler::recover_from_snapshot(log, &store, state_machine.clone()).unwrap();
+
+        let read_storage = storage.read().unwrap();
+        assert_eq!(9, read_storage.len());
+        assert_eq!(None, read_storage.get("key0"));
+        assert_eq!("value1", read_storage.get("key1").unwrap());
+        assert_eq!(Ok(1), state_machine.version("key9"));
+        assert_eq!(12, handler.log().last_index());
+    }
+
+    #[test]
+    fn test_continue_from_the_latest_snapshot_after_a_restart() {
+        let log_directory = TempDir::new().unwrap();
+        let snapshot_directory = TempDir::new().unwrap();
+        for round in 0..2 {
+            let state_machine = Arc::new(InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new()))));
+            let log = WriteAheadLog::open(log_directory.path(), 64, FsyncPolicy::Always).unwrap();
+            let store = SnapshotStore::open(snapshot_directory.path()).unwrap();
+            let handler = WriteAheadLogHandler::recover_from_snapshot(log, &store, state_machine)
+                .unwrap()
+                .with_snapshots(store, 5);
+            for index in 0..5 {
+                handler.handle(StorageCommand::Put { key: format!("key{}", round * 5 + index), value: String::from("value") });
+            }
+        }
+
+        let snapshot = SnapshotStore::open(snapshot_directory.path()).unwrap().latest().unwrap().unwrap();
+        assert_eq!(10, snapshot.last_index);
+        assert_eq!(10, snapshot.entries.len());
+    }
+
+    #[test]
+    fn test_snapshot_without_blocking_the_queue() {
+        let log_directory = TempDir::new().unwrap();
+        let snapshot_directory = TempDir::new().unwrap();
+        let state_machine = Arc::new(InMemoryStorageHandler::new(Arc::new(RwLock::new(HashMap::new()))));
+        let log = WriteAheadLog::open(log_directory.path(), 1024, FsyncPolicy::Never).unwrap();
+        let store = SnapshotStore::open(snapshot_directory.path()).unwrap();
+        let handler = Arc::new(WriteAheadLogHandler::new(log, state_machine).with_snapshots(store, 5));
+        let singular_update_queue = SingularUpdateQueue::init(handler.clone(), Executor::Thread);
+
+        for index in 0..10 {
+            let ticket = singular_update_queue.execute(StorageCommand::Put { key: format!("key{}", index), value: String::from("value") });
+            assert_eq!(Ok(Status::Ok), ticket.wait());
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while handler.last_snapshot_index() < 10 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(10, handler.last_snapshot_index());
+    }
+
+    //counts the calls which reach the log, every one of them appends and fsyncs once
+    struct CountingHandler {
+        inner: WriteAheadLogHandler,
+        appends: AtomicUsize,
+    }
+
+    impl CommandHandler<StorageCommand, Status> for CountingHandler {
+        fn handle(&self, command: StorageCommand) -> Status {
+            self.appends.fetch_add(1, Ordering::SeqCst);
+            return self.inner.handle(command);
+        }
+
+        fn handle_batch(&self, commands: Vec<StorageCommand>) -> Vec<Status> {
+            self.appends.fetch_add(1, Ordering::SeqCst);
+            return self.inner.handle_batch(commands);
+        }
+    }
+
+    //the worker task only runs once the test awaits, so every command is enqueued before the first batch is taken
+    #[tokio::test]
+    async fn test_share_one_append_per_batch() {
+        let commands: usize = 100;
+        for batch_size in [1, 8, 64, 256] {
+            let directory = TempDir::new().unwrap();
+            let storage: Storage = Arc::new(RwLock::new(HashMap::new()));
+            let log = WriteAheadLog::open(directory.path(), 1024 * 1024, FsyncPolicy::Always).unwrap();
+            let inner = WriteAheadLogHandler::new(log, Arc::new(InMemoryStorageHandler::new(storage)));
+            let handler = Arc::new(CountingHandler { inner, appends: AtomicUsize::new(0) });
+            let config = QueueConfig::default().with_max_batch_size(batch_size);
+            let singular_update_queue = SingularUpdateQueue::init_with_config(handler.clone(), Executor::Task, config);
+
+            let tickets: Vec<Ticket<Status>> = (0..commands)
+                .map(|index| singular_update_queue.execute(StorageCommand::Put {
+                    key: format!("key{}", index),
+                    value: format!("value{}", index),
+                }))
+                .collect();
+            for ticket in tickets {
+                assert_eq!(Ok(Status::Ok), ticket.await);
+            }
+            assert_eq!(commands.div_ceil(batch_size), handler.appends.load(Ordering::SeqCst));
+        }
+    }
+}